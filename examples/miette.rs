@@ -54,7 +54,7 @@ impl Panic {
                 let mut backtrace = String::new();
                 let trace = Backtrace::new();
                 let frames = backtrace_ext::short_frames_strict(&trace).enumerate();
-                for (idx, (frame, sub_frames)) in frames {
+                for (idx, backtrace_ext::ShortFrame { frame, sub_frames }) in frames {
                     let ip = frame.ip();
                     let _ = write!(backtrace, "\n{:4}: {:2$?}", idx, ip, HEX_WIDTH);
 
@@ -0,0 +1,75 @@
+//! Compares the time it takes to resolve a whole backtrace with resolving just what the
+//! short backtrace needs, or just a fixed number of frames, and with only capturing the
+//! addresses.
+//!
+//! Run with `cargo run --release --example resolution`.
+//!
+//! Resolving is slowest the first time, when the debuginfo gets loaded (that's the case
+//! a panic hook cares about), so each capture is timed in a fresh process.
+
+use backtrace_ext::IpScan;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+const RUNS: u32 = 20;
+/// How deep the stack is when capturing, deep enough that a resolution limit matters
+const DEPTH: usize = 128;
+/// How many frames `capture_short_limited` resolves
+const LIMIT: usize = 32;
+
+#[inline(never)]
+fn recurse(depth: usize, f: &mut dyn FnMut()) {
+    if depth == 0 {
+        f();
+    } else {
+        recurse(depth - 1, f);
+    }
+    // Keep this from becoming a loop
+    std::hint::spin_loop();
+}
+
+/// Captures one backtrace the given way, and prints how long it took in nanoseconds
+fn child(mode: &str) {
+    let mode = mode.to_owned();
+    // Run under the begin marker, like a thread spawned by std would be
+    let handle = std::thread::spawn(move || {
+        recurse(DEPTH, &mut || {
+            let start = Instant::now();
+            match &*mode {
+                "eager" => drop(backtrace::Backtrace::new()),
+                "limited" => drop(backtrace_ext::capture_short_limited(LIMIT)),
+                "ips" => drop(backtrace_ext::capture_short_ips(IpScan::Markers)),
+                "full-ips" => drop(backtrace_ext::capture_short_ips(IpScan::FullStack)),
+                _ => drop(backtrace_ext::capture_short_resolved()),
+            }
+            println!("{}", start.elapsed().as_nanos());
+        });
+    });
+    handle.join().unwrap();
+}
+
+fn average(mode: &str) -> Duration {
+    let exe = std::env::current_exe().unwrap();
+    let mut total = Duration::default();
+    for _ in 0..RUNS {
+        let output = Command::new(&exe).arg(mode).output().unwrap();
+        let nanos: u64 = String::from_utf8(output.stdout)
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        total += Duration::from_nanos(nanos);
+    }
+    total / RUNS
+}
+
+fn main() {
+    if let Some(mode) = std::env::args().nth(1) {
+        return child(&mode);
+    }
+    println!("Backtrace::new:         {:?}", average("eager"));
+    println!("capture_short_resolved: {:?}", average("short"));
+    println!("capture_short_limited:  {:?}", average("limited"));
+    println!("capture_short_ips:      {:?}", average("ips"));
+    println!("  with IpScan::FullStack: {:?}", average("full-ips"));
+}
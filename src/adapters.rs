@@ -0,0 +1,285 @@
+//! Variations on [`short_frames_strict`][].
+
+use crate::*;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+/// Like [`short_frames_strict`][], but yields the frames sorted with the given comparator.
+///
+/// This is for grouped views of a backtrace (like "all of my frames, then all the
+/// dependencies"). The sort is stable, so frames that compare equal stay in backtrace order.
+///
+/// Note that this isn't lazy: sorting needs to see every frame, so they're all collected
+/// up front.
+pub fn short_frames_sorted_by<'a, F>(
+    backtrace: &'a Backtrace,
+    mut compare: F,
+) -> impl Iterator<Item = ShortFrame<'a>>
+where
+    F: FnMut(&ShortFrame<'a>, &ShortFrame<'a>) -> Ordering,
+{
+    short_frames_sorted_by_impl(backtrace, move |a, b| {
+        compare(&a.clone().into(), &b.clone().into())
+    })
+    .map(ShortFrame::from)
+}
+
+pub(crate) fn short_frames_sorted_by_impl<'a, B, F>(
+    backtrace: &'a B,
+    compare: F,
+) -> impl Iterator<Item = (&'a B::Frame, Range<usize>)>
+where
+    B: Backtraceish,
+    F: FnMut(&(&'a B::Frame, Range<usize>), &(&'a B::Frame, Range<usize>)) -> Ordering,
+{
+    let mut frames: Vec<_> = short_frames_strict_impl(backtrace).collect();
+    frames.sort_by(compare);
+    frames.into_iter()
+}
+
+/// Like [`short_frames_strict`][], but also yields the demangled names (without the hash)
+/// of each frame's symbols, so code that matches on names doesn't have to demangle the
+/// same symbol over and over.
+///
+/// `inline_policy` picks which of the frame's symbols to get names for
+/// ([`InlinePolicy::InnermostOnly`][] gets just the primary one). Symbols without a name
+/// are skipped, so the list may be empty. Names that don't need demangling are borrowed
+/// from the backtrace.
+pub fn short_frames_with_names(
+    backtrace: &Backtrace,
+    inline_policy: InlinePolicy,
+) -> impl Iterator<Item = (&BacktraceFrame, Vec<Cow<'_, str>>)> {
+    short_frames_with_names_impl(backtrace, inline_policy)
+}
+
+pub(crate) fn short_frames_with_names_impl<B: Backtraceish>(
+    backtrace: &B,
+    inline_policy: InlinePolicy,
+) -> impl Iterator<Item = (&B::Frame, Vec<Cow<'_, str>>)> {
+    short_frames_strict_impl(backtrace).map(move |(frame, subframes)| {
+        let names = frame.symbols()[inline_policy.apply(subframes)]
+            .iter()
+            .filter_map(|symbol| {
+                let demangled = demangled_name(symbol)?;
+                match symbol.name_str() {
+                    Some(raw) if raw == demangled => Some(Cow::Borrowed(raw)),
+                    _ => Some(Cow::Owned(demangled)),
+                }
+            })
+            .collect();
+        (frame, names)
+    })
+}
+
+/// Like [`short_frames_strict`][], but yields how many of each frame's symbols are in the
+/// short backtrace instead of their range, for sizing tables and other layouts up front.
+///
+/// The count is just the length of the range [`short_frames_strict`][] would yield, so
+/// it's 0 for frames without any symbols.
+pub fn short_frames_with_counts(
+    backtrace: &Backtrace,
+) -> impl Iterator<Item = (&BacktraceFrame, usize)> {
+    short_frames_with_counts_impl(backtrace)
+}
+
+pub(crate) fn short_frames_with_counts_impl<B: Backtraceish>(
+    backtrace: &B,
+) -> impl Iterator<Item = (&B::Frame, usize)> {
+    short_frames_strict_impl(backtrace).map(|(frame, subframes)| (frame, subframes.len()))
+}
+
+/// Where a frame is in the short backtrace, from [`short_frames_with_position`][].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FramePosition {
+    /// The first (newest) frame, when there's more than one.
+    First,
+    /// Any frame between the first and the last.
+    Interior,
+    /// The last (oldest) frame, when there's more than one.
+    Last,
+    /// The only frame.
+    Only,
+}
+
+/// Like [`short_frames_strict`][], but also yields where each frame is in the short
+/// backtrace, for renderers that draw the edges differently (like a box around it).
+pub fn short_frames_with_position(
+    backtrace: &Backtrace,
+) -> impl Iterator<Item = (&BacktraceFrame, Range<usize>, FramePosition)> {
+    short_frames_with_position_impl(backtrace)
+}
+
+pub(crate) fn short_frames_with_position_impl<B: Backtraceish>(
+    backtrace: &B,
+) -> impl Iterator<Item = (&B::Frame, Range<usize>, FramePosition)> {
+    let slice = short_slice_impl(backtrace);
+    let last = slice.0.len().saturating_sub(1);
+    clamped_frames(slice)
+        .enumerate()
+        .map(move |(idx, (_, frame, subframes))| {
+            let position = match (idx == 0, idx == last) {
+                (true, true) => FramePosition::Only,
+                (true, false) => FramePosition::First,
+                (false, true) => FramePosition::Last,
+                (false, false) => FramePosition::Interior,
+            };
+            (frame, subframes, position)
+        })
+}
+
+/// Like [`short_frames_strict`][], but drops the last (oldest) `k` frames of the short
+/// backtrace, for runtimes that are known to always add exactly that many frames under
+/// your code.
+///
+/// This is blunt, but it works even on platforms where the runtime's frames don't have
+/// names to match (unlike [`short_frames_without_symbols`][]). If there are `k` or fewer
+/// frames, nothing is yielded. See [`capture_short_skip`][] for dropping the newest frames.
+pub fn short_frames_trim_tail(
+    backtrace: &Backtrace,
+    k: usize,
+) -> impl Iterator<Item = ShortFrame<'_>> {
+    short_frames_trim_tail_impl(backtrace, k).map(ShortFrame::from)
+}
+
+pub(crate) fn short_frames_trim_tail_impl<B: Backtraceish>(
+    backtrace: &B,
+    k: usize,
+) -> impl Iterator<Item = (&B::Frame, Range<usize>)> {
+    let slice = short_slice_impl(backtrace);
+    let kept = slice.0.len().saturating_sub(k);
+    clamped_frames(slice)
+        .take(kept)
+        .map(|(_, frame, range)| (frame, range))
+}
+
+/// Like [`short_frames_strict`][], but with your own marker functions in place of
+/// `rust_end_short_backtrace` and `rust_begin_short_backtrace`, for runtimes that wrap
+/// entry points in sentinel functions of their own.
+///
+/// **Note the order of the markers**: they're named for where the short backtrace starts
+/// and ends when it's printed newest to oldest, which is the *opposite* of what the std
+/// names say. So [`short_frames_strict`][] is exactly:
+///
+/// ```
+/// # let backtrace = backtrace::Backtrace::new();
+/// backtrace_ext::short_frames_strict_with_markers(
+///     &backtrace,
+///     "rust_end_short_backtrace",   // start_marker: the newer side, like the panic runtime
+///     "rust_begin_short_backtrace", // end_marker: the older side, like `main`
+/// )
+/// # ;
+/// ```
+///
+/// They're matched against the symbol names with `contains`, and everything else works
+/// exactly like [`short_frames_strict`][]: the closest pair wins, and markers in the wrong
+/// order are ignored. To clamp to calls that aren't meant as markers, see
+/// [`short_frames_between`][].
+pub fn short_frames_strict_with_markers<'a>(
+    backtrace: &'a Backtrace,
+    start_marker: &str,
+    end_marker: &str,
+) -> impl DoubleEndedIterator<Item = ShortFrame<'a>> + ExactSizeIterator {
+    short_frames_strict_with_markers_impl(backtrace, start_marker, end_marker).map(ShortFrame::from)
+}
+
+pub(crate) fn short_frames_strict_with_markers_impl<'a, B: Backtraceish>(
+    backtrace: &'a B,
+    start_marker: &str,
+    end_marker: &str,
+) -> impl DoubleEndedIterator<Item = (&'a B::Frame, Range<usize>)> + ExactSizeIterator {
+    let frames = backtrace.frames();
+    let (start, end) = find_markers_named(frames, start_marker, end_marker);
+    clamped_frames(clamp_slice(frames, start, end)).map(|(_, frame, range)| (frame, range))
+}
+
+/// Like [`short_frames_strict`][], but clamps the whole backtrace to the calls between
+/// two functions of your choosing instead of the `rust_*_short_backtrace` markers.
+///
+/// This yields everything between the newest symbol whose demangled name contains
+/// `inner`, and the closest symbol older than it whose name contains `outer`, so it's the
+/// call path from `outer` down to `inner`. Like the markers, the matching symbols aren't
+/// included themselves (even if other symbols were inlined into their frames). If `inner`
+/// isn't found, this starts at the newest frame, and if `outer` isn't found this runs to
+/// the oldest frame.
+pub fn short_frames_between<'a>(
+    backtrace: &'a Backtrace,
+    outer: &str,
+    inner: &str,
+) -> impl Iterator<Item = ShortFrame<'a>> {
+    short_frames_between_impl(backtrace, outer, inner).map(ShortFrame::from)
+}
+
+pub(crate) fn short_frames_between_impl<'a, B: Backtraceish>(
+    backtrace: &'a B,
+    outer: &str,
+    inner: &str,
+) -> impl Iterator<Item = (&'a B::Frame, Range<usize>)> {
+    let frames = backtrace.frames();
+    let mut positions = named_positions(frames);
+    let start = positions.by_ref().find(|(_, name)| name.contains(inner));
+    // Only look for `outer` after `inner`, so it's the closest one
+    let end = match start {
+        Some(_) => positions.find(|(_, name)| name.contains(outer)),
+        None => named_positions(frames).find(|(_, name)| name.contains(outer)),
+    };
+    let (start, end) = (start.map(|(pos, _)| pos), end.map(|(pos, _)| pos));
+    clamped_frames(clamp_slice(frames, start, end)).map(|(_, frame, range)| (frame, range))
+}
+
+/// The `(frame_idx, subframe_idx)` and demangled name of every symbol that has a name
+fn named_positions<F: Frameish>(
+    frames: &[F],
+) -> impl Iterator<Item = ((usize, usize), String)> + '_ {
+    frames.iter().enumerate().flat_map(|(frame_idx, frame)| {
+        frame
+            .symbols()
+            .iter()
+            .enumerate()
+            .filter_map(move |(subframe_idx, symbol)| {
+                Some(((frame_idx, subframe_idx), demangled_name(symbol)?))
+            })
+    })
+}
+
+/// How [`short_frames_with_boundary`][] clamps when a marker is the first or last symbol of
+/// its frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkerBoundary {
+    /// Start at the whole next frame (or end at the whole previous frame), since nothing
+    /// in the marker's frame is part of the short backtrace. This is what
+    /// [`short_frames_strict`][] does, and the default.
+    AdjacentFrame,
+    /// Clamp precisely at the marker's subframe, skipping the marker's frame if that leaves
+    /// none of its symbols in the short backtrace.
+    ///
+    /// Since a frame is only ever yielded with some of its symbols, this comes out the same
+    /// as [`AdjacentFrame`][Self::AdjacentFrame]: moving to the adjacent frame never adds
+    /// anything that clamping at the subframe wouldn't keep.
+    Subframe,
+}
+
+impl Default for MarkerBoundary {
+    fn default() -> Self {
+        MarkerBoundary::AdjacentFrame
+    }
+}
+
+/// Like [`short_frames_strict`][], but with a choice of what happens when a marker is the
+/// first or last symbol of its frame (see [`MarkerBoundary`][]).
+///
+/// Both choices yield the same frames, and when a marker shares its frame with symbols
+/// that are in the short backtrace they both clamp to the marker's subframe.
+pub fn short_frames_with_boundary(
+    backtrace: &Backtrace,
+    boundary: MarkerBoundary,
+) -> impl Iterator<Item = ShortFrame<'_>> {
+    short_frames_with_boundary_impl(backtrace, boundary).map(ShortFrame::from)
+}
+
+pub(crate) fn short_frames_with_boundary_impl<B: Backtraceish>(
+    backtrace: &B,
+    boundary: MarkerBoundary,
+) -> impl Iterator<Item = (&B::Frame, Range<usize>)> {
+    clamped_frames(short_slice_with_impl(backtrace, boundary))
+        .map(|(_, frame, range)| (frame, range))
+}
@@ -0,0 +1,120 @@
+//! Assertions for tests (behind the `test-util` feature).
+
+use crate::*;
+
+/// Asserts that the short backtrace has a symbol whose name contains some text.
+///
+/// This uses [`find_frame_by_name`][], so the same rules about what matches apply.
+/// If nothing matches, the panic message includes the whole short backtrace so you can
+/// see what was there instead.
+///
+/// ```
+/// #[inline(never)]
+/// fn check() {
+///     let backtrace = backtrace::Backtrace::new();
+///     backtrace_ext::assert_backtrace_contains!(backtrace, "check");
+/// }
+/// check();
+/// ```
+#[macro_export]
+macro_rules! assert_backtrace_contains {
+    ($backtrace:expr, $name:expr $(,)?) => {
+        $crate::__assert_backtrace_contains(&$backtrace, $name)
+    };
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn __assert_backtrace_contains(backtrace: &Backtrace, name: &str) {
+    assert_backtrace_contains_impl(backtrace, name)
+}
+
+#[track_caller]
+pub(crate) fn assert_backtrace_contains_impl<B: Backtraceish>(backtrace: &B, name: &str) {
+    if query::find_frame_by_name_impl(backtrace, name).is_none() {
+        panic!(
+            "no symbol in the short backtrace contains `{}`, the short backtrace was:{}",
+            name,
+            ShortBacktraceFormatter::new().format_impl(backtrace)
+        );
+    }
+}
+
+/// The environment variable that makes [`assert_golden_backtrace`][] overwrite the golden
+/// files instead of comparing against them.
+pub const BLESS_VAR: &str = "BACKTRACE_EXT_BLESS";
+
+/// Asserts that the short backtrace goes through the same functions as the one stored in
+/// a golden file, for tests like "the crash path for bug #123 should stay the same".
+///
+/// The backtrace is stored as its [`short_backtrace_names`][], one per line, so addresses,
+/// hashes, source locations and [gunk][GUNK_SYMBOLS] don't make it fragile. If the file
+/// doesn't exist yet (or [`BLESS_VAR`][] is set to anything but `0`), it's written instead
+/// of compared against, so check the new file in after reviewing it. If the names don't
+/// match, the panic message has a diff of them: lines starting with `-` are only in the
+/// golden file, and lines starting with `+` are only in the new backtrace.
+#[track_caller]
+pub fn assert_golden_backtrace(backtrace: &Backtrace, path: impl AsRef<Path>) {
+    let bless = std::env::var(BLESS_VAR).map_or(false, |var| !var.is_empty() && var != "0");
+    assert_golden_backtrace_impl(backtrace, path.as_ref(), bless)
+}
+
+#[track_caller]
+pub(crate) fn assert_golden_backtrace_impl<B: Backtraceish>(
+    backtrace: &B,
+    path: &Path,
+    bless: bool,
+) {
+    let names = summary::short_backtrace_names_impl(backtrace);
+    let golden = match std::fs::read_to_string(path) {
+        Ok(golden) if !bless => golden,
+        _ => {
+            let mut contents = names.join("\n");
+            contents.push('\n');
+            if let Err(e) = std::fs::write(path, contents) {
+                panic!("couldn't write golden backtrace {}: {}", path.display(), e);
+            }
+            return;
+        }
+    };
+    let expected: Vec<&str> = golden.lines().collect();
+    if expected != names {
+        panic!(
+            "short backtrace doesn't match golden backtrace {} (set {}=1 to update it):\n{}",
+            path.display(),
+            BLESS_VAR,
+            diff_lines(&expected, &names)
+        );
+    }
+}
+
+/// A line-by-line diff, with ` `, `-`, or `+` at the start of each line
+fn diff_lines(old: &[&str], new: &[String]) -> String {
+    // lcs[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut lines = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(format!("  {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(format!("- {}", old[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+ {}", new[j]));
+            j += 1;
+        }
+    }
+    lines.join("\n")
+}
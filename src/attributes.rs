@@ -0,0 +1,44 @@
+//! Flattening a short backtrace into key-value attributes.
+
+use crate::*;
+
+/// The prefix of the keys produced by [`short_backtrace_attributes`][].
+pub const ATTRIBUTE_PREFIX: &str = "exception.stacktrace.frame";
+
+/// Gets the short backtrace as flat `(key, value)` attributes, like the ones you'd attach
+/// to an OpenTelemetry span.
+///
+/// Every symbol of the short backtrace gets a number (newest first, counting inlined
+/// symbols separately), and produces up to three attributes:
+///
+/// ```text
+/// exception.stacktrace.frame.0.function = app::inner
+/// exception.stacktrace.frame.0.file = src/inner.rs
+/// exception.stacktrace.frame.0.line = 10
+/// ```
+///
+/// Keys are only emitted for the information that's actually available, so an
+/// unresolved frame still takes up a number but has no attributes. This doesn't depend
+/// on any OpenTelemetry crate, so convert the pairs into whatever your exporter wants.
+pub fn short_backtrace_attributes(backtrace: &Backtrace) -> Vec<(String, String)> {
+    short_backtrace_attributes_impl(backtrace)
+}
+
+pub(crate) fn short_backtrace_attributes_impl<B: Backtraceish>(
+    backtrace: &B,
+) -> Vec<(String, String)> {
+    let mut attributes = vec![];
+    for (idx, frame) in resolved::resolved_short_symbols_impl(backtrace).enumerate() {
+        let key = |field: &str| format!("{}.{}.{}", ATTRIBUTE_PREFIX, idx, field);
+        if let Some(name) = frame.name {
+            attributes.push((key("function"), name));
+        }
+        if let Some(file) = frame.file {
+            attributes.push((key("file"), file.display().to_string()));
+        }
+        if let Some(line) = frame.line {
+            attributes.push((key("line"), line.to_string()));
+        }
+    }
+    attributes
+}
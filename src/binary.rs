@@ -0,0 +1,157 @@
+//! A compact binary encoding of short backtraces, for storing lots of them.
+
+use crate::*;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::path::PathBuf;
+
+/// The first byte of every encoding, so the format can change later.
+const VERSION: u8 = 1;
+
+/// Encodes the short backtrace (as the [`ResolvedFrame`][]s of
+/// [`resolved_short_symbols`][]) into a compact binary blob, which
+/// [`short_backtrace_from_bytes`][] turns back into the same frames.
+///
+/// This is meant for crash telemetry backends that store so many backtraces that JSON is
+/// too bulky. Every distinct name and file is only stored once, and numbers are stored as
+/// variable-length integers. Files that aren't valid UTF-8 are stored lossily. The
+/// encoding starts with a version byte, and a later version of this crate might change it
+/// (but will still decode older blobs).
+pub fn short_backtrace_to_bytes(backtrace: &Backtrace) -> Vec<u8> {
+    short_backtrace_to_bytes_impl(backtrace)
+}
+
+/// Decodes a blob made by [`short_backtrace_to_bytes`][] back into its frames.
+///
+/// Returns `None` if the blob is corrupt, truncated, or from a newer version of this crate.
+pub fn short_backtrace_from_bytes(bytes: &[u8]) -> Option<Vec<ResolvedFrame>> {
+    let mut reader = Reader { bytes };
+    if reader.byte()? != VERSION {
+        return None;
+    }
+    let mut strings = vec![];
+    for _ in 0..reader.varint()? {
+        let len = reader.len()?;
+        let string = std::str::from_utf8(reader.take(len)?).ok()?;
+        strings.push(string.to_owned());
+    }
+    // Each string refers to the index in the table plus 1, with 0 for no string
+    let string = |reader: &mut Reader<'_>| -> Option<Option<String>> {
+        match reader.len()? {
+            0 => Some(None),
+            id => strings.get(id - 1).cloned().map(Some),
+        }
+    };
+
+    let mut frames = vec![];
+    for _ in 0..reader.varint()? {
+        let index = reader.len()?;
+        let ip = reader.len()?;
+        let name = string(&mut reader)?;
+        let file = string(&mut reader)?.map(PathBuf::from);
+        let line = match reader.varint()? {
+            0 => None,
+            line => Some(u32::try_from(line - 1).ok()?),
+        };
+        frames.push(ResolvedFrame {
+            index,
+            ip,
+            name,
+            file,
+            line,
+        });
+    }
+    if !reader.bytes.is_empty() {
+        return None;
+    }
+    Some(frames)
+}
+
+pub(crate) fn short_backtrace_to_bytes_impl<B: Backtraceish>(backtrace: &B) -> Vec<u8> {
+    let frames: Vec<_> = resolved::resolved_short_symbols_impl(backtrace).collect();
+
+    let mut strings = vec![];
+    let mut ids = HashMap::new();
+    let mut intern = |string: Option<String>| -> u64 {
+        match string {
+            Some(string) => *ids.entry(string.clone()).or_insert_with(|| {
+                strings.push(string);
+                strings.len() as u64
+            }),
+            None => 0,
+        }
+    };
+    let mut entries = vec![];
+    for frame in frames {
+        let name = intern(frame.name);
+        let file = intern(frame.file.map(|file| file.to_string_lossy().into_owned()));
+        entries.push([
+            frame.index as u64,
+            frame.ip as u64,
+            name,
+            file,
+            frame.line.map_or(0, |line| line as u64 + 1),
+        ]);
+    }
+
+    let mut out = vec![VERSION];
+    write_varint(&mut out, strings.len() as u64);
+    for string in &strings {
+        write_varint(&mut out, string.len() as u64);
+        out.extend_from_slice(string.as_bytes());
+    }
+    write_varint(&mut out, entries.len() as u64);
+    for entry in &entries {
+        for &value in entry {
+            write_varint(&mut out, value);
+        }
+    }
+    out
+}
+
+/// Writes a LEB128 varint: 7 bits at a time, with the high bit set on every byte but the last.
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Option<u8> {
+        let (&byte, rest) = self.bytes.split_first()?;
+        self.bytes = rest;
+        Some(byte)
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if len > self.bytes.len() {
+            return None;
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Some(taken)
+    }
+
+    fn varint(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f).checked_shl(shift)?;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    /// A varint that has to fit in a usize
+    fn len(&mut self) -> Option<usize> {
+        usize::try_from(self.varint()?).ok()
+    }
+}
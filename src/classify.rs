@@ -0,0 +1,169 @@
+//! Sorting frames into user code, the standard library, and dependencies.
+
+use crate::*;
+
+/// The crates that make up Rust's standard library and runtime.
+pub const STD_CRATES: &[&str] = &[
+    "std",
+    "core",
+    "alloc",
+    "proc_macro",
+    "test",
+    "panic_unwind",
+    "panic_abort",
+    "std_detect",
+];
+
+/// Who a frame (or symbol) belongs to, according to [`classify_frame`][].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrameKind {
+    /// Code from the crate (or module path) you consider to be yours.
+    User,
+    /// Code from the standard library (see [`STD_CRATES`][]).
+    Std,
+    /// Code from any other crate.
+    Dependency,
+    /// Code that isn't Rust (like a C library), because its name didn't demangle as a Rust
+    /// symbol. Note that this includes Rust functions that are `#[no_mangle]`.
+    Foreign,
+    /// No name, so we can't tell.
+    Unknown,
+}
+
+/// Classifies a single symbol.
+///
+/// `user_prefix` is the name of your crate (like `myapp`) or a path inside of it
+/// (like `myapp::server`). Matching is done on whole path segments of the demangled
+/// name, and trait methods (`<myapp::Foo as core::fmt::Debug>::fmt`) count as part of
+/// the type's crate.
+pub fn classify_symbol(symbol: &BacktraceSymbol, user_prefix: &str) -> FrameKind {
+    classify_symbol_impl(symbol, user_prefix)
+}
+
+/// Classifies a frame yielded by [`short_frames_strict`][], looking at all of the symbols
+/// in `sub_frames` (see [`classify_symbol`][]).
+///
+/// If the frame has several symbols (because of inlining) the "most interesting" one
+/// wins: [`User`][FrameKind::User] over [`Dependency`][FrameKind::Dependency] over
+/// [`Std`][FrameKind::Std] over [`Foreign`][FrameKind::Foreign].
+pub fn classify_frame(
+    frame: &BacktraceFrame,
+    sub_frames: Range<usize>,
+    user_prefix: &str,
+) -> FrameKind {
+    classify_frame_impl(frame, sub_frames, user_prefix)
+}
+
+pub(crate) fn classify_symbol_impl<S: Symbolish>(symbol: &S, user_prefix: &str) -> FrameKind {
+    match demangled_name(symbol) {
+        Some(_) if !symbol.is_rust() => FrameKind::Foreign,
+        Some(name) => classify_name(&name, user_prefix),
+        None => FrameKind::Unknown,
+    }
+}
+
+pub(crate) fn classify_name(name: &str, user_prefix: &str) -> FrameKind {
+    if names::matches_path_prefix(name, user_prefix) {
+        return FrameKind::User;
+    }
+    match names::crate_name(name) {
+        Some(krate) if STD_CRATES.contains(&krate) => FrameKind::Std,
+        Some(_) => FrameKind::Dependency,
+        None => FrameKind::Unknown,
+    }
+}
+
+pub(crate) fn classify_frame_impl<F: Frameish>(
+    frame: &F,
+    sub_frames: Range<usize>,
+    user_prefix: &str,
+) -> FrameKind {
+    let kinds = frame.symbols()[sub_frames]
+        .iter()
+        .map(|symbol| classify_symbol_impl(symbol, user_prefix));
+    let mut result = FrameKind::Unknown;
+    for kind in kinds {
+        result = match (result, kind) {
+            (FrameKind::User, _) | (_, FrameKind::User) => FrameKind::User,
+            (FrameKind::Dependency, _) | (_, FrameKind::Dependency) => FrameKind::Dependency,
+            (FrameKind::Std, _) | (_, FrameKind::Std) => FrameKind::Std,
+            (FrameKind::Foreign, _) | (_, FrameKind::Foreign) => FrameKind::Foreign,
+            _ => FrameKind::Unknown,
+        };
+    }
+    result
+}
+
+/// A run of adjacent frames of the short backtrace from the same crate, found by
+/// [`short_backtrace_tree`][].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CrateGroup {
+    /// The name of the crate, or `None` for frames that don't have a Rust name
+    /// (unresolved frames and code that isn't Rust).
+    pub crate_name: Option<String>,
+    /// The indices of the frames in the short backtrace.
+    pub frames: Range<usize>,
+}
+
+/// Groups the frames of the short backtrace into runs of adjacent frames from the same
+/// crate, for a view of the backtrace where each crate can be collapsed.
+///
+/// A frame belongs to the crate of its outermost named symbol (the function the frame is
+/// actually in, rather than something inlined into it), found the same way as
+/// [`classify_symbol`][] does. The groups are in backtrace order and cover every frame, so
+/// the same crate can show up in several groups if the backtrace goes in and out of it.
+pub fn short_backtrace_tree(backtrace: &Backtrace) -> Vec<CrateGroup> {
+    short_backtrace_tree_impl(backtrace)
+}
+
+pub(crate) fn short_backtrace_tree_impl<B: Backtraceish>(backtrace: &B) -> Vec<CrateGroup> {
+    let mut groups: Vec<CrateGroup> = vec![];
+    for (idx, (frame, subframes)) in short_frames_strict_impl(backtrace).enumerate() {
+        let crate_name = frame_crate(frame, subframes);
+        match groups.last_mut() {
+            Some(group) if group.crate_name == crate_name => group.frames.end = idx + 1,
+            _ => groups.push(CrateGroup {
+                crate_name,
+                frames: idx..idx + 1,
+            }),
+        }
+    }
+    groups
+}
+
+/// The crate of the frame's outermost named symbol, if it's Rust
+pub(crate) fn frame_crate<F: Frameish>(frame: &F, subframes: Range<usize>) -> Option<String> {
+    frame.symbols()[subframes]
+        .iter()
+        .rev()
+        .find(|symbol| symbol.has_name())
+        .filter(|symbol| symbol.is_rust())
+        .and_then(demangled_name)
+        .and_then(|name| names::crate_name(&name).map(str::to_owned))
+}
+
+/// Checks whether a frame looks like it came from a macro expansion (like a derive or
+/// some async desugaring), going by the filenames of the symbols in `sub_frames`.
+///
+/// This is a best-effort heuristic: rustc names code that doesn't come from a real file
+/// with something in angle brackets (like `<::core::macros::panic macros>` or
+/// `<proc-macro source code>`), so a frame counts as a macro frame if all of its symbols
+/// that have a filename have one like that (and at least one does). Code generated by
+/// macros that rustc attributes to the macro's call site can't be told apart from
+/// normal code.
+pub fn is_macro_frame(frame: &BacktraceFrame, sub_frames: Range<usize>) -> bool {
+    is_macro_frame_impl(frame, sub_frames)
+}
+
+pub(crate) fn is_macro_frame_impl<F: Frameish>(frame: &F, sub_frames: Range<usize>) -> bool {
+    let mut files = frame.symbols()[sub_frames]
+        .iter()
+        .filter_map(|symbol| symbol.filename())
+        .peekable();
+    files.peek().is_some() && files.all(is_macro_file)
+}
+
+fn is_macro_file(file: &Path) -> bool {
+    let file = file.to_string_lossy();
+    file.starts_with('<') && file.ends_with('>')
+}
@@ -0,0 +1,117 @@
+//! Building a [`Backtrace`][] out of the raw frames from [`backtrace::trace`][].
+
+use crate::*;
+
+/// Accumulates the raw [`Frame`][]s handed out by [`backtrace::trace`][] so they can
+/// be resolved and clamped afterwards.
+///
+/// `trace` is the cheap way to walk the stack, but its frames don't have symbols yet,
+/// which the clamping logic needs. Push the frames in the `trace` callback and then
+/// call [`FrameCollector::finish`][] to get a resolved [`Backtrace`][] that works with
+/// [`short_frames_strict`][] and everything else in this crate.
+///
+/// ```
+/// let mut collector = backtrace_ext::FrameCollector::new();
+/// backtrace::trace(|frame| {
+///     collector.push(frame);
+///     true
+/// });
+/// let backtrace = collector.finish();
+/// for frame in backtrace_ext::short_frames_strict(&backtrace) {
+///     let _ = frame.symbols();
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FrameCollector {
+    frames: Vec<BacktraceFrame>,
+}
+
+impl FrameCollector {
+    /// Makes an empty collector.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a frame. Frames should be pushed in the order `trace` yields them
+    /// (newest first).
+    pub fn push(&mut self, frame: &Frame) {
+        self.frames.push(BacktraceFrame::from(frame.clone()));
+    }
+
+    /// How many frames have been recorded so far.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Whether no frames have been recorded yet.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Resolves the symbols of all the recorded frames and returns them as a [`Backtrace`][].
+    pub fn finish(self) -> Backtrace {
+        let mut backtrace = Backtrace::from(self.frames);
+        backtrace.resolve();
+        backtrace
+    }
+}
+
+/// Makes a collector out of frames you already have, newest first.
+///
+/// This is for frames captured some other way than calling [`backtrace::trace`][] on the
+/// current thread, like a watchdog that suspends a stuck thread and walks its stack
+/// with platform-specific APIs. This crate doesn't do the suspending, it just clamps the
+/// result. Keep in mind that a thread that isn't panicking won't have the
+/// `rust_end_short_backtrace` marker, so its short backtrace starts at the newest frame.
+impl From<Vec<Frame>> for FrameCollector {
+    fn from(frames: Vec<Frame>) -> Self {
+        FrameCollector {
+            frames: frames.into_iter().map(BacktraceFrame::from).collect(),
+        }
+    }
+}
+
+/// Captures a backtrace, leaving out the newest `skip` frames of the short backtrace.
+///
+/// This is for helpers (like a logging function) that capture a backtrace on behalf of
+/// their caller, and don't want to show up in it themselves. The frame of
+/// `capture_short_skip` is never included.
+///
+/// The skip applies *after* clamping, so `skip` counts frames of the short backtrace
+/// (what [`short_frames_strict`][] yields), not of the whole stack. The marker frames are
+/// kept, so [`short_frames_strict`][] still works on the result. If a skipped frame also
+/// contains a marker (because they got inlined together) it can't be dropped
+/// without losing the marker, so its remaining symbols will still show up.
+#[inline(never)]
+pub fn capture_short_skip(skip: usize) -> Backtrace {
+    let backtrace = Backtrace::new();
+    // `Backtrace::new` already hides its own frames, so hide ours the same way
+    let own_address = capture_short_skip as *const () as usize;
+    let frames = backtrace.frames();
+    let start = frames
+        .iter()
+        .position(|frame| frame.symbol_address() as usize == own_address)
+        .map_or(0, |idx| idx + 1);
+    let backtrace = Backtrace::from(frames[start..].to_vec());
+
+    let skipped = skipped_frames_impl(&backtrace, skip);
+    let frames = backtrace
+        .frames()
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| !skipped.contains(idx))
+        .map(|(_, frame)| frame.clone())
+        .collect::<Vec<_>>();
+    Backtrace::from(frames)
+}
+
+/// The indices (in `backtrace.frames()`) of the frames to drop to skip the first `skip`
+/// frames of the short backtrace.
+pub(crate) fn skipped_frames_impl<B: Backtraceish>(backtrace: &B, skip: usize) -> Vec<usize> {
+    short_frames_indexed_impl(backtrace)
+        .take(skip)
+        // Frames that were only partially in the short backtrace have a marker in them
+        .filter(|(_, frame, subframes)| subframes.len() == frame.symbols().len())
+        .map(|(idx, _, _)| idx)
+        .collect()
+}
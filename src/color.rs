@@ -0,0 +1,108 @@
+//! Coloring of formatted backtraces with ANSI escape codes.
+
+use crate::*;
+
+/// Resets all ANSI styles.
+pub(crate) const RESET: &str = "\x1b[0m";
+
+/// The colors used by a [`ShortBacktraceFormatter`][] with
+/// [`colorize`][ShortBacktraceFormatter::colorize] on.
+///
+/// Each color is the parameters of an ANSI SGR escape code (the part between `\x1b[`
+/// and `m`), so `"32"` is green and `"1;31"` is bold red. An empty string leaves that
+/// kind of frame uncolored. Frames are sorted into kinds with [`classify_frame`][],
+/// using the formatter's [`user_prefix`][ShortBacktraceFormatter::user_prefix].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColorMap {
+    /// Your code. Defaults to green (`32`).
+    pub user: String,
+    /// The standard library. Defaults to blue (`34`).
+    pub std: String,
+    /// Your dependencies. Defaults to dim (`2`).
+    pub dependency: String,
+    /// Code that isn't Rust. Defaults to magenta (`35`).
+    pub foreign: String,
+    /// Frames we couldn't classify. Defaults to no color.
+    pub unknown: String,
+    /// Added on top of the frame's color for the frame that called `panic!`
+    /// (the first real frame after the panic runtime). Defaults to bold (`1`).
+    pub panic_origin: String,
+    /// Added on top of the frame's color for frames matching a
+    /// [highlight][ShortBacktraceFormatter::highlight_substring] pattern. Defaults to
+    /// reverse video (`7`).
+    pub highlight: String,
+}
+
+impl Default for ColorMap {
+    fn default() -> Self {
+        ColorMap {
+            user: "32".to_owned(),
+            std: "34".to_owned(),
+            dependency: "2".to_owned(),
+            foreign: "35".to_owned(),
+            unknown: String::new(),
+            panic_origin: "1".to_owned(),
+            highlight: "7".to_owned(),
+        }
+    }
+}
+
+impl ColorMap {
+    /// The escape code that starts the color of a frame, if it has one.
+    pub(crate) fn escape(
+        &self,
+        kind: FrameKind,
+        is_panic_origin: bool,
+        is_highlighted: bool,
+    ) -> Option<String> {
+        let color = match kind {
+            FrameKind::User => &self.user,
+            FrameKind::Std => &self.std,
+            FrameKind::Dependency => &self.dependency,
+            FrameKind::Foreign => &self.foreign,
+            FrameKind::Unknown => &self.unknown,
+        };
+        let mut params: Vec<&str> = Vec::new();
+        if is_panic_origin {
+            params.push(&self.panic_origin);
+        }
+        if is_highlighted {
+            params.push(&self.highlight);
+        }
+        params.push(color);
+        let params: Vec<&str> = params.into_iter().filter(|p| !p.is_empty()).collect();
+        if params.is_empty() {
+            None
+        } else {
+            Some(format!("\x1b[{}m", params.join(";")))
+        }
+    }
+}
+
+/// Finds the frame that called `panic!`: the first frame after the panic runtime
+/// (see [`PANIC_SYMBOLS`][]) that has a name and isn't [gunk][GUNK_SYMBOLS].
+///
+/// Returns its index in `backtrace.frames()`.
+pub(crate) fn panic_origin_impl<'a, F: Frameish + 'a>(
+    frames: impl Iterator<Item = (usize, &'a F, Range<usize>)>,
+) -> Option<usize> {
+    let mut origin = None;
+    let mut in_panic = false;
+    for (frame_idx, frame, subframes) in frames {
+        let names: Vec<String> = frame.symbols()[subframes]
+            .iter()
+            .filter_map(demangled_name)
+            .collect();
+        if names
+            .iter()
+            .any(|name| PANIC_SYMBOLS.iter().any(|panic| name.contains(panic)))
+        {
+            in_panic = true;
+            origin = None;
+        } else if in_panic && origin.is_none() && names.iter().any(|name| !gunk::is_gunk_name(name))
+        {
+            origin = Some(frame_idx);
+        }
+    }
+    origin
+}
@@ -0,0 +1,96 @@
+//! Backtraces with extra information attached, for crash reports.
+
+use crate::*;
+use std::fmt::Write;
+
+/// A backtrace along with key-value pairs of context about where it came from, like a
+/// request ID, a user ID, or a build hash. Together they make a minimal crash report.
+///
+/// The context is kept in the order it was added, and keys don't have to be unique.
+///
+/// ```
+/// use backtrace_ext::{ContextualBacktrace, ShortBacktraceFormatter};
+///
+/// let report = ContextualBacktrace::new(backtrace::Backtrace::new())
+///     .with_context("request", "4f2a")
+///     .with_context("build", env!("CARGO_PKG_VERSION"));
+/// eprintln!("{}", report.format(&ShortBacktraceFormatter::new()));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ContextualBacktrace {
+    backtrace: Backtrace,
+    context: Vec<(String, String)>,
+}
+
+impl ContextualBacktrace {
+    /// Wraps a backtrace, with no context yet.
+    pub fn new(backtrace: Backtrace) -> Self {
+        ContextualBacktrace {
+            backtrace,
+            context: vec![],
+        }
+    }
+
+    /// Adds a key-value pair to the context.
+    pub fn with_context(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.context.push((key.into(), value.into()));
+        self
+    }
+
+    /// The backtrace.
+    pub fn backtrace(&self) -> &Backtrace {
+        &self.backtrace
+    }
+
+    /// The context, in the order it was added.
+    pub fn context(&self) -> &[(String, String)] {
+        &self.context
+    }
+
+    /// Formats the context as a block of `key: value` lines, followed by the short
+    /// backtrace as formatted by `formatter`.
+    ///
+    /// Like the formatter's output, every line starts with a newline.
+    pub fn format(&self, formatter: &ShortBacktraceFormatter) -> String {
+        format_contextual_impl(&self.context, formatter, &self.backtrace)
+    }
+
+    /// Gets the report as JSON Lines: a `{"context":{...}}` object with the context,
+    /// followed by the lines of [`short_backtrace_jsonl`][].
+    ///
+    /// If a key was added more than once, it's in the object more than once too.
+    pub fn jsonl(&self) -> impl Iterator<Item = String> + '_ {
+        contextual_jsonl_impl(&self.context, &self.backtrace)
+    }
+}
+
+pub(crate) fn format_contextual_impl<B: Backtraceish>(
+    context: &[(String, String)],
+    formatter: &ShortBacktraceFormatter,
+    backtrace: &B,
+) -> String {
+    let mut out = String::new();
+    for (key, value) in context {
+        let _ = write!(out, "\n{}: {}", key, value);
+    }
+    // Writing to a String can't fail
+    let _ = formatter.write_impl(&mut out, backtrace);
+    out
+}
+
+pub(crate) fn contextual_jsonl_impl<'a, B: Backtraceish>(
+    context: &[(String, String)],
+    backtrace: &'a B,
+) -> impl Iterator<Item = String> + 'a {
+    let mut line = String::from("{\"context\":{");
+    for (idx, (key, value)) in context.iter().enumerate() {
+        if idx != 0 {
+            line.push(',');
+        }
+        json::write_json_str(&mut line, Some(key));
+        line.push(':');
+        json::write_json_str(&mut line, Some(value));
+    }
+    line.push_str("}}");
+    std::iter::once(line).chain(json::short_backtrace_jsonl_impl(backtrace))
+}
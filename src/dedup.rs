@@ -0,0 +1,107 @@
+//! Suppressing backtraces that were already printed.
+
+use crate::*;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Prints each distinct short backtrace only once, for panic hooks that can fire over and
+/// over with the same backtrace (like a panic in a retry loop).
+///
+/// Two backtraces count as the same if [`short_backtrace_names`][] gives the same names for
+/// both, so the same call path is recognized even if it was loaded at a different address.
+/// How unresolved symbols are compared is up to [`unknown_frames`][Self::unknown_frames].
+/// The printer remembers every backtrace it has seen, and can be shared between threads
+/// (like in a `static` used by a panic hook).
+///
+/// ```
+/// use backtrace_ext::DedupPrinter;
+///
+/// let printer = DedupPrinter::new().report_repeats(true);
+/// for _ in 0..3 {
+///     let backtrace = backtrace::Backtrace::new();
+///     // The first time this is the backtrace, then "(backtrace repeated N times)"
+///     printer.print(&backtrace);
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct DedupPrinter {
+    formatter: ShortBacktraceFormatter,
+    report_repeats: bool,
+    unknown_frames: UnknownFramePolicy,
+    seen: Mutex<HashMap<Vec<String>, usize>>,
+}
+
+impl DedupPrinter {
+    /// Makes a printer that hasn't seen any backtraces yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the formatter used for backtraces that haven't been seen before
+    /// (default: [`ShortBacktraceFormatter::new`][]).
+    pub fn formatter(mut self, formatter: ShortBacktraceFormatter) -> Self {
+        self.formatter = formatter;
+        self
+    }
+
+    /// Whether a repeated backtrace gets a `(backtrace repeated N times)` line instead of
+    /// nothing at all (default: false). `N` is how many times it was repeated after it was
+    /// first printed.
+    pub fn report_repeats(mut self, report_repeats: bool) -> Self {
+        self.report_repeats = report_repeats;
+        self
+    }
+
+    /// How unresolved symbols are treated when comparing backtraces
+    /// (default: [`UnknownFramePolicy::Skip`][]).
+    pub fn unknown_frames(mut self, unknown_frames: UnknownFramePolicy) -> Self {
+        self.unknown_frames = unknown_frames;
+        self
+    }
+
+    /// Formats the backtrace if it hasn't been seen before, and remembers it.
+    ///
+    /// For a repeated backtrace this returns `None`, or the repeat line if
+    /// [`report_repeats`][Self::report_repeats] is set. Like the formatter's output, the
+    /// repeat line starts with a newline.
+    pub fn format(&self, backtrace: &Backtrace) -> Option<String> {
+        self.format_impl(backtrace)
+    }
+
+    /// Prints the result of [`format`][Self::format] (if any) to stderr, with a newline.
+    pub fn print(&self, backtrace: &Backtrace) {
+        if let Some(out) = self.format(backtrace) {
+            eprintln!("{}", out);
+        }
+    }
+
+    /// Forgets every backtrace seen so far, so they'll all be printed again.
+    pub fn clear(&self) {
+        self.seen().clear();
+    }
+
+    pub(crate) fn format_impl<B: Backtraceish>(&self, backtrace: &B) -> Option<String> {
+        let key = summary::fingerprint_names_impl(backtrace, self.unknown_frames);
+        let count = {
+            let mut seen = self.seen();
+            let count = seen.entry(key).or_insert(0);
+            *count += 1;
+            *count
+        };
+        if count == 1 {
+            Some(self.formatter.format_impl(backtrace))
+        } else if self.report_repeats {
+            Some(format!("\n(backtrace repeated {} times)", count - 1))
+        } else {
+            None
+        }
+    }
+
+    fn seen(&self) -> std::sync::MutexGuard<'_, HashMap<Vec<String>, usize>> {
+        // This is likely to be used in a panic hook, so don't give up just because
+        // some other thread panicked while holding the lock
+        self.seen
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
@@ -0,0 +1,258 @@
+//! Capturing now and resolving later, for async code.
+
+use crate::*;
+use std::cell::RefCell;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// How many frames [`DeferredBacktrace::resolve_short`][] resolves at once.
+pub(crate) const RESOLVE_CHUNK: usize = 8;
+
+/// A backtrace that has been captured but not resolved, made with [`capture_deferred`][].
+///
+/// Walking the stack is fast, but looking up the symbols (which [`short_frames_strict`][]
+/// needs) can take a long time, which you don't want to do while polling a future.
+/// This lets you do the fast part in place, and the slow part somewhere else: either call
+/// [`resolve`][Self::resolve] wherever your runtime lets you block (like tokio's
+/// `spawn_blocking`), or await [`resolve_in_background`][Self::resolve_in_background].
+#[derive(Debug, Clone)]
+pub struct DeferredBacktrace {
+    backtrace: Backtrace,
+}
+
+/// Captures a backtrace without resolving it (see [`DeferredBacktrace`][]).
+pub fn capture_deferred() -> DeferredBacktrace {
+    DeferredBacktrace {
+        backtrace: Backtrace::new_unresolved(),
+    }
+}
+
+/// Captures a backtrace and resolves only the frames the short backtrace needs (see
+/// [`DeferredBacktrace::resolve_short`][]).
+///
+/// This gives the same short backtrace as [`Backtrace::new`][], but doesn't look up the
+/// symbols of the runtime's frames at the bottom of the stack.
+pub fn capture_short_resolved() -> Backtrace {
+    capture_deferred().resolve_short()
+}
+
+/// Captures a backtrace and resolves at most `max_resolved` of the frames the short
+/// backtrace needs (see [`DeferredBacktrace::resolve_short_limited`][]).
+///
+/// This bounds how long capturing can take on a very deep stack, like in a panic hook
+/// after runaway recursion.
+pub fn capture_short_limited(max_resolved: usize) -> Backtrace {
+    capture_deferred().resolve_short_limited(max_resolved)
+}
+
+impl DeferredBacktrace {
+    /// Resolves the backtrace, blocking until it's done.
+    pub fn resolve(mut self) -> Backtrace {
+        self.backtrace.resolve();
+        self.backtrace
+    }
+
+    /// Resolves the symbols of at most the first (newest) `max_frames` frames of the
+    /// backtrace, leaving the rest unresolved.
+    ///
+    /// On some platforms looking up the symbols of certain addresses is pathologically slow,
+    /// so this lets something like a panic hook bound how much work it does. The frames
+    /// that weren't resolved have no symbols, so they get printed as `<unresolved>` (see
+    /// also [`ShortBacktraceFormatter::collapse_unresolved_runs`][]). If the
+    /// `rust_begin_short_backtrace` marker ends up among them, the short backtrace just
+    /// runs to the end.
+    pub fn resolve_at_most(self, max_frames: usize) -> Backtrace {
+        let mut frames: Vec<BacktraceFrame> = self.backtrace.into();
+        let unresolved = frames.split_off(max_frames.min(frames.len()));
+        let mut resolved = Backtrace::from(frames);
+        resolved.resolve();
+        let mut frames: Vec<BacktraceFrame> = resolved.into();
+        frames.extend(unresolved);
+        Backtrace::from(frames)
+    }
+
+    /// Resolves the frames needed to find the short backtrace, leaving the frames after it
+    /// unresolved.
+    ///
+    /// Frames are resolved newest first, a few at a time, until one has the
+    /// `rust_begin_short_backtrace` marker. Everything older than that is startup code that
+    /// [`short_frames_strict`][] would throw away anyway, so those frames are left without
+    /// symbols (apart from the few resolved along with the marker). Skipping them saves
+    /// the most the first time a process resolves a backtrace, since their debuginfo never
+    /// needs to be loaded. The frames before the short
+    /// backtrace still have to be resolved, since that's the only way to find the
+    /// `rust_end_short_backtrace` marker.
+    ///
+    /// If there's no begin marker, every frame gets resolved. The one difference from
+    /// [`resolve`][Self::resolve] is a backtrace with an end marker *after* the first begin
+    /// marker (which [`short_frames_strict`][] treats as having no markers at all): that
+    /// end marker isn't resolved, so the frames are clamped to the begin marker instead.
+    pub fn resolve_short(self) -> Backtrace {
+        self.resolve_short_limited(usize::MAX)
+    }
+
+    /// Like [`resolve_short`][Self::resolve_short], but resolves at most `max_resolved`
+    /// frames, leaving the rest unresolved.
+    ///
+    /// The frames are picked the same way: newest first, stopping at the
+    /// `rust_begin_short_backtrace` marker. The short backtrace is at the top of the stack,
+    /// under only a few frames of panic machinery, so these are the frames most likely to
+    /// be shown, and the ones older than the marker are never needed. If the budget runs
+    /// out first, the rest of the frames (including the marker) get no symbols, and print
+    /// as `<unresolved>` like with [`resolve_at_most`][Self::resolve_at_most]. A budget
+    /// smaller than the frames above the `rust_end_short_backtrace` marker means the
+    /// marker won't be found, so the short backtrace starts at the top of the stack.
+    pub fn resolve_short_limited(self, max_resolved: usize) -> Backtrace {
+        let mut rest: Vec<BacktraceFrame> = self.backtrace.into();
+        let mut resolved = Vec::with_capacity(rest.len());
+        let mut budget = max_resolved;
+        while !rest.is_empty() && budget > 0 {
+            // Resolving has some overhead per call, so do a few frames at a time
+            let len = RESOLVE_CHUNK.min(rest.len()).min(budget);
+            budget -= len;
+            let tail = rest.split_off(len);
+            let mut chunk = Backtrace::from(std::mem::replace(&mut rest, tail));
+            chunk.resolve();
+            let chunk: Vec<BacktraceFrame> = chunk.into();
+            // Check the raw names, demangling them would take longer than resolving
+            let marker = BEGIN_MARKER.as_bytes();
+            let has_begin = chunk
+                .iter()
+                .flat_map(|frame| frame.symbols())
+                .filter_map(|symbol| symbol.name_bytes())
+                .any(|name| name.windows(marker.len()).any(|window| window == marker));
+            resolved.extend(chunk);
+            if has_begin {
+                break;
+            }
+        }
+        resolved.extend(rest);
+        Backtrace::from(resolved)
+    }
+
+    /// Resolves and clamps the backtrace on a background thread, returning a future that
+    /// completes with it.
+    ///
+    /// The backtrace is resolved like [`resolve_short`][Self::resolve_short], and then the
+    /// frames past the `rust_*_short_backtrace` markers are dropped, so it's just the frames
+    /// of the short backtrace (plus the frames the markers are in, so that
+    /// [`short_frames_strict`][] and everything else here still clamp it the same way).
+    ///
+    /// Each thread that calls this gets one background thread, which is reused by its later
+    /// calls (so a runtime's worker threads get one each, instead of one per backtrace). The
+    /// `backtrace` crate only resolves one backtrace at a time anyway. If resolving panics,
+    /// the panic is resumed when the future is polled.
+    ///
+    /// This works with any executor, since it doesn't need anything from the runtime.
+    pub fn resolve_in_background(self) -> ResolvingBacktrace {
+        self.resolve_in_background_with(|deferred| clamp_to_markers(deferred.resolve_short()))
+    }
+
+    pub(crate) fn resolve_in_background_with(
+        self,
+        resolve: fn(DeferredBacktrace) -> Backtrace,
+    ) -> ResolvingBacktrace {
+        let shared = Arc::new(Mutex::new(Resolving {
+            backtrace: None,
+            waker: None,
+        }));
+        let background = shared.clone();
+        run_in_background(Box::new(move || {
+            let backtrace = std::panic::catch_unwind(AssertUnwindSafe(|| resolve(self)));
+            let waker = {
+                let mut resolving = background.lock().unwrap();
+                resolving.backtrace = Some(backtrace);
+                resolving.waker.take()
+            };
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }));
+        ResolvingBacktrace { shared }
+    }
+}
+
+/// Drops the frames before the frame with the `rust_end_short_backtrace` marker, and after
+/// the one with the `rust_begin_short_backtrace` marker.
+pub(crate) fn clamp_to_markers(backtrace: Backtrace) -> Backtrace {
+    let mut frames: Vec<BacktraceFrame> = backtrace.into();
+    let (short_start, short_end) = find_markers(&frames);
+    if let Some((frame_idx, _)) = short_end {
+        frames.truncate(frame_idx + 1);
+    }
+    if let Some((frame_idx, _)) = short_start {
+        frames.drain(..frame_idx);
+    }
+    Backtrace::from(frames)
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+thread_local! {
+    /// The background thread for this thread's [`DeferredBacktrace::resolve_in_background`].
+    static BACKGROUND: RefCell<Option<Sender<Job>>> = RefCell::new(None);
+}
+
+/// Runs the job on this thread's background thread, starting it if needed.
+fn run_in_background(job: Job) {
+    let mut job = Some(job);
+    let _ = BACKGROUND.try_with(|background| {
+        let mut background = background.borrow_mut();
+        let sender = background.get_or_insert_with(spawn_background);
+        if let Err(mpsc::SendError(returned)) = sender.send(job.take().unwrap()) {
+            // The jobs catch their own panics, so this only happens if a waker panicked
+            job = Some(returned);
+            *background = None;
+        }
+    });
+    // Either this thread is exiting and can't keep a background thread, or it died
+    if let Some(job) = job {
+        std::thread::spawn(job);
+    }
+}
+
+fn spawn_background() -> Sender<Job> {
+    let (sender, receiver) = mpsc::channel::<Job>();
+    std::thread::spawn(move || {
+        for job in receiver {
+            job();
+        }
+    });
+    sender
+}
+
+/// A future for a backtrace being resolved in the background, from
+/// [`DeferredBacktrace::resolve_in_background`][].
+#[derive(Debug)]
+pub struct ResolvingBacktrace {
+    shared: Arc<Mutex<Resolving>>,
+}
+
+#[derive(Debug)]
+struct Resolving {
+    backtrace: Option<std::thread::Result<Backtrace>>,
+    waker: Option<Waker>,
+}
+
+impl Future for ResolvingBacktrace {
+    type Output = Backtrace;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Backtrace> {
+        let mut resolving = self.shared.lock().unwrap();
+        match resolving.backtrace.take() {
+            Some(Ok(backtrace)) => Poll::Ready(backtrace),
+            Some(Err(panic)) => {
+                drop(resolving);
+                std::panic::resume_unwind(panic)
+            }
+            None => {
+                resolving.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
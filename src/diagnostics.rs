@@ -0,0 +1,380 @@
+//! Tools for checking how well the short backtrace heuristics worked.
+
+use crate::*;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+
+/// Which of the special short backtrace frames a symbol is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MarkerKind {
+    /// `rust_end_short_backtrace`, which marks where the short backtrace starts
+    /// (everything newer than it is the panic runtime).
+    End,
+    /// `rust_begin_short_backtrace`, which marks where the short backtrace ends
+    /// (everything older than it is setting up main or the thread).
+    Begin,
+}
+
+/// Finds every symbol in the whole backtrace that is one of the special short
+/// backtrace frames.
+///
+/// Each entry is `(frame_index, subframe_index, kind)`, indexing into `backtrace.frames()`
+/// and then `frame.symbols()`, in the order they appear in the backtrace. A symbol that
+/// somehow contains both names appears twice (`End` first).
+///
+/// [`short_frames_strict`][] only uses the innermost pair of these, so this is useful
+/// for seeing what the stack actually contained when the result looks wrong.
+pub fn all_marker_positions(backtrace: &Backtrace) -> Vec<(usize, usize, MarkerKind)> {
+    all_marker_positions_impl(backtrace)
+}
+
+pub(crate) fn all_marker_positions_impl<B: Backtraceish>(
+    backtrace: &B,
+) -> Vec<(usize, usize, MarkerKind)> {
+    let mut positions = vec![];
+    for (frame_idx, frame) in backtrace.frames().iter().enumerate() {
+        for (subframe_idx, symbol) in frame.symbols().iter().enumerate() {
+            if let Some(name) = symbol.name_str() {
+                if name.contains(END_MARKER) {
+                    positions.push((frame_idx, subframe_idx, MarkerKind::End));
+                }
+                if name.contains(BEGIN_MARKER) {
+                    positions.push((frame_idx, subframe_idx, MarkerKind::Begin));
+                }
+            }
+        }
+    }
+    positions
+}
+
+/// Counts the frames that were clamped off of each side of the short backtrace.
+///
+/// Returns `(newer, older)`: `newer` is the number of frames newer than the short
+/// backtrace (the panic runtime, up to and including `rust_end_short_backtrace`), and
+/// `older` is the number of frames older than it (setting up main or the thread, starting
+/// at `rust_begin_short_backtrace`). A frame that is only partially in the short
+/// backtrace (because it had a marker inlined into it) isn't counted.
+///
+/// This is handy for seeing how much glue a platform puts around the short backtrace.
+/// If no markers were found, both are 0.
+pub fn gunk_frame_counts(backtrace: &Backtrace) -> (usize, usize) {
+    gunk_frame_counts_impl(backtrace)
+}
+
+pub(crate) fn gunk_frame_counts_impl<B: Backtraceish>(backtrace: &B) -> (usize, usize) {
+    let (_, frame_range, _, _) = short_slice_impl(backtrace);
+    let total = backtrace.frames().len();
+    (frame_range.start, total.saturating_sub(frame_range.end))
+}
+
+/// Formats every frame of the backtrace, marking which symbols made it into the short
+/// backtrace and where the markers are, for checking the heuristics on a new platform.
+///
+/// Every symbol gets a line, starting with `>` if it's in the range yielded by
+/// [`short_frames_strict`][], and the ones found by [`all_marker_positions`][] get a note
+/// at the end. Like [`short_debug`][] this doesn't start with a newline:
+///
+/// ```text
+///      0: 0x55d4159a3a10 - std::rust_end_short_backtrace  <-- end marker
+/// >    1: 0x55d4159a3b2f - app::parse
+/// >                      - app::main
+///      2: 0x55d4159a3c40 - std::rust_begin_short_backtrace  <-- begin marker
+///      3: 0x55d4159a3d00 - <unresolved>
+/// ```
+pub fn format_annotated_full(backtrace: &Backtrace) -> String {
+    format_annotated_full_impl(backtrace)
+}
+
+pub(crate) fn format_annotated_full_impl<B: Backtraceish>(backtrace: &B) -> String {
+    let short: HashMap<_, _> = short_frames_indexed_impl(backtrace)
+        .map(|(frame_idx, _, subframes)| (frame_idx, subframes))
+        .collect();
+    let markers = all_marker_positions_impl(backtrace);
+
+    let mut lines = vec![];
+    for (frame_idx, frame) in backtrace.frames().iter().enumerate() {
+        let address = format!("{:#x}", frame.ip() as usize);
+        let symbols = frame.symbols();
+        if symbols.is_empty() {
+            let mark = if short.contains_key(&frame_idx) {
+                '>'
+            } else {
+                ' '
+            };
+            lines.push(format!(
+                "{} {:4}: {} - <unresolved>",
+                mark, frame_idx, address
+            ));
+        }
+        for (subframe_idx, symbol) in symbols.iter().enumerate() {
+            let in_short = short
+                .get(&frame_idx)
+                .map_or(false, |subframes| subframes.contains(&subframe_idx));
+            let mut line = String::new();
+            line.push(if in_short { '>' } else { ' ' });
+            if subframe_idx == 0 {
+                let _ = write!(line, " {:4}: {} - ", frame_idx, address);
+            } else {
+                let _ = write!(line, "{:1$}- ", "", address.len() + 8);
+            }
+            match demangled_name(symbol) {
+                Some(name) => line.push_str(&name),
+                None => line.push_str("<unknown>"),
+            }
+            for &(_, _, kind) in markers
+                .iter()
+                .filter(|&&(f, s, _)| (f, s) == (frame_idx, subframe_idx))
+            {
+                line.push_str(match kind {
+                    MarkerKind::End => "  <-- end marker",
+                    MarkerKind::Begin => "  <-- begin marker",
+                });
+            }
+            lines.push(line);
+        }
+    }
+    lines.join("\n")
+}
+
+/// Symbols that the oldest frame of a complete stack usually belongs to: where the OS
+/// starts a process or a thread.
+///
+/// These are matched like [`MAIN_SYMBOLS`][] (which also count as entry points).
+pub const ENTRY_POINT_SYMBOLS: &[&str] = &[
+    "_start",
+    "__libc_start_main",
+    "__libc_start_call_main",
+    "start_thread",
+    "thread_start",
+    "_pthread_start",
+    "clone",
+    "clone3",
+    "BaseThreadInitThunk",
+    "RtlUserThreadStart",
+    "mainCRTStartup",
+];
+
+/// How many of the oldest frames are searched for an entry point.
+const ENTRY_POINT_SEARCH_FRAMES: usize = 4;
+
+/// Checks whether the oldest frames of the backtrace might be missing (like when it was
+/// captured with a depth limit), so you can tell users there may be more to it.
+///
+/// This is a heuristic: the backtrace counts as truncated if it has no
+/// `rust_begin_short_backtrace` marker *and* none of its oldest few frames are an entry
+/// point (see [`ENTRY_POINT_SYMBOLS`][] and [`MAIN_SYMBOLS`][]). An empty backtrace is
+/// never truncated.
+pub fn is_truncated(backtrace: &Backtrace) -> bool {
+    is_truncated_impl(backtrace)
+}
+
+pub(crate) fn is_truncated_impl<B: Backtraceish>(backtrace: &B) -> bool {
+    let frames = backtrace.frames();
+    let has_begin_marker = all_marker_positions_impl(backtrace)
+        .iter()
+        .any(|&(_, _, kind)| kind == MarkerKind::Begin);
+    if frames.is_empty() || has_begin_marker {
+        return false;
+    }
+    let oldest = &frames[frames.len().saturating_sub(ENTRY_POINT_SEARCH_FRAMES)..];
+    let has_entry_point = oldest
+        .iter()
+        .flat_map(|frame| frame.symbols())
+        .filter_map(demangled_name)
+        .any(|name| {
+            ENTRY_POINT_SYMBOLS
+                .iter()
+                .chain(MAIN_SYMBOLS)
+                .any(|entry| names::matches_path_prefix(&name, entry))
+        });
+    !has_entry_point
+}
+
+/// Splits the backtrace into every region delimited by a pair of markers, for programs
+/// where several layers (like an executor and the tasks it runs) each call their code
+/// through `rust_end_short_backtrace` and `rust_begin_short_backtrace`.
+///
+/// A region is everything between a `rust_end_short_backtrace` and the next
+/// `rust_begin_short_backtrace` older than it, as long as there are no other markers
+/// between them. Each region is yielded like [`short_frames_strict`][] would if it were
+/// the only one. The regions are in backtrace order, so the newest (innermost) one comes
+/// first. Unlike [`short_frames_strict`][], a backtrace without any complete pair of
+/// markers has no regions, rather than being one big region.
+pub fn short_regions(backtrace: &Backtrace) -> Vec<impl Iterator<Item = ShortFrame<'_>>> {
+    short_regions_impl(backtrace)
+        .into_iter()
+        .map(|region| region.map(ShortFrame::from))
+        .collect()
+}
+
+pub(crate) fn short_regions_impl<B: Backtraceish>(
+    backtrace: &B,
+) -> Vec<impl Iterator<Item = (&B::Frame, Range<usize>)>> {
+    let markers = all_marker_positions_impl(backtrace);
+    markers
+        .windows(2)
+        .filter_map(|pair| match pair {
+            [(f1, s1, MarkerKind::End), (f2, s2, MarkerKind::Begin)] if (f1, s1) != (f2, s2) => {
+                Some(((*f1, *s1), (*f2, *s2)))
+            }
+            _ => None,
+        })
+        .map(|(start, end)| {
+            clamped_frames(clamp_slice(backtrace.frames(), Some(start), Some(end)))
+                .map(|(_, frame, range)| (frame, range))
+        })
+        .collect()
+}
+
+/// What [`self_check`][] found out about short backtraces on this platform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelfCheckReport {
+    /// Whether the `rust_end_short_backtrace` marker showed up in the backtrace of a panic.
+    pub end_marker: bool,
+    /// Whether the `rust_begin_short_backtrace` marker showed up in the backtrace of a thread.
+    pub begin_marker: bool,
+    /// Whether the markers were in the order [`short_frames_strict`][] expects, so it
+    /// clamped to them instead of ignoring both. This is false if either is missing.
+    pub in_order: bool,
+    /// Whether the functions that panicked were in the short backtrace, innermost first.
+    pub frames_found: bool,
+}
+
+impl SelfCheckReport {
+    /// Whether everything worked, so short backtraces are as short as they should be.
+    pub fn is_ok(&self) -> bool {
+        self.end_marker && self.begin_marker && self.in_order && self.frames_found
+    }
+}
+
+/// Prints `short backtrace support: OK`, or `short backtrace support: degraded` followed by
+/// what went wrong, for logging at startup.
+impl fmt::Display for SelfCheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_ok() {
+            return f.write_str("short backtrace support: OK");
+        }
+        let problems = [
+            (self.end_marker, "no rust_end_short_backtrace marker"),
+            (self.begin_marker, "no rust_begin_short_backtrace marker"),
+            // Missing markers can't be in order, but that's not worth mentioning twice
+            (
+                self.in_order || !(self.end_marker && self.begin_marker),
+                "markers out of order",
+            ),
+            (self.frames_found, "missing frames"),
+        ];
+        let problems: Vec<&str> = problems
+            .iter()
+            .filter(|(ok, _)| !ok)
+            .map(|(_, problem)| *problem)
+            .collect();
+        write!(
+            f,
+            "short backtrace support: degraded ({})",
+            problems.join(", ")
+        )
+    }
+}
+
+/// Checks whether short backtraces work on this platform, by panicking on purpose and
+/// looking at the backtrace.
+///
+/// The marker frames this crate relies on depend on how std was compiled and how well
+/// the platform's unwinder and debuginfo work, and when they're missing
+/// [`short_frames_strict`][] quietly falls back to the whole backtrace. Running this at
+/// startup lets you log that up front:
+///
+/// ```
+/// let report = backtrace_ext::self_check();
+/// eprintln!("{}", report);
+/// ```
+///
+/// The panic happens on a new thread (so the `rust_begin_short_backtrace` marker is there)
+/// and is caught. While it runs, the panic hook is temporarily replaced, but panics on
+/// other threads are still passed on to the previous hook. This can't be called from a
+/// panic hook or while panicking, since replacing the hook isn't allowed then.
+pub fn self_check() -> SelfCheckReport {
+    std::thread::spawn(self_check_thread)
+        .join()
+        .unwrap_or(SelfCheckReport {
+            end_marker: false,
+            begin_marker: false,
+            in_order: false,
+            frames_found: false,
+        })
+}
+
+fn self_check_thread() -> SelfCheckReport {
+    let captured = Arc::new(Mutex::new(None));
+    let this_thread = std::thread::current().id();
+    let previous = Arc::new(std::panic::take_hook());
+    {
+        let captured = captured.clone();
+        let previous = previous.clone();
+        std::panic::set_hook(Box::new(move |info| {
+            if std::thread::current().id() == this_thread {
+                *captured.lock().unwrap() = Some(Backtrace::new());
+            } else {
+                previous(info);
+            }
+        }));
+    }
+    let _ = std::panic::catch_unwind(self_check_outer);
+    // Dropping our hook drops its handle on the previous one
+    drop(std::panic::take_hook());
+    match Arc::try_unwrap(previous) {
+        Ok(previous) => std::panic::set_hook(previous),
+        Err(previous) => std::panic::set_hook(Box::new(move |info| previous(info))),
+    }
+
+    let backtrace = captured.lock().unwrap().take();
+    match backtrace {
+        Some(backtrace) => self_check_report_impl(&backtrace),
+        None => self_check_report_impl(&Backtrace::from(vec![])),
+    }
+}
+
+#[inline(never)]
+fn self_check_outer() {
+    self_check_inner();
+}
+
+#[inline(never)]
+fn self_check_inner() {
+    panic!("backtrace_ext::self_check");
+}
+
+pub(crate) fn self_check_report_impl<B: Backtraceish>(backtrace: &B) -> SelfCheckReport {
+    let markers = all_marker_positions_impl(backtrace);
+    let end = markers
+        .iter()
+        .rev()
+        .find(|&&(_, _, kind)| kind == MarkerKind::End);
+    let begin = markers
+        .iter()
+        .find(|&&(_, _, kind)| kind == MarkerKind::Begin);
+    let in_order = match (end, begin) {
+        (Some((f1, s1, _)), Some((f2, s2, _))) => (f1, s1) < (f2, s2),
+        _ => false,
+    };
+    let names: Vec<String> = short_frames_strict_impl(backtrace)
+        .flat_map(|(frame, subframes)| frame.symbols()[subframes].iter().filter_map(demangled_name))
+        .collect();
+    let position = |function: &str| {
+        names
+            .iter()
+            .position(|name| names::leaf_name(name) == function)
+    };
+    let frames_found = match (position("self_check_inner"), position("self_check_outer")) {
+        (Some(inner), Some(outer)) => inner < outer,
+        _ => false,
+    };
+    SelfCheckReport {
+        end_marker: end.is_some(),
+        begin_marker: begin.is_some(),
+        in_order,
+        frames_found,
+    }
+}
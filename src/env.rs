@@ -0,0 +1,50 @@
+//! Formatting that follows the `RUST_BACKTRACE` environment variable.
+
+use crate::*;
+
+/// Formats the short backtrace if `RUST_BACKTRACE` is set, exactly like the example in
+/// [`short_frames_strict`][] does.
+///
+/// This returns an empty string if `RUST_BACKTRACE` is unset, empty, or `0`, and the
+/// output of a default [`ShortBacktraceFormatter`][] otherwise. Unlike
+/// [`format_short_backtrace_env`][], `RUST_BACKTRACE=full` still gets the short backtrace.
+pub fn format_short_backtrace(backtrace: &Backtrace) -> String {
+    let var = std::env::var("RUST_BACKTRACE").ok();
+    format_short_backtrace_impl(backtrace, var.as_deref())
+}
+
+pub(crate) fn format_short_backtrace_impl<B: Backtraceish>(
+    backtrace: &B,
+    var: Option<&str>,
+) -> String {
+    match var {
+        None | Some("") | Some("0") => String::new(),
+        Some(_) => ShortBacktraceFormatter::new().format_impl(backtrace),
+    }
+}
+
+/// Formats the backtrace the way `RUST_BACKTRACE` asks for, like std does for panics.
+///
+/// * unset, empty, or `0`: returns an empty string
+/// * `full`: every frame of the backtrace, including the ones that are normally clamped away
+/// * anything else (like `1`): the short backtrace
+///
+/// The frames are printed by a default [`ShortBacktraceFormatter`][] (with
+/// [`full_backtrace`][ShortBacktraceFormatter::full_backtrace] for `full`).
+pub fn format_short_backtrace_env(backtrace: &Backtrace) -> String {
+    let var = std::env::var("RUST_BACKTRACE").ok();
+    format_short_backtrace_env_impl(backtrace, var.as_deref())
+}
+
+pub(crate) fn format_short_backtrace_env_impl<B: Backtraceish>(
+    backtrace: &B,
+    var: Option<&str>,
+) -> String {
+    match var {
+        None | Some("") | Some("0") => String::new(),
+        Some("full") => ShortBacktraceFormatter::new()
+            .full_backtrace(true)
+            .format_impl(backtrace),
+        Some(_) => ShortBacktraceFormatter::new().format_impl(backtrace),
+    }
+}
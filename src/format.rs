@@ -0,0 +1,1922 @@
+//! Ready-made rendering of short backtraces.
+
+use crate::*;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const HEX_WIDTH: usize = std::mem::size_of::<usize>() + 2;
+// Padding for next lines after frame's address
+const NEXT_SYMBOL_PADDING: usize = HEX_WIDTH + 6;
+
+/// Renders the frames yielded by [`short_frames_strict`][] as text.
+///
+/// The output is the same as the example in the docs of [`short_frames_strict`][]:
+/// a numbered list of frames with their address, the name of every symbol in the frame's
+/// range, and the `file:line` of each symbol when debuginfo knows it. Note that every
+/// frame (including the first) starts with a newline, so the output is intended to be
+/// appended directly after some message.
+#[derive(Debug, Clone, Default)]
+pub struct ShortBacktraceFormatter {
+    show_columns: bool,
+    hide_addresses: bool,
+    hide_file_lines: bool,
+    inline_policy: InlinePolicy,
+    frame_annotations: HashMap<usize, String>,
+    style: FormatStyle,
+    user_prefix: Option<String>,
+    mark_dependency_boundary: bool,
+    line_ending: LineEnding,
+    collapse_unresolved_runs: bool,
+    full_backtrace: bool,
+    header: Option<String>,
+    colorize: bool,
+    color_map: ColorMap,
+    mark_catch_unwind: bool,
+    mark_alloc_failure: bool,
+    mark_abort: bool,
+    mark_thread_entry: bool,
+    #[cfg(unix)]
+    mark_signal_boundary: bool,
+    workspace_root: Option<PathBuf>,
+    frame_prefix: String,
+    show_crate_tag: bool,
+    indent: usize,
+    max_bytes: Option<usize>,
+    max_frames: Option<(usize, usize)>,
+    address_format: AddressFormat,
+    address_affixes: Option<(String, String)>,
+    address_base: Option<usize>,
+    fold_generics: bool,
+    simplify_closures: bool,
+    middle_truncate_names: Option<usize>,
+    frame_separator: Option<String>,
+    collapse_recursion: Option<usize>,
+    collapse_repeated_frames: bool,
+    show_mangled: bool,
+    time_budget: Option<Duration>,
+    index_base: usize,
+    macro_frames: MacroFramePolicy,
+    module_names: HashMap<usize, String>,
+    subframe_order: SubframeOrder,
+    demangler: Option<Demangler>,
+    #[cfg(feature = "source-snippets")]
+    source_context: Option<usize>,
+    highlights: Vec<Highlight>,
+}
+
+/// A user-provided demangler for non-Rust symbols, see
+/// [`ShortBacktraceFormatter::demangler`][].
+#[derive(Clone)]
+struct Demangler(Arc<DemangleFn>);
+
+type DemangleFn = dyn Fn(&str) -> Option<String> + Send + Sync;
+
+impl fmt::Debug for Demangler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Demangler(..)")
+    }
+}
+
+/// A pattern for [`ShortBacktraceFormatter::highlight_substring`][] (or `highlight`
+/// with the `regex` feature).
+#[derive(Debug, Clone)]
+enum Highlight {
+    Substring(String),
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+}
+
+impl Highlight {
+    fn is_match(&self, name: &str) -> bool {
+        match self {
+            Highlight::Substring(pattern) => name.contains(pattern.as_str()),
+            #[cfg(feature = "regex")]
+            Highlight::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+/// The overall layout of the output of a [`ShortBacktraceFormatter`][].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatStyle {
+    /// The layout from the example in [`short_frames_strict`][] (the default):
+    ///
+    /// ```text
+    ///    0: 0x55d4159a3b2f - app::parse
+    ///                 at src/parse.rs:10
+    ///                  - app::main
+    ///                 at src/main.rs:5
+    /// ```
+    Default,
+    /// The layout of gdb's `backtrace` command, for tools that parse that:
+    ///
+    /// ```text
+    /// #0  app::parse at src/parse.rs:10
+    /// #1  app::main at src/main.rs:5
+    /// #2  libc_thing ()
+    /// ```
+    ///
+    /// Like gdb, every inlined call gets its own line and number.
+    /// Unknown functions are shown as `??`.
+    GdbLike,
+    /// Exactly one line per frame, for log formats where every line should stand on its own:
+    ///
+    /// ```text
+    /// 0: app::parse (src/parse.rs:10)
+    /// 1: app::main (src/main.rs:5)
+    /// 2: <unresolved>
+    /// ```
+    ///
+    /// Only the innermost symbol of each frame is printed (regardless of the
+    /// [`inline_policy`][ShortBacktraceFormatter::inline_policy]), and addresses aren't
+    /// printed. Options that add their own lines (like
+    /// [`frame_annotations`][ShortBacktraceFormatter::frame_annotations]) still do.
+    SingleLine,
+    /// The frames as a chain of causes, oldest first, like the chain of an error:
+    ///
+    /// ```text
+    /// app::main (src/main.rs:5)
+    ///   caused by app::handle (src/handle.rs:3)
+    ///   caused by app::parse (src/parse.rs:10)
+    /// ```
+    ///
+    /// Each frame is rendered like [`FormatStyle::SingleLine`][], just without the index.
+    CausedBy,
+}
+
+impl Default for FormatStyle {
+    fn default() -> Self {
+        FormatStyle::Default
+    }
+}
+
+/// The line ending used by a [`ShortBacktraceFormatter`][].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n` (the default).
+    Lf,
+    /// `\r\n`, for tools that are strict about Windows line endings.
+    CrLf,
+}
+
+impl Default for LineEnding {
+    fn default() -> Self {
+        LineEnding::Lf
+    }
+}
+
+impl LineEnding {
+    /// The line ending as a string.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+/// How a [`ShortBacktraceFormatter`][] prints the address of each frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressFormat {
+    /// Hex with a `0x` prefix, right-aligned to `width` with spaces (like `      0x20`).
+    /// The default is this with a width of `size_of::<usize>() + 2`.
+    Hex {
+        /// The minimum width, including the `0x`.
+        width: usize,
+    },
+    /// Hex with a `0x` prefix and exactly `digits` digits (or more, if needed),
+    /// padded with zeros (like `0x0000000000000020`).
+    ZeroPaddedHex {
+        /// The minimum number of digits, not including the `0x`.
+        digits: usize,
+    },
+    /// Plain decimal (like `32`).
+    Decimal,
+}
+
+impl Default for AddressFormat {
+    fn default() -> Self {
+        AddressFormat::Hex { width: HEX_WIDTH }
+    }
+}
+
+impl AddressFormat {
+    fn write(self, out: &mut dyn fmt::Write, address: usize) -> fmt::Result {
+        match self {
+            AddressFormat::Hex { width } => {
+                // Pad it ourselves, so we don't have to allocate to right-align it
+                let mut digits = 1;
+                while digits < 2 * std::mem::size_of::<usize>() && address >> (4 * digits) != 0 {
+                    digits += 1;
+                }
+                let padding = width.saturating_sub(digits + 2);
+                write!(out, "{:1$}{2:#x}", "", padding, address)
+            }
+            AddressFormat::ZeroPaddedHex { digits } => write!(out, "0x{:01$x}", address, digits),
+            AddressFormat::Decimal => write!(out, "{}", address),
+        }
+    }
+}
+
+/// Which symbols to print when a frame has several (because calls got inlined into it).
+///
+/// The backtrace crate lists the symbols of a frame from the innermost (the function
+/// that was inlined into the others) to the outermost (the function that actually
+/// owns the frame).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InlinePolicy {
+    /// Print every symbol (the default).
+    All,
+    /// Only print the innermost symbol of each frame.
+    InnermostOnly,
+    /// Only print the outermost symbol of each frame.
+    OutermostOnly,
+}
+
+impl Default for InlinePolicy {
+    fn default() -> Self {
+        InlinePolicy::All
+    }
+}
+
+impl InlinePolicy {
+    /// Narrows the range of a frame's symbols down to the ones we want to print.
+    pub(crate) fn apply(self, range: Range<usize>) -> Range<usize> {
+        if range.is_empty() {
+            return range;
+        }
+        match self {
+            InlinePolicy::All => range,
+            InlinePolicy::InnermostOnly => range.start..range.start + 1,
+            InlinePolicy::OutermostOnly => range.end - 1..range.end,
+        }
+    }
+}
+
+/// What a [`ShortBacktraceFormatter`][] does with frames that look like they came from
+/// a macro expansion (see [`is_macro_frame`][]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MacroFramePolicy {
+    /// Print them like any other frame (the default).
+    Show,
+    /// Print them with `(macro)` on the line after them.
+    Annotate,
+    /// Don't print them at all. The indices of the other frames don't change, so you
+    /// can still tell something was left out.
+    Hide,
+}
+
+impl Default for MacroFramePolicy {
+    fn default() -> Self {
+        MacroFramePolicy::Show
+    }
+}
+
+/// The order a [`ShortBacktraceFormatter`][] prints the symbols of a frame in, when it
+/// has several (because calls got inlined into it).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubframeOrder {
+    /// The function that was inlined first, followed by the functions it was inlined into
+    /// (the default). This is the order the backtrace crate uses on every platform, so
+    /// it matches the order of the whole backtrace: newest call first.
+    InnermostFirst,
+    /// The function that actually owns the frame first, followed by the functions that
+    /// were inlined into it.
+    OutermostFirst,
+}
+
+impl Default for SubframeOrder {
+    fn default() -> Self {
+        SubframeOrder::InnermostFirst
+    }
+}
+
+impl SubframeOrder {
+    /// Iterates over the symbols in this order, given them innermost first.
+    fn iter<S>(self, symbols: &[S]) -> impl Iterator<Item = &S> {
+        let reverse = self == SubframeOrder::OutermostFirst;
+        (0..symbols.len()).map(move |i| &symbols[if reverse { symbols.len() - 1 - i } else { i }])
+    }
+}
+
+/// Renders a single symbol of a frame, for use with
+/// [`ShortBacktraceFormatter::write_with_renderer`][].
+///
+/// The formatter takes care of finding the short backtrace, and writing each frame's
+/// header (its index and address). Everything after that is up to the renderer.
+pub trait SymbolRenderer {
+    /// Renders one symbol.
+    ///
+    /// `index` is the position of this symbol among the ones being printed for its frame.
+    /// 0 is the first symbol, which is written on the same line as the frame's header.
+    /// Any others are inlined calls, which you probably want to start on a new line.
+    fn render(
+        &self,
+        out: &mut dyn fmt::Write,
+        index: usize,
+        symbol: &BacktraceSymbol,
+    ) -> fmt::Result;
+}
+
+/// The [`SymbolRenderer`][] that renders symbols exactly like a default
+/// [`ShortBacktraceFormatter`][] does.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultSymbolRenderer;
+
+impl SymbolRenderer for DefaultSymbolRenderer {
+    fn render(
+        &self,
+        out: &mut dyn fmt::Write,
+        index: usize,
+        symbol: &BacktraceSymbol,
+    ) -> fmt::Result {
+        ShortBacktraceFormatter::default().render_symbol(out, index, symbol, None)
+    }
+}
+
+/// A way of formatting a short backtrace that can be stored as a trait object, for
+/// applications that let plugins (or configuration) pick how backtraces get printed.
+///
+/// This is implemented for [`ShortBacktraceFormatter`][] (however it's configured),
+/// [`DefaultShortBacktraceFormat`][], and any `Fn(&Backtrace) -> String`:
+///
+/// ```
+/// use backtrace_ext::{DefaultShortBacktraceFormat, ShortBacktraceFormat, ShortBacktraceFormatter};
+///
+/// let mut formats: Vec<Box<dyn ShortBacktraceFormat>> = vec![
+///     Box::new(DefaultShortBacktraceFormat),
+///     Box::new(ShortBacktraceFormatter::new().colorize(true)),
+///     Box::new(|backtrace: &backtrace::Backtrace| format!("{:?}", backtrace)),
+/// ];
+/// let backtrace = backtrace::Backtrace::new();
+/// for format in &formats {
+///     eprintln!("{}", format.format(&backtrace));
+/// }
+/// ```
+pub trait ShortBacktraceFormat {
+    /// Formats the short backtrace into a new String.
+    fn format(&self, backtrace: &Backtrace) -> String;
+}
+
+/// The [`ShortBacktraceFormat`][] that formats backtraces exactly like a default
+/// [`ShortBacktraceFormatter`][] does.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultShortBacktraceFormat;
+
+impl ShortBacktraceFormat for DefaultShortBacktraceFormat {
+    fn format(&self, backtrace: &Backtrace) -> String {
+        ShortBacktraceFormatter::default().format(backtrace)
+    }
+}
+
+impl ShortBacktraceFormat for ShortBacktraceFormatter {
+    fn format(&self, backtrace: &Backtrace) -> String {
+        self.format_impl(backtrace)
+    }
+}
+
+impl<F: Fn(&Backtrace) -> String> ShortBacktraceFormat for F {
+    fn format(&self, backtrace: &Backtrace) -> String {
+        self(backtrace)
+    }
+}
+
+/// The result of [`ShortBacktraceFormatter::format_to_slice`][].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SliceWriteResult {
+    /// How many bytes at the start of the buffer were filled in. These bytes are always valid UTF-8.
+    pub written: usize,
+    /// Whether the output didn't fit in the buffer and was cut short.
+    pub truncated: bool,
+}
+
+impl ShortBacktraceFormatter {
+    /// Creates a formatter with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to include the column in source locations (`file:line:col`), when debuginfo
+    /// has one. Defaults to `false`.
+    pub fn show_columns(mut self, show_columns: bool) -> Self {
+        self.show_columns = show_columns;
+        self
+    }
+
+    /// Whether to print the address of each frame (default: true). Without them the
+    /// symbols stay where they'd be with addresses, so they still line up with the
+    /// symbols of inlined calls on the lines below. This only affects the default
+    /// [`style`][Self::style], since the others don't print addresses.
+    pub fn show_addresses(mut self, show_addresses: bool) -> Self {
+        self.hide_addresses = !show_addresses;
+        self
+    }
+
+    /// Whether to print the `file:line` of each symbol when debuginfo knows it
+    /// (default: true), in every [`style`][Self::style]. Turning this off also turns off
+    /// `source_context`, since that shows the code at the same location.
+    pub fn show_file_lines(mut self, show_file_lines: bool) -> Self {
+        self.hide_file_lines = !show_file_lines;
+        self
+    }
+
+    /// Which symbols to print for frames that have several inlined into them.
+    /// Defaults to [`InlinePolicy::All`][].
+    pub fn inline_policy(mut self, inline_policy: InlinePolicy) -> Self {
+        self.inline_policy = inline_policy;
+        self
+    }
+
+    /// Whether to only print the first (innermost) symbol of each frame, for a terser
+    /// backtrace.
+    ///
+    /// This is a shortcut for [`inline_policy`][Self::inline_policy] with
+    /// [`InlinePolicy::InnermostOnly`][] (or [`InlinePolicy::All`][] for `false`), so
+    /// whichever of the two is called last wins.
+    pub fn first_symbol_only(self, first_symbol_only: bool) -> Self {
+        self.inline_policy(if first_symbol_only {
+            InlinePolicy::InnermostOnly
+        } else {
+            InlinePolicy::All
+        })
+    }
+
+    /// Extra text to print with specific frames, like timings from a sampling profiler.
+    ///
+    /// The keys are the index of the frame in `backtrace.frames()` (*not* the index
+    /// printed in the output, which only counts the short backtrace). Each annotation is
+    /// printed in brackets on its own line, after the frame's symbols. Frames without an
+    /// annotation are printed as usual, and by default there are no annotations.
+    pub fn frame_annotations(mut self, frame_annotations: HashMap<usize, String>) -> Self {
+        self.frame_annotations = frame_annotations;
+        self
+    }
+
+    /// The overall layout of the output. Defaults to [`FormatStyle::Default`][].
+    ///
+    /// Custom [`SymbolRenderer`][]s are only used by the default style.
+    pub fn style(mut self, style: FormatStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// The name of your crate (or a path prefix in it), which lets options like
+    /// [`mark_dependency_boundary`][Self::mark_dependency_boundary] tell your code apart
+    /// from your dependencies. See [`classify_symbol`][] for how it's matched.
+    pub fn user_prefix(mut self, user_prefix: impl Into<String>) -> Self {
+        self.user_prefix = Some(user_prefix.into());
+        self
+    }
+
+    /// Whether to print a `--- dependency frames ---` line where the backtrace goes from
+    /// your code (see [`user_prefix`][Self::user_prefix]) to code from a dependency (as
+    /// determined by [`classify_frame`][]). Only the first such transition is marked.
+    /// Defaults to `false`.
+    pub fn mark_dependency_boundary(mut self, mark_dependency_boundary: bool) -> Self {
+        self.mark_dependency_boundary = mark_dependency_boundary;
+        self
+    }
+
+    /// The line ending to use for every line of the output. Defaults to [`LineEnding::Lf`][].
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    /// Whether to replace runs of consecutive frames that have no names or source files
+    /// with a single `... N unresolved frames ...` line. Runs of just one frame (and frames
+    /// with an [annotation][Self::frame_annotations]) are printed as usual. The index
+    /// printed for later frames isn't affected. Defaults to `false`.
+    pub fn collapse_unresolved_runs(mut self, collapse_unresolved_runs: bool) -> Self {
+        self.collapse_unresolved_runs = collapse_unresolved_runs;
+        self
+    }
+
+    /// Whether to print every frame of the backtrace, instead of just the short backtrace
+    /// (like `RUST_BACKTRACE=full`). Defaults to `false`.
+    pub fn full_backtrace(mut self, full_backtrace: bool) -> Self {
+        self.full_backtrace = full_backtrace;
+        self
+    }
+
+    /// A line to print before the frames, like `Backtrace (most recent call first):`.
+    /// Like the frames, it starts with a newline. Defaults to `None`.
+    pub fn header(mut self, header: Option<String>) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Whether to color the symbol names of each frame with ANSI escape codes, based on
+    /// whether they're your code, std, or a dependency (see the
+    /// [`color_map`][Self::color_map]). Only the names are colored, not the index and
+    /// address of the frame or the `at file:line` lines. Defaults to `false`.
+    pub fn colorize(mut self, colorize: bool) -> Self {
+        self.colorize = colorize;
+        self
+    }
+
+    /// The colors to use when [`colorize`][Self::colorize] is on.
+    /// Defaults to [`ColorMap::default`][].
+    pub fn color_map(mut self, color_map: ColorMap) -> Self {
+        self.color_map = color_map;
+        self
+    }
+
+    /// Whether to print `(caught here)` after the frame where the panic was caught by
+    /// `catch_unwind` (as found by [`catch_unwind_frame`][]). Defaults to `false`.
+    pub fn mark_catch_unwind(mut self, mark_catch_unwind: bool) -> Self {
+        self.mark_catch_unwind = mark_catch_unwind;
+        self
+    }
+
+    /// Whether to print an `(allocation failure)` line before the frames if the backtrace
+    /// went through the allocation error handler (see [`is_alloc_failure`][]), so running
+    /// out of memory stands out from an ordinary panic. Defaults to `false`.
+    pub fn mark_alloc_failure(mut self, mark_alloc_failure: bool) -> Self {
+        self.mark_alloc_failure = mark_alloc_failure;
+        self
+    }
+
+    /// Whether to print an `(abort)` line before the frames if the backtrace is from a hard
+    /// abort (see [`abort_kind`][]), or `(abort: panic while panicking)` for a panic that
+    /// started during another one. Defaults to `false`.
+    pub fn mark_abort(mut self, mark_abort: bool) -> Self {
+        self.mark_abort = mark_abort;
+        self
+    }
+
+    /// Whether to print a `(thread entry)` line after the frames if the backtrace is from a
+    /// spawned thread (see [`thread_entry_frame`][]), so panics on worker threads stand out
+    /// from ones on the main thread. Defaults to `false`.
+    pub fn mark_thread_entry(mut self, mark_thread_entry: bool) -> Self {
+        self.mark_thread_entry = mark_thread_entry;
+        self
+    }
+
+    /// Whether to print `(signal handler boundary)` after the signal trampoline frame (see
+    /// [`signal_trampoline_frame`][]), where the backtrace goes from a signal handler back
+    /// into the code that was interrupted by the signal. Defaults to `false`.
+    #[cfg(unix)]
+    pub fn mark_signal_boundary(mut self, mark_signal_boundary: bool) -> Self {
+        self.mark_signal_boundary = mark_signal_boundary;
+        self
+    }
+
+    /// The root of your workspace, so source files in it are printed relative to it (like
+    /// `src/main.rs`), the way rustc's diagnostics show them. Files outside of it are
+    /// printed as usual. Debuginfo usually records absolute paths, so this should be one too.
+    /// Defaults to `None`.
+    pub fn workspace_root(mut self, workspace_root: impl Into<PathBuf>) -> Self {
+        self.workspace_root = Some(workspace_root.into());
+        self
+    }
+
+    /// Text to put at the start of the first line of each frame, like `    at ` to blend in
+    /// with Java or JavaScript stack traces. In the gdb style every numbered line gets it.
+    /// Defaults to nothing.
+    pub fn frame_prefix(mut self, frame_prefix: impl Into<String>) -> Self {
+        self.frame_prefix = frame_prefix.into();
+        self
+    }
+
+    /// Whether to put the name of the crate each symbol is from in brackets before its
+    /// name, like `[tokio] tokio::runtime::park`. For trait methods this is the crate of
+    /// the type. Symbols that aren't Rust (see [`FrameKind::Foreign`][]) are tagged `[C]`.
+    /// Other symbols that don't look like a Rust path don't get a tag.
+    /// Defaults to `false`.
+    pub fn show_crate_tag(mut self, show_crate_tag: bool) -> Self {
+        self.show_crate_tag = show_crate_tag;
+        self
+    }
+
+    /// How many spaces to put at the start of every line (including the lines of inlined
+    /// symbols and source locations), so the backtrace nests under some other text.
+    /// Defaults to 0.
+    pub fn indent(mut self, indent: usize) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// The most bytes of output to write. If there's more, the output is cut off (on a
+    /// `char` boundary) once it reaches the limit, and `... (truncated)` is added after it.
+    /// Unlimited by default.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Print at most `head + tail` frames: if there are more, only the first `head` and the
+    /// last `tail` are printed, with a `... N frames hidden ...` line in place of the rest.
+    ///
+    /// Only frames that would be printed count, so frames hidden by
+    /// [`macro_frames`][Self::macro_frames] don't. A run of unresolved frames collapsed by
+    /// [`collapse_unresolved_runs`][Self::collapse_unresolved_runs] counts as all of its
+    /// frames. Unlimited by default.
+    pub fn max_frames(mut self, head: usize, tail: usize) -> Self {
+        self.max_frames = Some((head, tail));
+        self
+    }
+
+    /// How to print the address of each frame. Defaults to [`AddressFormat::default`][],
+    /// which matches the example in [`short_frames_strict`][]. Note that the lines after
+    /// the first line of a frame are always indented as if the address had the default
+    /// width.
+    pub fn address_format(mut self, address_format: AddressFormat) -> Self {
+        self.address_format = address_format;
+        self
+    }
+
+    /// Wraps each address in `prefix` and `suffix` (like `<` and `>`), for IDEs and
+    /// terminals that only offer things like "disassemble at address" for addresses in a
+    /// particular form. Padding goes before the prefix, so the addresses stay aligned.
+    /// Addresses printed as `module+offset` aren't wrapped, since they aren't addresses a
+    /// debugger can use as is. By default addresses aren't wrapped.
+    ///
+    /// Addresses are always written the same way on every platform (see
+    /// [`AddressFormat`][]), so for fixed-width addresses that look like `0x00005555…` use
+    /// [`AddressFormat::ZeroPaddedHex`][] with 16 digits.
+    pub fn address_affixes(mut self, prefix: impl Into<String>, suffix: impl Into<String>) -> Self {
+        self.address_affixes = Some((prefix.into(), suffix.into()));
+        self
+    }
+
+    /// Print addresses relative to `base` (usually the module's load address, see
+    /// [`FrameOffset`][]) instead of the absolute instruction pointer, so they don't
+    /// change between runs because of ASLR. Frames with an address below `base` can't be
+    /// relative to it, so they're printed as usual. By default addresses are absolute.
+    pub fn address_base(mut self, base: usize) -> Self {
+        self.address_base = Some(base);
+        self
+    }
+
+    /// Whether to print every list of generic arguments as `_`, so `app::parse::<u32>`
+    /// and `app::parse::<String>` are both printed as `app::parse::<_>`. Nested generics
+    /// are folded together. Defaults to `false`.
+    pub fn fold_generics(mut self, fold_generics: bool) -> Self {
+        self.fold_generics = fold_generics;
+        self
+    }
+
+    /// Whether to print closures as the function they're in with a single `::{closure}`,
+    /// however deeply they're nested, so `app::main::{{closure}}::{{closure}}` is printed
+    /// as `app::main::{closure}`. Numbered closures (like `{closure#1}`) are simplified
+    /// the same way. Defaults to `false`.
+    pub fn simplify_closures(mut self, simplify_closures: bool) -> Self {
+        self.simplify_closures = simplify_closures;
+        self
+    }
+
+    /// Shortens names longer than `max` chars by replacing their middle with `…`, for
+    /// narrow terminals. With a `max` of 27, `app::very::deeply::nested::module::parse` is
+    /// printed as `app::very::de…module::parse`, keeping both the crate and the function.
+    /// This is applied after [`fold_generics`][Self::fold_generics] and
+    /// [`simplify_closures`][Self::simplify_closures]. Defaults to no limit.
+    pub fn middle_truncate_names(mut self, max: usize) -> Self {
+        self.middle_truncate_names = Some(max);
+        self
+    }
+
+    /// A line to print between consecutive frames (but not before the first or after the
+    /// last), like `Some(String::new())` for a blank line or `Some("---".into())` for a
+    /// rule. A run of unresolved frames collapsed by
+    /// [`collapse_unresolved_runs`][Self::collapse_unresolved_runs] counts as one frame.
+    /// Defaults to `None`.
+    pub fn frame_separator(mut self, frame_separator: Option<String>) -> Self {
+        self.frame_separator = frame_separator;
+        self
+    }
+
+    /// Collapse every run of frames that repeats the same cycle of at most `max_cycle_len`
+    /// functions (see [`recursion_cycles`][]) into a single line listing the cycle in the
+    /// order of the calls, like `(eval → apply) × 12`. Frames with an annotation are
+    /// never collapsed. Off by default.
+    pub fn collapse_recursion(mut self, max_cycle_len: usize) -> Self {
+        self.collapse_recursion = Some(max_cycle_len);
+        self
+    }
+
+    /// Whether to print a run of the same frame repeated back to back (see
+    /// [`short_frame_groups`][]) only once, followed by a line like
+    /// `... (repeated 214 times)` with how many times it was repeated after that.
+    /// Frames with an annotation are never collapsed. Defaults to `false`.
+    pub fn collapse_repeated_frames(mut self, collapse_repeated_frames: bool) -> Self {
+        self.collapse_repeated_frames = collapse_repeated_frames;
+        self
+    }
+
+    /// Whether to print the raw name of every symbol in brackets after its demangled name,
+    /// like `app::main [_ZN3app4main17h5e3bd3c2a4f4e9f1E]`, for debugging linker issues.
+    /// Names that aren't valid UTF-8 are printed lossily. Defaults to `false`.
+    pub fn show_mangled(mut self, show_mangled: bool) -> Self {
+        self.show_mangled = show_mangled;
+        self
+    }
+
+    /// The longest formatting should take, for panic hooks that have to finish before
+    /// something like a watchdog kills the process. Once the time is up no more frames are
+    /// written, and `... (time budget exceeded)` is added on its own line. The clock is
+    /// only checked between frames, so formatting can take a bit longer than `budget`.
+    /// Unlimited by default.
+    pub fn time_budget(mut self, budget: Duration) -> Self {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    /// The number of the first frame, for continuing the numbering of an earlier section
+    /// or matching a tool that counts from 1. With [`FormatStyle::GdbLike`][] this is the
+    /// number of the first line. Defaults to 0.
+    pub fn index_base(mut self, index_base: usize) -> Self {
+        self.index_base = index_base;
+        self
+    }
+
+    /// What to do with frames that look like they came from a macro expansion
+    /// (see [`MacroFramePolicy`][]). Defaults to [`MacroFramePolicy::Show`][].
+    pub fn macro_frames(mut self, macro_frames: MacroFramePolicy) -> Self {
+        self.macro_frames = macro_frames;
+        self
+    }
+
+    /// Names for the modules (executables and shared libraries) that frames were loaded
+    /// from, keyed by the module's base address.
+    ///
+    /// The backtrace crate can find a frame's module base (see [`FrameOffset`][]) but not
+    /// its name, so you have to provide that (for example from `dl_iterate_phdr` or
+    /// `/proc/self/maps`). The address of a frame from a named module is printed as
+    /// `libfoo.so+0x4a1c` instead, which is what offline symbolication tools expect.
+    /// Other frames are printed as usual. By default no modules have names.
+    pub fn module_names(mut self, module_names: HashMap<usize, String>) -> Self {
+        self.module_names = module_names;
+        self
+    }
+
+    /// The order to print the symbols of a frame in. This is applied after the
+    /// [`inline_policy`][Self::inline_policy]. Defaults to [`SubframeOrder::InnermostFirst`][].
+    pub fn subframe_order(mut self, subframe_order: SubframeOrder) -> Self {
+        self.subframe_order = subframe_order;
+        self
+    }
+
+    /// A demangler for the names of symbols that aren't Rust (see [`FrameKind::Foreign`][]),
+    /// like a C++ demangler from the `cpp_demangle` crate.
+    ///
+    /// It's given the raw (mangled) name, and returns the name to print, or `None` to print
+    /// the raw name as usual. Rust symbols are always demangled by the backtrace crate.
+    /// By default there's no demangler.
+    pub fn demangler<F>(mut self, demangler: F) -> Self
+    where
+        F: Fn(&str) -> Option<String> + Send + Sync + 'static,
+    {
+        self.demangler = Some(Demangler(Arc::new(demangler)));
+        self
+    }
+
+    /// Prints the source code around the location of each symbol, with `radius` lines
+    /// before and after it (see [`source_snippet`][]). Only the default style does this.
+    ///
+    /// This reads the source files while formatting, so it's best kept to reports meant
+    /// for humans. Symbols whose file can't be read are printed as usual.
+    /// By default no source is printed.
+    #[cfg(feature = "source-snippets")]
+    pub fn source_context(mut self, radius: usize) -> Self {
+        self.source_context = Some(radius);
+        self
+    }
+
+    /// Emphasizes frames with a symbol whose name matches `regex`, like `^serde::` for all
+    /// the frames in `serde` or `poll` for anything polling a future.
+    ///
+    /// This is only available with the `regex` feature, which needs Rust 1.65 (current
+    /// versions of `regex` need it, even though this crate otherwise supports 1.56).
+    /// Without it, [`highlight_substring`][Self::highlight_substring] does the same for
+    /// plain substrings, and describes how highlighted frames are shown.
+    #[cfg(feature = "regex")]
+    pub fn highlight(mut self, regex: regex::Regex) -> Self {
+        self.highlights.push(Highlight::Regex(regex));
+        self
+    }
+
+    /// Emphasizes frames with a symbol whose name contains `pattern`, like `serde::` for
+    /// the frames in `serde` or `poll` for anything polling a future.
+    ///
+    /// With [`colorize`][Self::colorize] on, the frame's symbols get
+    /// [`ColorMap::highlight`][] on top of their usual color. Otherwise the frame is marked
+    /// with a `>` before its index. This can be called several times (and mixed with
+    /// `highlight` for regexes) to highlight frames matching any of the patterns.
+    /// By default no frames are highlighted.
+    pub fn highlight_substring(mut self, pattern: impl Into<String>) -> Self {
+        self.highlights.push(Highlight::Substring(pattern.into()));
+        self
+    }
+
+    /// Formats the short backtrace into a new String.
+    pub fn format(&self, backtrace: &Backtrace) -> String {
+        self.format_impl(backtrace)
+    }
+
+    /// Formats the short backtrace into an existing String, replacing its contents.
+    ///
+    /// The String is cleared but keeps its allocation, so formatting lots of backtraces
+    /// into the same buffer avoids allocating a new String each time.
+    pub fn format_into(&self, buf: &mut String, backtrace: &Backtrace) {
+        self.format_into_impl(buf, backtrace)
+    }
+
+    /// Writes the short backtrace to the given [`fmt::Write`][].
+    pub fn write(&self, out: &mut dyn fmt::Write, backtrace: &Backtrace) -> fmt::Result {
+        self.write_impl(out, backtrace)
+    }
+
+    /// Formats the backtraces of several threads into one report.
+    ///
+    /// Each thread gets a section that starts with a `{label}:` line, followed by its short
+    /// backtrace formatted the same as [`format`][Self::format] would. Sections are separated
+    /// by a blank line. This is intended for whole-process dumps, like the ones a deadlock
+    /// detector produces.
+    pub fn format_threads<L: fmt::Display>(&self, threads: &[(L, Backtrace)]) -> String {
+        self.format_threads_impl(threads)
+    }
+
+    /// Writes the short backtrace to the given [`fmt::Write`][], using a custom
+    /// [`SymbolRenderer`][] for each symbol.
+    ///
+    /// Options that affect how a symbol is rendered (like [`show_columns`][Self::show_columns]
+    /// or the colors of [`colorize`][Self::colorize]) are ignored, since that's the
+    /// renderer's job now.
+    pub fn write_with_renderer(
+        &self,
+        out: &mut dyn fmt::Write,
+        backtrace: &Backtrace,
+        renderer: &dyn SymbolRenderer,
+    ) -> fmt::Result {
+        self.write_frames_impl(out, backtrace, &|out, index, symbol, _| {
+            renderer.render(out, index, symbol)
+        })
+    }
+
+    /// Writes the short backtrace into a fixed buffer, cutting the output short if it
+    /// doesn't fit.
+    ///
+    /// This never allocates a buffer for the output, so the memory used by the rendered
+    /// backtrace is bounded by `buf`, which is handy in a panic hook running under a strict
+    /// memory budget. Truncation always happens on a `char` boundary so
+    /// `&buf[..result.written]` is valid UTF-8.
+    pub fn format_to_slice(&self, buf: &mut [u8], backtrace: &Backtrace) -> SliceWriteResult {
+        self.format_to_slice_impl(buf, backtrace)
+    }
+
+    /// Formats the short backtrace into a new String, along with the byte offset in it where
+    /// each frame starts, for log indexers that want to jump straight to a frame.
+    ///
+    /// Each offset points at the newline that starts the frame's first line (after the
+    /// [`frame_separator`][Self::frame_separator], if any), so `&out[offsets[i]..]` starts
+    /// with the `i`th frame printed. Frames that aren't printed on their own, because they
+    /// were folded into one line by
+    /// [`collapse_unresolved_runs`][Self::collapse_unresolved_runs] or
+    /// [`collapse_recursion`][Self::collapse_recursion], don't get an offset.
+    pub fn format_with_offsets(&self, backtrace: &Backtrace) -> (String, Vec<usize>) {
+        self.format_with_offsets_impl(backtrace)
+    }
+
+    /// Appends the short backtrace to a byte buffer as UTF-8, for log sinks that take bytes.
+    ///
+    /// Unlike [`format_into`][Self::format_into], the existing contents of `buf` are kept.
+    /// The output is written straight into `buf`, without going through a String first.
+    pub fn append_bytes(&self, buf: &mut Vec<u8>, backtrace: &Backtrace) {
+        self.append_bytes_impl(buf, backtrace)
+    }
+
+    pub(crate) fn format_impl<B: Backtraceish>(&self, backtrace: &B) -> String {
+        let mut out = String::new();
+        self.format_into_impl(&mut out, backtrace);
+        out
+    }
+
+    pub(crate) fn format_into_impl<B: Backtraceish>(&self, buf: &mut String, backtrace: &B) {
+        buf.clear();
+        // Writing to a String can't fail
+        let _ = self.write_impl(buf, backtrace);
+    }
+
+    pub(crate) fn format_with_offsets_impl<B: Backtraceish>(
+        &self,
+        backtrace: &B,
+    ) -> (String, Vec<usize>) {
+        let mut out = String::new();
+        let tracker = FrameTracker::default();
+        let mut counting = CountingWriter {
+            out: &mut out,
+            tracker: &tracker,
+        };
+        // Writing to a String can't fail
+        let _ = self.write_frames_tracked(
+            &mut counting,
+            backtrace,
+            &|out, index, symbol, color| self.render_symbol(out, index, symbol, color),
+            Some(&tracker),
+        );
+        (out, tracker.starts.into_inner())
+    }
+
+    pub(crate) fn format_threads_impl<L: fmt::Display, B: Backtraceish>(
+        &self,
+        threads: &[(L, B)],
+    ) -> String {
+        let mut out = String::new();
+        for (idx, (label, backtrace)) in threads.iter().enumerate() {
+            if idx != 0 {
+                out.push_str(self.line_ending.as_str());
+                out.push_str(self.line_ending.as_str());
+            }
+            // Writing to a String can't fail
+            let _ = write!(out, "{}:", label);
+            let _ = self.write_impl(&mut out, backtrace);
+        }
+        out
+    }
+
+    pub(crate) fn append_bytes_impl<B: Backtraceish>(&self, buf: &mut Vec<u8>, backtrace: &B) {
+        // Writing to a Vec can't fail
+        let _ = self.write_impl(&mut VecWriter(buf), backtrace);
+    }
+
+    pub(crate) fn format_to_slice_impl<B: Backtraceish>(
+        &self,
+        buf: &mut [u8],
+        backtrace: &B,
+    ) -> SliceWriteResult {
+        let mut writer = SliceWriter {
+            buf,
+            written: 0,
+            truncated: false,
+        };
+        // The only error we can get is from running out of room, so it's
+        // already recorded in `truncated`.
+        let _ = self.write_impl(&mut writer, backtrace);
+        SliceWriteResult {
+            written: writer.written,
+            truncated: writer.truncated,
+        }
+    }
+
+    pub(crate) fn write_impl<B: Backtraceish>(
+        &self,
+        out: &mut dyn fmt::Write,
+        backtrace: &B,
+    ) -> fmt::Result {
+        self.write_frames_impl(out, backtrace, &|out, index, symbol, color| {
+            self.render_symbol(out, index, symbol, color)
+        })
+    }
+
+    pub(crate) fn write_frames_impl<B: Backtraceish>(
+        &self,
+        out: &mut dyn fmt::Write,
+        backtrace: &B,
+        render: RenderFn<'_, B>,
+    ) -> fmt::Result {
+        self.write_frames_tracked(out, backtrace, render, None)
+    }
+
+    /// [`write_frames_impl`][Self::write_frames_impl], recording where each frame starts
+    /// in `tracker` (if there is one)
+    fn write_frames_tracked<B: Backtraceish>(
+        &self,
+        out: &mut dyn fmt::Write,
+        backtrace: &B,
+        render: RenderFn<'_, B>,
+        tracker: Option<&FrameTracker>,
+    ) -> fmt::Result {
+        let max_bytes = match self.max_bytes {
+            Some(max_bytes) => max_bytes,
+            None => return self.write_frames_unlimited(out, backtrace, render, tracker),
+        };
+        let mut limited = LimitWriter {
+            out,
+            remaining: max_bytes,
+            truncated: false,
+        };
+        let result = self.write_frames_unlimited(&mut limited, backtrace, render, tracker);
+        if limited.truncated {
+            // The error was just us stopping the output
+            limited.out.write_str(TRUNCATED_MARKER)
+        } else {
+            result
+        }
+    }
+
+    fn write_frames_unlimited<B: Backtraceish>(
+        &self,
+        out: &mut dyn fmt::Write,
+        backtrace: &B,
+        render: RenderFn<'_, B>,
+        tracker: Option<&FrameTracker>,
+    ) -> fmt::Result {
+        let started = Instant::now();
+        // Everything below writes `\n`, so translate it here if we need to
+        let mut crlf;
+        let out: &mut dyn fmt::Write = match self.line_ending {
+            LineEnding::Lf => out,
+            LineEnding::CrLf => {
+                crlf = CrLfWriter(out);
+                &mut crlf
+            }
+        };
+        let mut indented;
+        let out: &mut dyn fmt::Write = if self.indent == 0 {
+            out
+        } else {
+            indented = IndentWriter {
+                out,
+                indent: self.indent,
+            };
+            &mut indented
+        };
+        if let Some(header) = &self.header {
+            write!(out, "\n{}", header)?;
+        }
+        if self.mark_alloc_failure && gunk::is_alloc_failure_impl(backtrace) {
+            write!(out, "\n(allocation failure)")?;
+        }
+        if self.mark_abort {
+            match gunk::abort_kind_impl(backtrace) {
+                Some(AbortKind::Abort) => write!(out, "\n(abort)")?,
+                Some(AbortKind::PanicWhilePanicking) => {
+                    write!(out, "\n(abort: panic while panicking)")?
+                }
+                None => {}
+            }
+        }
+        let mut state = WriteState {
+            gdb_idx: self.index_base,
+            wrote_frame: false,
+            panic_origin: if self.colorize {
+                color::panic_origin_impl(self.frames_impl(backtrace))
+            } else {
+                None
+            },
+            caught_at: if self.mark_catch_unwind {
+                self.find_frame(backtrace, gunk::is_catch_unwind_name)
+            } else {
+                None
+            },
+            signal_at: self.signal_boundary(backtrace),
+            tracker,
+        };
+        let mut frames: Vec<_> = self.frames_impl(backtrace).collect();
+        if self.style == FormatStyle::CausedBy {
+            frames.reverse();
+        }
+        let (hidden, hidden_count) = self.hidden_frames(&frames);
+        // The names of every frame, if we're looking for cycles of them
+        let keys: Vec<_> = if self.collapse_recursion.is_some() {
+            frames
+                .iter()
+                .enumerate()
+                .map(|(idx, (frame_idx, frame, subframes))| {
+                    if self.frame_annotations.contains_key(frame_idx) || hidden.contains(&idx) {
+                        None
+                    } else {
+                        recursion::frame_key(*frame, subframes.clone())
+                    }
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+        let cycles = recursion::find_cycles(&keys, self.collapse_recursion.unwrap_or(0));
+        let mut cycles = cycles.into_iter().peekable();
+        let mut boundary = DependencyBoundary::new(self.mark_dependency_boundary);
+        // Whether each frame is a repeat of the frame before it, if we're collapsing those
+        let repeats: Vec<bool> = if self.collapse_repeated_frames {
+            let mut repeats = vec![false];
+            repeats.extend(frames.windows(2).enumerate().map(|(idx, pair)| {
+                let (_, prev, prev_subframes) = &pair[0];
+                let (frame_idx, frame, subframes) = &pair[1];
+                !self.frame_annotations.contains_key(frame_idx)
+                    && !hidden.contains(&(idx + 1))
+                    && recursion::same_frame(
+                        *prev,
+                        prev_subframes.clone(),
+                        *frame,
+                        subframes.clone(),
+                    )
+            }));
+            repeats
+        } else {
+            vec![]
+        };
+        // The end of the run of repeated frames we're skipping
+        let mut repeats_end = 0;
+        // Unresolved frames we're holding onto in case they turn out to be a run
+        let mut unresolved_run = vec![];
+        for (idx, (frame_idx, frame, subframes)) in frames.into_iter().enumerate() {
+            if self
+                .time_budget
+                .map_or(false, |budget| started.elapsed() >= budget)
+            {
+                self.flush_unresolved_run(out, &mut unresolved_run, &mut state, render)?;
+                return write!(out, "\n{}", TIME_BUDGET_MARKER);
+            }
+            if hidden.contains(&idx) {
+                if idx == hidden.start {
+                    self.flush_unresolved_run(out, &mut unresolved_run, &mut state, render)?;
+                    self.write_separator(out, &mut state)?;
+                    write!(out, "\n      ... {} frames hidden ...", hidden_count)?;
+                }
+                // Still count the lines gdb would have numbered, so later numbers don't change
+                state.gdb_idx += self.inline_policy.apply(subframes).len().max(1);
+                continue;
+            }
+            if idx < repeats_end {
+                // Still count the lines gdb would have numbered, so later numbers don't change
+                state.gdb_idx += self.inline_policy.apply(subframes).len().max(1);
+                continue;
+            }
+            if let Some(cycle) = cycles.peek().copied() {
+                if cycle.frames().contains(&idx) {
+                    if idx == cycle.start {
+                        self.flush_unresolved_run(out, &mut unresolved_run, &mut state, render)?;
+                        if boundary.is_crossed_by(|| self.classify_frame(frame, subframes.clone()))
+                        {
+                            write!(out, "\n--- dependency frames ---")?;
+                        }
+                        self.write_separator(out, &mut state)?;
+                        let mut names: Vec<_> = keys[cycle.start..cycle.start + cycle.len]
+                            .iter()
+                            .flatten()
+                            .flatten()
+                            .map(|name| names::leaf_name(name))
+                            .collect();
+                        names.reverse();
+                        write!(out, "\n      ({}) × {}", names.join(" → "), cycle.repeats)?;
+                    }
+                    if idx + 1 == cycle.frames().end {
+                        cycles.next();
+                    }
+                    // Still count the lines gdb would have numbered, so later numbers don't change
+                    state.gdb_idx += self.inline_policy.apply(subframes).len().max(1);
+                    continue;
+                }
+            }
+            if self.macro_frames == MacroFramePolicy::Hide
+                && classify::is_macro_frame_impl(frame, subframes.clone())
+            {
+                // Still count the lines gdb would have numbered, so later numbers don't change
+                state.gdb_idx += self.inline_policy.apply(subframes).len().max(1);
+                continue;
+            }
+            if self.collapse_unresolved_runs
+                && !self.frame_annotations.contains_key(&frame_idx)
+                && is_fully_unresolved(frame, subframes.clone())
+            {
+                unresolved_run.push((idx, frame_idx, frame, subframes));
+                continue;
+            }
+            self.flush_unresolved_run(out, &mut unresolved_run, &mut state, render)?;
+
+            if boundary.is_crossed_by(|| self.classify_frame(frame, subframes.clone())) {
+                write!(out, "\n--- dependency frames ---")?;
+            }
+            self.write_frame(out, (idx, frame_idx, frame, subframes), &mut state, render)?;
+            if self.collapse_repeated_frames {
+                let count = repeats[idx + 1..]
+                    .iter()
+                    .take_while(|&&repeat| repeat)
+                    .count();
+                if count > 0 {
+                    write!(out, "\n      ... (repeated {} times)", count)?;
+                    repeats_end = idx + 1 + count;
+                }
+            }
+        }
+        self.flush_unresolved_run(out, &mut unresolved_run, &mut state, render)?;
+        if self.mark_thread_entry && query::thread_entry_frame_impl(backtrace).is_some() {
+            write!(out, "\n(thread entry)")?;
+        }
+        Ok(())
+    }
+
+    /// The positions (in `frames`) of the frames [`max_frames`][Self::max_frames] hides,
+    /// and how many of them would have been printed
+    fn hidden_frames<F: Frameish>(
+        &self,
+        frames: &[(usize, &F, Range<usize>)],
+    ) -> (Range<usize>, usize) {
+        let (head, tail) = match self.max_frames {
+            Some(max_frames) => max_frames,
+            None => return (0..0, 0),
+        };
+        let printed: Vec<usize> = frames
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, frame, subframes))| {
+                self.macro_frames != MacroFramePolicy::Hide
+                    || !classify::is_macro_frame_impl(*frame, subframes.clone())
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+        if printed.len() <= head.saturating_add(tail) {
+            return (0..0, 0);
+        }
+        let count = printed.len() - head - tail;
+        (printed[head]..printed[head + count - 1] + 1, count)
+    }
+
+    /// The frames to print, either the short backtrace or all of them
+    fn frames_impl<'a, B: Backtraceish>(
+        &self,
+        backtrace: &'a B,
+    ) -> impl Iterator<Item = (usize, &'a B::Frame, Range<usize>)> + 'a {
+        let (full, short) = if self.full_backtrace {
+            let all = backtrace.frames().iter().enumerate();
+            (
+                Some(all.map(|(idx, frame)| (idx, frame, 0..frame.symbols().len()))),
+                None,
+            )
+        } else {
+            (None, Some(short_frames_indexed_impl(backtrace)))
+        };
+        full.into_iter()
+            .flatten()
+            .chain(short.into_iter().flatten())
+    }
+
+    /// The index (in `backtrace.frames()`) of the first frame we print with a symbol whose
+    /// demangled name matches `is_match`
+    fn find_frame<B: Backtraceish>(
+        &self,
+        backtrace: &B,
+        is_match: impl Fn(&str) -> bool,
+    ) -> Option<usize> {
+        self.frames_impl(backtrace)
+            .find(|(_, frame, subframes)| {
+                frame.symbols()[subframes.clone()]
+                    .iter()
+                    .any(|symbol| demangled_name(symbol).map_or(false, |name| is_match(&name)))
+            })
+            .map(|(frame_idx, _, _)| frame_idx)
+    }
+
+    /// Where to print `(signal handler boundary)`, if we're marking it
+    #[cfg(unix)]
+    fn signal_boundary<B: Backtraceish>(&self, backtrace: &B) -> Option<usize> {
+        if self.mark_signal_boundary {
+            self.find_frame(backtrace, gunk::is_signal_trampoline_name)
+        } else {
+            None
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn signal_boundary<B: Backtraceish>(&self, _backtrace: &B) -> Option<usize> {
+        None
+    }
+
+    /// Writes out the pending run of unresolved frames, collapsed if there's more than one.
+    fn flush_unresolved_run<F: Frameish>(
+        &self,
+        out: &mut dyn fmt::Write,
+        run: &mut Vec<IndexedFrame<'_, F>>,
+        state: &mut WriteState<'_>,
+        render: RenderSymbolFn<'_, F::Symbol>,
+    ) -> fmt::Result {
+        if run.len() > 1 {
+            self.write_separator(out, state)?;
+            write!(out, "\n      ... {} unresolved frames ...", run.len())?;
+            // Still count the lines gdb would have numbered, so later numbers don't change
+            for (_, _, _, subframes) in run.drain(..) {
+                state.gdb_idx += self.inline_policy.apply(subframes).len().max(1);
+            }
+        }
+        for frame in run.drain(..) {
+            self.write_frame(out, frame, state, render)?;
+        }
+        Ok(())
+    }
+
+    fn write_frame<F: Frameish>(
+        &self,
+        out: &mut dyn fmt::Write,
+        (idx, frame_idx, frame, subframes): IndexedFrame<'_, F>,
+        state: &mut WriteState<'_>,
+        render: RenderSymbolFn<'_, F::Symbol>,
+    ) -> fmt::Result {
+        let is_first = !state.wrote_frame;
+        self.write_separator(out, state)?;
+        if let Some(tracker) = state.tracker {
+            tracker.starts.borrow_mut().push(tracker.position.get());
+        }
+        let unfiltered_subframes = subframes.clone();
+        let symbols = frame.symbols();
+        let highlighted = self.is_highlighted(&symbols[subframes.clone()]);
+        let color = if self.colorize {
+            let kind = self.classify_frame(frame, subframes.clone());
+            let is_panic_origin = state.panic_origin == Some(frame_idx);
+            self.color_map.escape(kind, is_panic_origin, highlighted)
+        } else {
+            None
+        };
+        // Without colors, highlighted frames get a `>` marker instead
+        let marked = highlighted && !self.colorize;
+        let subframes = self.inline_policy.apply(subframes);
+        match self.style {
+            FormatStyle::Default => {
+                if marked {
+                    write!(out, "\n{}>{:3}: ", self.frame_prefix, self.index_base + idx)?;
+                } else {
+                    write!(out, "\n{}{:4}: ", self.frame_prefix, self.index_base + idx)?;
+                }
+                if self.hide_addresses {
+                    write!(out, "{:1$}", "", HEX_WIDTH)?;
+                } else {
+                    self.write_address(out, frame)?;
+                }
+                if symbols.is_empty() {
+                    write!(out, " - <unresolved>")?;
+                } else {
+                    let symbols = self.subframe_order.iter(&symbols[subframes]);
+                    for (idx, symbol) in symbols.enumerate() {
+                        render(out, idx, symbol, color.as_deref())?;
+                    }
+                }
+            }
+            FormatStyle::SingleLine => {
+                let marker = if marked { "> " } else { "" };
+                write!(
+                    out,
+                    "\n{}{}{}: ",
+                    self.frame_prefix,
+                    marker,
+                    self.index_base + idx
+                )?;
+                match symbols.get(unfiltered_subframes.start) {
+                    Some(symbol) => self.write_single_line_symbol(out, symbol, color.as_deref())?,
+                    None => write!(out, "<unresolved>")?,
+                }
+            }
+            FormatStyle::CausedBy => {
+                let marker = if marked { "> " } else { "" };
+                let caused_by = if is_first { "" } else { "  caused by " };
+                write!(out, "\n{}{}{}", self.frame_prefix, marker, caused_by)?;
+                match symbols.get(unfiltered_subframes.start) {
+                    Some(symbol) => self.write_single_line_symbol(out, symbol, color.as_deref())?,
+                    None => write!(out, "<unresolved>")?,
+                }
+            }
+            FormatStyle::GdbLike => {
+                let marker = if marked { "> " } else { "" };
+                if symbols.is_empty() {
+                    write!(
+                        out,
+                        "\n{}{}#{:<3}?? ()",
+                        self.frame_prefix, marker, state.gdb_idx
+                    )?;
+                    state.gdb_idx += 1;
+                }
+                for symbol in self.subframe_order.iter(&symbols[subframes]) {
+                    self.write_gdb_symbol(out, state.gdb_idx, marker, symbol, color.as_deref())?;
+                    state.gdb_idx += 1;
+                }
+            }
+        }
+
+        if state.caught_at == Some(frame_idx) {
+            write!(out, "\n{:1$}(caught here)", "", NEXT_SYMBOL_PADDING)?;
+        }
+        if state.signal_at == Some(frame_idx) {
+            write!(
+                out,
+                "\n{:1$}(signal handler boundary)",
+                "", NEXT_SYMBOL_PADDING
+            )?;
+        }
+        if self.macro_frames == MacroFramePolicy::Annotate
+            && classify::is_macro_frame_impl(frame, unfiltered_subframes.clone())
+        {
+            write!(out, "\n{:1$}(macro)", "", NEXT_SYMBOL_PADDING)?;
+        }
+        if let Some(annotation) = self.frame_annotations.get(&frame_idx) {
+            write!(out, "\n{:2$}[{}]", "", annotation, NEXT_SYMBOL_PADDING)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the address of the frame, as `module+offset` if we know the module's name
+    fn write_address<F: Frameish>(&self, out: &mut dyn fmt::Write, frame: &F) -> fmt::Result {
+        let ip = frame.ip() as usize;
+        let module = frame.module_base_address().and_then(|base| {
+            let base = base as usize;
+            let name = self.module_names.get(&base)?;
+            Some((name, ip.checked_sub(base)?))
+        });
+        if let Some((name, offset)) = module {
+            let address = format!("{}+{:#x}", name, offset);
+            return write!(out, "{:>1$}", address, HEX_WIDTH);
+        }
+        let address = match self.address_base {
+            Some(base) => ip.checked_sub(base).unwrap_or(ip),
+            None => ip,
+        };
+        match &self.address_affixes {
+            Some((prefix, suffix)) => {
+                let mut formatted = String::new();
+                self.address_format.write(&mut formatted, address)?;
+                let trimmed = formatted.trim_start();
+                let padding = formatted.len() - trimmed.len();
+                write!(out, "{:1$}{2}{3}{4}", "", padding, prefix, trimmed, suffix)
+            }
+            None => self.address_format.write(out, address),
+        }
+    }
+
+    /// Writes the [`frame_separator`][Self::frame_separator] if this isn't the first frame
+    fn write_separator(&self, out: &mut dyn fmt::Write, state: &mut WriteState<'_>) -> fmt::Result {
+        if let (Some(separator), true) = (&self.frame_separator, state.wrote_frame) {
+            write!(out, "\n{}", separator)?;
+        }
+        state.wrote_frame = true;
+        Ok(())
+    }
+
+    fn classify_frame<F: Frameish>(&self, frame: &F, subframes: Range<usize>) -> FrameKind {
+        let user_prefix = self.user_prefix.as_deref().unwrap_or("");
+        classify::classify_frame_impl(frame, subframes, user_prefix)
+    }
+
+    /// Writes the name of the symbol (which must have one), in `color` if there is one
+    fn write_name<S: Symbolish>(
+        &self,
+        out: &mut dyn fmt::Write,
+        symbol: &S,
+        color: Option<&str>,
+    ) -> fmt::Result {
+        if let Some(color) = color {
+            out.write_str(color)?;
+        }
+        let demangled = match (&self.demangler, symbol.name_str()) {
+            (Some(demangler), Some(raw)) if !symbol.is_rust() => (demangler.0)(raw),
+            _ => None,
+        };
+        let rewrite =
+            self.fold_generics || self.simplify_closures || self.middle_truncate_names.is_some();
+        match (demangled, rewrite) {
+            (Some(name), true) => out.write_str(&self.rewrite_name(name))?,
+            (Some(name), false) => out.write_str(&name)?,
+            (None, true) => {
+                let name = SymbolNameDisplay(symbol).to_string();
+                out.write_str(&self.rewrite_name(name))?;
+            }
+            (None, false) => write!(out, "{}", SymbolNameDisplay(symbol))?,
+        }
+        if color.is_some() {
+            out.write_str(color::RESET)?;
+        }
+        if let (true, Some(raw)) = (self.show_mangled, symbol.name_bytes()) {
+            write!(out, " [{}]", String::from_utf8_lossy(raw))?;
+        }
+        Ok(())
+    }
+
+    /// Applies [`fold_generics`][Self::fold_generics],
+    /// [`simplify_closures`][Self::simplify_closures] and
+    /// [`middle_truncate_names`][Self::middle_truncate_names] to a name
+    fn rewrite_name(&self, mut name: String) -> String {
+        if self.fold_generics {
+            name = names::fold_generics(&name);
+        }
+        if self.simplify_closures {
+            name = names::simplify_closures(&name);
+        }
+        if let Some(max) = self.middle_truncate_names {
+            name = names::middle_truncate_name(&name, max);
+        }
+        name
+    }
+
+    /// Writes `[crate] ` if [`show_crate_tag`][Self::show_crate_tag] is on
+    fn write_crate_tag<S: Symbolish>(&self, out: &mut dyn fmt::Write, symbol: &S) -> fmt::Result {
+        if !self.show_crate_tag {
+            return Ok(());
+        }
+        if symbol.has_name() && !symbol.is_rust() {
+            return write!(out, "[C] ");
+        }
+        let name = demangled_name(symbol).unwrap_or_default();
+        // A bare name isn't in a crate
+        if names::path_segments(&name).len() < 2 {
+            return Ok(());
+        }
+        match names::crate_name(&name) {
+            Some(crate_name) => write!(out, "[{}] ", crate_name),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether any of the symbols matches a [highlight][Self::highlight_substring] pattern
+    fn is_highlighted<S: Symbolish>(&self, symbols: &[S]) -> bool {
+        !self.highlights.is_empty()
+            && symbols.iter().filter_map(demangled_name).any(|name| {
+                self.highlights
+                    .iter()
+                    .any(|highlight| highlight.is_match(&name))
+            })
+    }
+
+    /// The path to print for a source file, relative to the
+    /// [`workspace_root`][Self::workspace_root] if it's in there
+    fn display_path<'a>(&self, file: &'a Path) -> &'a Path {
+        self.workspace_root
+            .as_deref()
+            .and_then(|root| file.strip_prefix(root).ok())
+            .unwrap_or(file)
+    }
+
+    /// The symbol's `file:line`, if we know it and are supposed to print it
+    fn file_line<'s, S: Symbolish>(&self, symbol: &'s S) -> Option<(&'s Path, u32)> {
+        if self.hide_file_lines {
+            return None;
+        }
+        Some((symbol.filename()?, symbol.lineno()?))
+    }
+
+    fn write_gdb_symbol<S: Symbolish>(
+        &self,
+        out: &mut dyn fmt::Write,
+        index: usize,
+        marker: &str,
+        symbol: &S,
+        color: Option<&str>,
+    ) -> fmt::Result {
+        write!(out, "\n{}{}#{:<3}", self.frame_prefix, marker, index)?;
+        if symbol.has_name() {
+            self.write_crate_tag(out, symbol)?;
+            self.write_name(out, symbol, color)?;
+        } else {
+            write!(out, "??")?;
+        }
+        match self.file_line(symbol) {
+            Some((file, line)) => {
+                write!(out, " at {}:{}", self.display_path(file).display(), line)
+            }
+            _ => write!(out, " ()"),
+        }
+    }
+
+    fn write_single_line_symbol<S: Symbolish>(
+        &self,
+        out: &mut dyn fmt::Write,
+        symbol: &S,
+        color: Option<&str>,
+    ) -> fmt::Result {
+        if symbol.has_name() {
+            self.write_crate_tag(out, symbol)?;
+            self.write_name(out, symbol, color)?;
+        } else {
+            write!(out, "<unknown>")?;
+        }
+        if let Some((file, line)) = self.file_line(symbol) {
+            write!(out, " ({}:{}", self.display_path(file).display(), line)?;
+            if let (true, Some(col)) = (self.show_columns, symbol.colno()) {
+                write!(out, ":{}", col)?;
+            }
+            write!(out, ")")?;
+        }
+        Ok(())
+    }
+
+    fn render_symbol<S: Symbolish>(
+        &self,
+        out: &mut dyn fmt::Write,
+        index: usize,
+        symbol: &S,
+        color: Option<&str>,
+    ) -> fmt::Result {
+        // Print symbols from this address,
+        // if there are several addresses
+        // we need to put it on next line
+        if index != 0 {
+            write!(out, "\n{:1$}", "", NEXT_SYMBOL_PADDING)?;
+        }
+
+        if symbol.has_name() {
+            write!(out, " - ")?;
+            self.write_crate_tag(out, symbol)?;
+            self.write_name(out, symbol, color)?;
+        } else {
+            write!(out, " - <unknown>")?;
+        }
+
+        // See if there is debug information with file name and line
+        if let Some((file, line)) = self.file_line(symbol) {
+            write!(
+                out,
+                "\n{:3$}at {}:{}",
+                "",
+                self.display_path(file).display(),
+                line,
+                NEXT_SYMBOL_PADDING
+            )?;
+            if let (true, Some(col)) = (self.show_columns, symbol.colno()) {
+                write!(out, ":{}", col)?;
+            }
+            #[cfg(feature = "source-snippets")]
+            if let Some(radius) = self.source_context {
+                if let Some(snippet) = snippet::source_snippet(file, line, radius) {
+                    for snippet_line in snippet.lines() {
+                        write!(out, "\n{:2$}{}", "", snippet_line, NEXT_SYMBOL_PADDING + 2)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Tracks when we should print the `--- dependency frames ---` line.
+enum DependencyBoundary {
+    /// We aren't looking for it (or already printed it)
+    Done,
+    /// We haven't seen any user code yet
+    BeforeUser,
+    /// We've seen user code, so the next dependency frame crosses the boundary
+    InUser,
+}
+
+impl DependencyBoundary {
+    fn new(enabled: bool) -> Self {
+        if enabled {
+            DependencyBoundary::BeforeUser
+        } else {
+            DependencyBoundary::Done
+        }
+    }
+
+    /// Returns true if the boundary should be printed before a frame of this kind
+    /// (which is only computed if we need it)
+    fn is_crossed_by(&mut self, kind: impl FnOnce() -> FrameKind) -> bool {
+        if let DependencyBoundary::Done = self {
+            return false;
+        }
+        match (&*self, kind()) {
+            (_, FrameKind::User) => {
+                *self = DependencyBoundary::InUser;
+                false
+            }
+            (DependencyBoundary::InUser, FrameKind::Dependency) => {
+                *self = DependencyBoundary::Done;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The state that [`ShortBacktraceFormatter::write_frames_impl`][] carries from
+/// frame to frame
+struct WriteState<'a> {
+    /// gdb gives every inlined call its own number
+    gdb_idx: usize,
+    /// Whether any frame was written yet, so we know to write a separator
+    wrote_frame: bool,
+    /// The index (in `backtrace.frames()`) of the frame that called `panic!`
+    panic_origin: Option<usize>,
+    /// The index (in `backtrace.frames()`) of the frame where `catch_unwind` caught the panic
+    caught_at: Option<usize>,
+    /// The index (in `backtrace.frames()`) of the signal trampoline frame
+    signal_at: Option<usize>,
+    /// Where to record the start of each frame, for `format_with_offsets`
+    tracker: Option<&'a FrameTracker>,
+}
+
+/// A frame of the short backtrace, with its index in the short backtrace and in
+/// `backtrace.frames()`
+type IndexedFrame<'a, F> = (usize, usize, &'a F, Range<usize>);
+
+/// Whether none of the frame's symbols have a name or a source file
+fn is_fully_unresolved<F: Frameish>(frame: &F, subframes: Range<usize>) -> bool {
+    frame.symbols()[subframes]
+        .iter()
+        .all(|symbol| !is_resolved(symbol))
+}
+
+/// Renders one symbol of a frame (see [`SymbolRenderer`][]), with the color for its name
+/// if [`colorize`][ShortBacktraceFormatter::colorize] is on
+pub(crate) type RenderFn<'a, B> =
+    RenderSymbolFn<'a, <<B as Backtraceish>::Frame as Frameish>::Symbol>;
+
+/// [`RenderFn`][] in terms of the symbol type
+pub(crate) type RenderSymbolFn<'a, S> =
+    &'a dyn Fn(&mut dyn fmt::Write, usize, &S, Option<&str>) -> fmt::Result;
+
+/// A fmt::Write that turns every `\n` into `\r\n`.
+struct CrLfWriter<'a>(&'a mut dyn fmt::Write);
+
+impl<'a> fmt::Write for CrLfWriter<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut lines = s.split('\n');
+        if let Some(first) = lines.next() {
+            self.0.write_str(first)?;
+        }
+        for line in lines {
+            self.0.write_str("\r\n")?;
+            self.0.write_str(line)?;
+        }
+        Ok(())
+    }
+}
+
+/// A fmt::Write that puts `indent` spaces after every `\n`.
+struct IndentWriter<'a> {
+    out: &'a mut dyn fmt::Write,
+    indent: usize,
+}
+
+impl<'a> fmt::Write for IndentWriter<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut lines = s.split('\n');
+        if let Some(first) = lines.next() {
+            self.out.write_str(first)?;
+        }
+        for line in lines {
+            write!(self.out, "\n{:1$}", "", self.indent)?;
+            self.out.write_str(line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Estimates how many bytes a default [`ShortBacktraceFormatter`][] would write for the
+/// backtrace, as a capacity hint for `String::with_capacity`.
+///
+/// This is only an estimate, it can be off in either direction: it's based on the raw
+/// (mangled) length of each name and assumes line numbers have a few digits, so it's
+/// much cheaper than formatting but doesn't know exactly how names demangle. Formatter
+/// options aren't taken into account.
+pub fn estimate_formatted_len(backtrace: &Backtrace) -> usize {
+    estimate_formatted_len_impl(backtrace)
+}
+
+pub(crate) fn estimate_formatted_len_impl<B: Backtraceish>(backtrace: &B) -> usize {
+    // `\n{:4}: ` and the address
+    const FRAME_HEADER_LEN: usize = 7 + HEX_WIDTH;
+    // A newline and the padding for the next line of the frame
+    const NEXT_LINE_LEN: usize = 1 + NEXT_SYMBOL_PADDING;
+    // `at ` and `:` around the file and line, and a guess at the digits of the line
+    const LOCATION_LEN: usize = NEXT_LINE_LEN + 4 + 4;
+
+    short_frames_strict_impl(backtrace)
+        .map(|(frame, subframes)| {
+            let symbols = &frame.symbols()[subframes];
+            if symbols.is_empty() {
+                return FRAME_HEADER_LEN + " - <unresolved>".len();
+            }
+            let symbols_len: usize = symbols
+                .iter()
+                .map(|symbol| {
+                    let name_len = match symbol.name_bytes() {
+                        Some(name) => name.len(),
+                        None => "<unknown>".len(),
+                    };
+                    let location_len = match (symbol.filename(), symbol.lineno()) {
+                        (Some(file), Some(_)) => LOCATION_LEN + file.as_os_str().len(),
+                        _ => 0,
+                    };
+                    " - ".len() + name_len + location_len
+                })
+                .sum();
+            FRAME_HEADER_LEN + symbols_len + NEXT_LINE_LEN * (symbols.len() - 1)
+        })
+        .sum()
+}
+
+/// Appends the short backtrace to `out`, with every line indented by `indent` spaces.
+///
+/// This is meant for `Display` impls of errors that print a chain of causes, so the
+/// backtrace can nest under them. Like the rest of the formatter's output, it starts with
+/// a newline. It's the same as a default [`ShortBacktraceFormatter`][] with
+/// [`indent`][ShortBacktraceFormatter::indent].
+pub fn append_short_backtrace(out: &mut String, indent: usize, backtrace: &Backtrace) {
+    append_short_backtrace_impl(out, indent, backtrace)
+}
+
+pub(crate) fn append_short_backtrace_impl<B: Backtraceish>(
+    out: &mut String,
+    indent: usize,
+    backtrace: &B,
+) {
+    // Writing to a String can't fail
+    let _ = ShortBacktraceFormatter::new()
+        .indent(indent)
+        .write_impl(out, backtrace);
+}
+
+/// Appends the short backtrace to `buf` as UTF-8 bytes, formatted by a default
+/// [`ShortBacktraceFormatter`][] (see [`ShortBacktraceFormatter::append_bytes`][]).
+pub fn write_short_backtrace_bytes(buf: &mut Vec<u8>, backtrace: &Backtrace) {
+    ShortBacktraceFormatter::new().append_bytes_impl(buf, backtrace)
+}
+
+/// What's written after output that got cut off by [`ShortBacktraceFormatter::max_bytes`][].
+const TRUNCATED_MARKER: &str = "... (truncated)";
+
+/// What's written after the frames when [`ShortBacktraceFormatter::time_budget`][] ran out.
+const TIME_BUDGET_MARKER: &str = "... (time budget exceeded)";
+
+/// A fmt::Write that passes through at most `remaining` bytes, and then refuses to
+/// write more.
+struct LimitWriter<'a> {
+    out: &'a mut dyn fmt::Write,
+    remaining: usize,
+    truncated: bool,
+}
+
+impl<'a> fmt::Write for LimitWriter<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.truncated {
+            return Err(fmt::Error);
+        }
+        if s.len() <= self.remaining {
+            self.remaining -= s.len();
+            return self.out.write_str(s);
+        }
+        // Doesn't fit, so write as much as we can without splitting a char
+        let mut len = self.remaining;
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.out.write_str(&s[..len])?;
+        self.remaining = 0;
+        self.truncated = true;
+        Err(fmt::Error)
+    }
+}
+
+/// Where each frame started in the output of `format_with_offsets`.
+#[derive(Default)]
+struct FrameTracker {
+    /// How many bytes have been written so far
+    position: Cell<usize>,
+    starts: RefCell<Vec<usize>>,
+}
+
+/// A fmt::Write that counts how many bytes went through it in a [`FrameTracker`][].
+struct CountingWriter<'a> {
+    out: &'a mut dyn fmt::Write,
+    tracker: &'a FrameTracker,
+}
+
+impl<'a> fmt::Write for CountingWriter<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.out.write_str(s)?;
+        let position = &self.tracker.position;
+        position.set(position.get() + s.len());
+        Ok(())
+    }
+}
+
+/// A fmt::Write that appends to a byte buffer.
+struct VecWriter<'a>(&'a mut Vec<u8>);
+
+impl<'a> fmt::Write for VecWriter<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.extend_from_slice(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// A fmt::Write that fills up a fixed buffer and then refuses to write more.
+struct SliceWriter<'a> {
+    buf: &'a mut [u8],
+    written: usize,
+    truncated: bool,
+}
+
+impl<'a> fmt::Write for SliceWriter<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.truncated {
+            return Err(fmt::Error);
+        }
+        let remaining = self.buf.len() - self.written;
+        let mut len = s.len();
+        if len > remaining {
+            // Doesn't fit, so write as much as we can without splitting a char
+            len = remaining;
+            while !s.is_char_boundary(len) {
+                len -= 1;
+            }
+            self.truncated = true;
+        }
+        self.buf[self.written..self.written + len].copy_from_slice(&s.as_bytes()[..len]);
+        self.written += len;
+        if self.truncated {
+            Err(fmt::Error)
+        } else {
+            Ok(())
+        }
+    }
+}
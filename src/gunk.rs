@@ -0,0 +1,479 @@
+//! Knowledge about the "gunk" frames that show up inside a short backtrace.
+
+use crate::*;
+
+/// Symbols that commonly show up in a short backtrace but are just glue
+/// (mostly the panic runtime and closure-calling shims).
+///
+/// These are matched against the demangled symbol name with `contains`.
+pub const GUNK_SYMBOLS: &[&str] = &[
+    "core::ops::function::FnOnce::call_once",
+    "std::panicking::begin_panic_handler",
+    "core::panicking::panic_fmt",
+    "rust_begin_unwind",
+];
+
+/// The subset of [`GUNK_SYMBOLS`][] that are part of the panic runtime, which sit right
+/// on top of the frame that called `panic!`.
+pub const PANIC_SYMBOLS: &[&str] = &[
+    "std::panicking::begin_panic_handler",
+    "core::panicking::panic_fmt",
+    "rust_begin_unwind",
+];
+
+/// Whether this (demangled) symbol name is one of the [`GUNK_SYMBOLS`][].
+pub(crate) fn is_gunk_name(name: &str) -> bool {
+    GUNK_SYMBOLS.iter().any(|gunk| name.contains(gunk))
+}
+
+/// Symbols from libtest's harness that can show up around the body of a `#[test]`.
+///
+/// These are matched against the start of the demangled symbol name (so a module
+/// of your own that happens to be called `test` won't match).
+pub const TEST_HARNESS_SYMBOLS: &[&str] = &[
+    "test::run_test",
+    "test::types::RunnableTest::run",
+    "<test::types::RunnableTest>::run",
+    "test::__rust_begin_short_backtrace",
+];
+
+/// Like [`short_frames_strict`][], but also cuts off any of libtest's harness
+/// (see [`TEST_HARNESS_SYMBOLS`][]).
+///
+/// The first harness symbol we find is treated like an extra `rust_begin_short_backtrace`,
+/// so it and everything older than it is dropped. This makes sure the backtrace of a
+/// failing test ends at the body of the test.
+pub fn short_frames_without_test_harness(
+    backtrace: &Backtrace,
+) -> impl Iterator<Item = ShortFrame<'_>> {
+    short_frames_without_test_harness_impl(backtrace).map(ShortFrame::from)
+}
+
+pub(crate) fn short_frames_without_test_harness_impl<B: Backtraceish>(
+    backtrace: &B,
+) -> impl Iterator<Item = (&B::Frame, Range<usize>)> {
+    cut_at_first(short_frames_strict_impl(backtrace), |name| {
+        TEST_HARNESS_SYMBOLS
+            .iter()
+            .any(|harness| name.starts_with(harness))
+    })
+}
+
+/// Symbols from the standard library's locks that show up when something panics on a
+/// poisoned lock (usually by `unwrap`ping the result of `lock()`).
+///
+/// These are matched against the demangled symbol name with `contains`.
+pub const SYNC_GLUE_SYMBOLS: &[&str] = &[
+    "std::sync::poison",
+    "std::sync::mutex::Mutex",
+    "std::sync::mutex::MutexGuard",
+    "std::sync::rwlock::RwLock",
+    "std::sync::rwlock::RwLockReadGuard",
+    "std::sync::rwlock::RwLockWriteGuard",
+    "<std::sync::poison::PoisonError",
+];
+
+/// Like [`short_frames_strict`][], but trims off the glue that survives inside the short
+/// backtrace (see [`GUNK_SYMBOLS`][]).
+///
+/// This is [`short_frames_without_symbols`][] with [`GUNK_SYMBOLS`][], so a frame that
+/// has a real symbol inlined together with the glue still yields the real one.
+pub fn short_frames_without_gunk(backtrace: &Backtrace) -> impl Iterator<Item = ShortFrame<'_>> {
+    short_frames_without_gunk_impl(backtrace).map(ShortFrame::from)
+}
+
+pub(crate) fn short_frames_without_gunk_impl<B: Backtraceish>(
+    backtrace: &B,
+) -> impl Iterator<Item = (&B::Frame, Range<usize>)> {
+    short_frames_without_symbols_impl(backtrace, GUNK_SYMBOLS)
+}
+
+/// Like [`short_frames_strict`][], but trims off the lock and poisoning glue of the
+/// standard library (see [`SYNC_GLUE_SYMBOLS`][]).
+///
+/// This is [`short_frames_without_symbols`][] with [`SYNC_GLUE_SYMBOLS`][].
+pub fn short_frames_without_sync_glue(
+    backtrace: &Backtrace,
+) -> impl Iterator<Item = ShortFrame<'_>> {
+    short_frames_without_symbols_impl(backtrace, SYNC_GLUE_SYMBOLS).map(ShortFrame::from)
+}
+
+/// Like [`short_frames_strict`][], but trims off any symbols whose demangled name
+/// contains one of `symbols`.
+///
+/// Inlined symbols can only be trimmed from either end of a frame's range (so the range
+/// stays contiguous), and frames that don't have any symbols left are dropped entirely.
+/// Frames with no symbols at all are kept, since we can't know what they are.
+pub fn short_frames_without_symbols<'a>(
+    backtrace: &'a Backtrace,
+    symbols: &'a [&'a str],
+) -> impl Iterator<Item = ShortFrame<'a>> {
+    short_frames_without_symbols_impl(backtrace, symbols).map(ShortFrame::from)
+}
+
+pub(crate) fn short_frames_without_symbols_impl<'a, B: Backtraceish>(
+    backtrace: &'a B,
+    symbols: &'a [&'a str],
+) -> impl Iterator<Item = (&'a B::Frame, Range<usize>)> {
+    trim_matching(short_frames_strict_impl(backtrace), move |name| {
+        symbols.iter().any(|symbol| name.contains(symbol))
+    })
+}
+
+/// Like [`short_frames_strict`][], but only keeps the symbols whose demangled name starts
+/// with one of `prefixes`, for only seeing your own code (like `&["myapp::", "mylib::"]`).
+///
+/// The prefixes are compared against the demangled path without the hash, so
+/// `_ZN5myapp4main17h0123456789abcdefE` matches `"myapp::"`. Each frame's range is narrowed
+/// to go from its first matching symbol to its last one (so the range stays contiguous, and
+/// can still have non-matching inlined symbols in the middle). Frames without any matching
+/// symbols are dropped entirely, including frames with no symbols at all.
+pub fn short_frames_only_from<'a>(
+    backtrace: &'a Backtrace,
+    prefixes: &'a [&'a str],
+) -> impl Iterator<Item = ShortFrame<'a>> {
+    short_frames_only_from_impl(backtrace, prefixes).map(ShortFrame::from)
+}
+
+pub(crate) fn short_frames_only_from_impl<'a, B: Backtraceish>(
+    backtrace: &'a B,
+    prefixes: &'a [&'a str],
+) -> impl Iterator<Item = (&'a B::Frame, Range<usize>)> {
+    short_frames_strict_impl(backtrace).filter_map(move |(frame, range)| {
+        let symbols = frame.symbols();
+        let matches = |idx: &usize| {
+            demangled_name(&symbols[*idx]).map_or(false, |name| {
+                prefixes.iter().any(|prefix| name.starts_with(prefix))
+            })
+        };
+        let start = range.clone().find(matches)?;
+        let end = range.rev().find(matches)? + 1;
+        Some((frame, start..end))
+    })
+}
+
+/// Like [`short_frames_strict`][], but drops the frames whose instruction pointer is in
+/// `ips`, for hiding code you know the addresses of but not the names (like a JIT region,
+/// an injected shim, or a whole module, see [`FrameOffset`][]).
+pub fn short_frames_exclude_ip_range(
+    backtrace: &Backtrace,
+    ips: Range<usize>,
+) -> impl Iterator<Item = ShortFrame<'_>> {
+    short_frames_exclude_ip_range_impl(backtrace, ips).map(ShortFrame::from)
+}
+
+pub(crate) fn short_frames_exclude_ip_range_impl<B: Backtraceish>(
+    backtrace: &B,
+    ips: Range<usize>,
+) -> impl Iterator<Item = (&B::Frame, Range<usize>)> {
+    short_frames_strict_impl(backtrace)
+        .filter(move |(frame, _)| !ips.contains(&(frame.ip() as usize)))
+}
+
+/// Narrows each frame's range past any symbols at either end that match `is_trimmed`,
+/// and drops the frames that had all of their symbols trimmed.
+pub(crate) fn trim_matching<'a, F: Frameish + 'a>(
+    frames: impl Iterator<Item = (&'a F, Range<usize>)>,
+    is_trimmed: impl Fn(&str) -> bool,
+) -> impl Iterator<Item = (&'a F, Range<usize>)> {
+    frames.filter_map(move |(frame, mut range)| {
+        let symbols = frame.symbols();
+        if symbols.is_empty() {
+            return Some((frame, range));
+        }
+        let trimmed =
+            |idx: usize| demangled_name(&symbols[idx]).map_or(false, |name| is_trimmed(&name));
+        while !range.is_empty() && trimmed(range.start) {
+            range.start += 1;
+        }
+        while !range.is_empty() && trimmed(range.end - 1) {
+            range.end -= 1;
+        }
+        if range.is_empty() {
+            None
+        } else {
+            Some((frame, range))
+        }
+    })
+}
+
+/// Symbols that show up where a panic is caught by `std::panic::catch_unwind`.
+///
+/// This is a heuristic: these are the names std's implementation has used, but how
+/// `catch_unwind` is implemented (and what gets inlined away) changes between versions.
+/// These are matched against the demangled symbol name with `contains`.
+pub const CATCH_UNWIND_SYMBOLS: &[&str] = &[
+    "std::panic::catch_unwind",
+    "std::panicking::catch_unwind",
+    "std::panicking::try",
+    "__rust_try",
+];
+
+/// Whether this (demangled) symbol name is one of the [`CATCH_UNWIND_SYMBOLS`][].
+pub(crate) fn is_catch_unwind_name(name: &str) -> bool {
+    CATCH_UNWIND_SYMBOLS
+        .iter()
+        .any(|catch| name.contains(catch))
+}
+
+/// Symbols that show up when an allocation failed (usually because the process ran out of
+/// memory) and the allocation error handler was called, instead of an ordinary panic.
+///
+/// These are matched against the demangled symbol name with `contains`.
+pub const ALLOC_ERROR_SYMBOLS: &[&str] = &[
+    "alloc::alloc::handle_alloc_error",
+    "__rust_alloc_error_handler",
+    "std::alloc::rust_oom",
+    "std::alloc::default_alloc_error_hook",
+    "__rdl_oom",
+    "__rg_oom",
+];
+
+/// Checks whether the backtrace went through the allocation error handler (see
+/// [`ALLOC_ERROR_SYMBOLS`][]), to tell crashes from running out of memory apart from
+/// ordinary bugs when triaging them.
+///
+/// The whole backtrace is searched, not just the short backtrace, since the handler
+/// isn't a panic so it isn't necessarily inside the markers.
+pub fn is_alloc_failure(backtrace: &Backtrace) -> bool {
+    is_alloc_failure_impl(backtrace)
+}
+
+pub(crate) fn is_alloc_failure_impl<B: Backtraceish>(backtrace: &B) -> bool {
+    backtrace
+        .frames()
+        .iter()
+        .flat_map(|frame| frame.symbols())
+        .filter_map(demangled_name)
+        .any(|name| ALLOC_ERROR_SYMBOLS.iter().any(|alloc| name.contains(alloc)))
+}
+
+/// Symbols that show up when the process is aborting instead of unwinding, either because
+/// something asked for it or because the panic runtime gave up.
+///
+/// These are matched against the demangled symbol name with `contains`.
+pub const ABORT_SYMBOLS: &[&str] = &[
+    "std::process::abort",
+    "core::intrinsics::abort",
+    "std::sys::abort_internal",
+    "std::sys::pal::unix::abort_internal",
+    "std::sys::pal::windows::abort_internal",
+];
+
+/// Symbols of the panics that aren't allowed to unwind, which abort right after the panic
+/// hook runs. These show up when a destructor panics while a panic is already unwinding.
+///
+/// These are matched against the demangled symbol name with `contains`.
+pub const NOUNWIND_PANIC_SYMBOLS: &[&str] = &[
+    "core::panicking::panic_in_cleanup",
+    "core::panicking::panic_cannot_unwind",
+    "core::panicking::panic_nounwind",
+];
+
+/// Every panic goes through this once, so seeing it twice means a second panic started
+/// before the first one was done.
+const PANIC_HOOK_SYMBOL: &str = "std::panicking::rust_panic_with_hook";
+
+/// Why a backtrace is from an abort, from [`abort_kind`][].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AbortKind {
+    /// The process is aborting (see [`ABORT_SYMBOLS`][]), like from an explicit
+    /// [`std::process::abort`][] or running out of memory.
+    Abort,
+    /// A panic started while another one was still unwinding, like a destructor that
+    /// panicked during unwinding. The runtime aborts as soon as the panic hook returns.
+    ///
+    /// This is recognized by the panic runtime appearing twice on the stack, or by one of
+    /// the [`NOUNWIND_PANIC_SYMBOLS`][], so it's found even in a backtrace captured by the
+    /// panic hook (before the abort itself happens).
+    PanicWhilePanicking,
+}
+
+/// Checks whether the backtrace is from a hard abort rather than a panic that could be
+/// recovered from, so triage code can tell these apart. Returns `None` for anything else.
+///
+/// [`AbortKind::PanicWhilePanicking`][] takes precedence over [`AbortKind::Abort`][],
+/// since a double panic ends in an abort too. Like [`is_alloc_failure`][], the whole
+/// backtrace is searched, not just the short backtrace.
+pub fn abort_kind(backtrace: &Backtrace) -> Option<AbortKind> {
+    abort_kind_impl(backtrace)
+}
+
+pub(crate) fn abort_kind_impl<B: Backtraceish>(backtrace: &B) -> Option<AbortKind> {
+    let mut panics = 0;
+    let mut aborts = false;
+    for name in backtrace
+        .frames()
+        .iter()
+        .flat_map(|frame| frame.symbols())
+        .filter_map(demangled_name)
+    {
+        if NOUNWIND_PANIC_SYMBOLS
+            .iter()
+            .any(|nounwind| name.contains(nounwind))
+        {
+            return Some(AbortKind::PanicWhilePanicking);
+        }
+        if name.contains(PANIC_HOOK_SYMBOL) {
+            panics += 1;
+            if panics > 1 {
+                return Some(AbortKind::PanicWhilePanicking);
+            }
+        }
+        aborts |= ABORT_SYMBOLS.iter().any(|abort| name.contains(abort));
+    }
+    if aborts {
+        Some(AbortKind::Abort)
+    } else {
+        None
+    }
+}
+
+/// Symbols of the trampolines that the OS returns through when a signal handler finishes,
+/// which sit between a signal handler and the code that was interrupted by the signal.
+///
+/// These are `__restore_rt` (glibc and musl on x86_64), `__kernel_rt_sigreturn` (the
+/// Linux vDSO on aarch64 and others) and `_sigtramp` (macOS and the BSDs). They're
+/// matched against the whole demangled symbol name.
+#[cfg(unix)]
+pub const SIGNAL_TRAMPOLINE_SYMBOLS: &[&str] =
+    &["__restore_rt", "__kernel_rt_sigreturn", "_sigtramp"];
+
+/// Whether this (demangled) symbol name is one of the [`SIGNAL_TRAMPOLINE_SYMBOLS`][].
+#[cfg(unix)]
+pub(crate) fn is_signal_trampoline_name(name: &str) -> bool {
+    SIGNAL_TRAMPOLINE_SYMBOLS.contains(&name)
+}
+
+/// Finds where the backtrace crosses from a signal handler into the code the signal
+/// interrupted: the first (newest) frame in the short backtrace with one of the
+/// [`SIGNAL_TRAMPOLINE_SYMBOLS`][].
+///
+/// Returns the frame and the index of the matching symbol in `frame.symbols()`. Frames
+/// newer than it ran in the signal handler. Unwinding through a signal frame is very
+/// platform-specific, so it may also just be where the backtrace stops.
+#[cfg(unix)]
+pub fn signal_trampoline_frame(backtrace: &Backtrace) -> Option<(&BacktraceFrame, usize)> {
+    signal_trampoline_frame_impl(backtrace)
+}
+
+#[cfg(unix)]
+pub(crate) fn signal_trampoline_frame_impl<B: Backtraceish>(
+    backtrace: &B,
+) -> Option<(&B::Frame, usize)> {
+    short_frames_strict_impl(backtrace).find_map(|(frame, subframes)| {
+        let symbols = frame.symbols();
+        subframes
+            .clone()
+            .find(|&idx| {
+                demangled_name(&symbols[idx]).map_or(false, |name| is_signal_trampoline_name(&name))
+            })
+            .map(|idx| (frame, idx))
+    })
+}
+
+/// Finds where the panic was caught by `catch_unwind`: the first (newest) frame in the
+/// short backtrace with one of the [`CATCH_UNWIND_SYMBOLS`][].
+///
+/// Returns the frame and the index of the matching symbol in `frame.symbols()`.
+/// If this returns `None` the panic probably wasn't caught (or it was caught outside the
+/// short backtrace, like libtest does).
+pub fn catch_unwind_frame(backtrace: &Backtrace) -> Option<(&BacktraceFrame, usize)> {
+    catch_unwind_frame_impl(backtrace)
+}
+
+pub(crate) fn catch_unwind_frame_impl<B: Backtraceish>(
+    backtrace: &B,
+) -> Option<(&B::Frame, usize)> {
+    short_frames_strict_impl(backtrace).find_map(|(frame, subframes)| {
+        let symbols = frame.symbols();
+        subframes
+            .clone()
+            .find(|&idx| {
+                demangled_name(&symbols[idx]).map_or(false, |name| is_catch_unwind_name(&name))
+            })
+            .map(|idx| (frame, idx))
+    })
+}
+
+/// Like [`short_frames_strict`][], but stops where the panic was caught by
+/// `catch_unwind` (see [`catch_unwind_frame`][]), dropping the `catch_unwind` machinery
+/// and everything that called it.
+///
+/// This shows just the part of the backtrace that was unwound. If there's no
+/// `catch_unwind` it's the same as [`short_frames_strict`][].
+pub fn short_frames_until_catch_unwind(
+    backtrace: &Backtrace,
+) -> impl Iterator<Item = ShortFrame<'_>> {
+    short_frames_until_catch_unwind_impl(backtrace).map(ShortFrame::from)
+}
+
+pub(crate) fn short_frames_until_catch_unwind_impl<B: Backtraceish>(
+    backtrace: &B,
+) -> impl Iterator<Item = (&B::Frame, Range<usize>)> {
+    cut_at_first(short_frames_strict_impl(backtrace), is_catch_unwind_name)
+}
+
+/// Stops the iterator at the first symbol whose demangled name matches `is_cut`,
+/// dropping that symbol and everything after it.
+pub(crate) fn cut_at_first<'a, F: Frameish + 'a>(
+    frames: impl Iterator<Item = (&'a F, Range<usize>)>,
+    is_cut: impl Fn(&str) -> bool,
+) -> impl Iterator<Item = (&'a F, Range<usize>)> {
+    frames
+        .scan(false, move |done, (frame, range)| {
+            if *done {
+                return None;
+            }
+            let symbols = frame.symbols();
+            let cut = range
+                .clone()
+                .find(|&idx| demangled_name(&symbols[idx]).map_or(false, |name| is_cut(&name)));
+            match cut {
+                Some(cut) => {
+                    *done = true;
+                    Some((frame, range.start..cut))
+                }
+                None => Some((frame, range)),
+            }
+        })
+        // Don't yield the frame we cut at if nothing was left of it
+        .filter(|(frame, range)| !range.is_empty() || frame.symbols().is_empty())
+}
+
+/// Gets just the frames around the panic runtime (see [`PANIC_SYMBOLS`][]) in the short
+/// backtrace.
+///
+/// This yields the frames that are within `radius` frames of the oldest frame with a panic
+/// runtime symbol, which means the panic plumbing itself and the code that called `panic!`
+/// (and whatever called that, for larger radii). If there's no panic runtime in the short
+/// backtrace (say, because it wasn't captured during a panic) nothing is yielded.
+pub fn panic_context_frames(
+    backtrace: &Backtrace,
+    radius: usize,
+) -> impl Iterator<Item = ShortFrame<'_>> {
+    panic_context_frames_impl(backtrace, radius).map(ShortFrame::from)
+}
+
+pub(crate) fn panic_context_frames_impl<B: Backtraceish>(
+    backtrace: &B,
+    radius: usize,
+) -> impl Iterator<Item = (&B::Frame, Range<usize>)> {
+    let mut frames: Vec<_> = short_frames_strict_impl(backtrace).collect();
+    let glue = frames.iter().rposition(|(frame, subframes)| {
+        frame.symbols()[subframes.clone()].iter().any(|symbol| {
+            demangled_name(symbol).map_or(false, |name| {
+                PANIC_SYMBOLS.iter().any(|panic| name.contains(panic))
+            })
+        })
+    });
+    match glue {
+        Some(glue) => {
+            let end = glue.saturating_add(radius).saturating_add(1);
+            frames.truncate(end);
+            frames.drain(..glue.saturating_sub(radius));
+        }
+        None => frames.clear(),
+    }
+    frames.into_iter()
+}
@@ -0,0 +1,67 @@
+//! Rendering a short backtrace as JSON, for log shippers.
+
+use crate::*;
+use std::fmt::Write;
+
+/// Gets the short backtrace as JSON Lines (NDJSON): one JSON object per line, so it can be
+/// shipped and processed a frame at a time instead of buffering a whole array.
+///
+/// There's an object for every [`ResolvedFrame`][] (see [`resolved_short_symbols`][]), so
+/// a frame with inlined calls gets a line for each of them, all with the same `index`:
+///
+/// ```text
+/// {"index":0,"ip":"0x55d4159a3b2f","name":"app::parse","file":"src/parse.rs","line":10}
+/// {"index":0,"ip":"0x55d4159a3b2f","name":"app::main","file":"src/main.rs","line":5}
+/// {"index":1,"ip":"0x55d4159a3c40","name":null,"file":null,"line":null}
+/// ```
+///
+/// The `ip` is a hex string, since JSON numbers can't reliably hold a 64-bit address.
+/// The lines don't end with a newline.
+pub fn short_backtrace_jsonl(backtrace: &Backtrace) -> impl Iterator<Item = String> + '_ {
+    short_backtrace_jsonl_impl(backtrace)
+}
+
+pub(crate) fn short_backtrace_jsonl_impl<B: Backtraceish>(
+    backtrace: &B,
+) -> impl Iterator<Item = String> + '_ {
+    resolved::resolved_short_symbols_impl(backtrace).map(|frame| {
+        let mut line = format!(
+            "{{\"index\":{},\"ip\":\"{:#x}\",\"name\":",
+            frame.index, frame.ip
+        );
+        write_json_str(&mut line, frame.name.as_deref());
+        line.push_str(",\"file\":");
+        let file = frame.file.as_ref().map(|file| file.to_string_lossy());
+        write_json_str(&mut line, file.as_deref());
+        match frame.line {
+            Some(lineno) => {
+                let _ = write!(line, ",\"line\":{}}}", lineno);
+            }
+            None => line.push_str(",\"line\":null}"),
+        }
+        line
+    })
+}
+
+/// Writes a JSON string (or `null`), escaping whatever JSON requires
+pub(crate) fn write_json_str(out: &mut String, s: Option<&str>) {
+    let s = match s {
+        Some(s) => s,
+        None => return out.push_str("null"),
+    };
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
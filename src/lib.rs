@@ -1,12 +1,140 @@
 //! Minor conveniences on top of the backtrace crate
 //!
 //! See [`short_frames_strict`][] for details.
+//!
+//! # Which `backtrace`
+//!
+//! Everything here takes and returns the types of the `backtrace` crate directly, so it's a
+//! public dependency: a breaking release of `backtrace` that this crate moves to is a
+//! breaking release of this crate too. To make sure you're using the same version as this
+//! crate (instead of depending on `backtrace` yourself and keeping the versions in sync),
+//! use the re-export at [`backtrace_ext::backtrace`][backtrace].
 use backtrace::*;
+use std::ffi::c_void;
+use std::fmt;
 use std::ops::Range;
+use std::path::Path;
 
+pub use backtrace;
+
+mod adapters;
+#[cfg(feature = "test-util")]
+mod assert;
+mod attributes;
+mod binary;
+mod classify;
+mod collect;
+mod color;
+mod context;
+mod dedup;
+mod deferred;
+mod diagnostics;
+mod env;
+mod format;
+mod gunk;
+mod json;
+mod markdown;
+mod names;
+mod offsets;
+mod parse;
+mod query;
+mod recursion;
+mod report;
+mod resolved;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(feature = "source-snippets")]
+mod snippet;
+mod stats;
+mod summary;
 #[cfg(test)]
 mod test;
 
+pub use adapters::{
+    short_frames_between, short_frames_sorted_by, short_frames_strict_with_markers,
+    short_frames_trim_tail, short_frames_with_boundary, short_frames_with_counts,
+    short_frames_with_names, short_frames_with_position, FramePosition, MarkerBoundary,
+};
+#[cfg(feature = "test-util")]
+#[doc(hidden)]
+pub use assert::__assert_backtrace_contains;
+#[cfg(feature = "test-util")]
+pub use assert::{assert_golden_backtrace, BLESS_VAR};
+pub use attributes::{short_backtrace_attributes, ATTRIBUTE_PREFIX};
+pub use binary::{short_backtrace_from_bytes, short_backtrace_to_bytes};
+pub use classify::{
+    classify_frame, classify_symbol, is_macro_frame, short_backtrace_tree, CrateGroup, FrameKind,
+    STD_CRATES,
+};
+pub use collect::{capture_short_skip, FrameCollector};
+pub use color::ColorMap;
+pub use context::ContextualBacktrace;
+pub use dedup::DedupPrinter;
+pub use deferred::{
+    capture_deferred, capture_short_limited, capture_short_resolved, DeferredBacktrace,
+    ResolvingBacktrace,
+};
+pub use diagnostics::{
+    all_marker_positions, format_annotated_full, gunk_frame_counts, is_truncated, self_check,
+    short_regions, MarkerKind, SelfCheckReport, ENTRY_POINT_SYMBOLS,
+};
+pub use env::{format_short_backtrace, format_short_backtrace_env};
+pub use format::{
+    append_short_backtrace, estimate_formatted_len, write_short_backtrace_bytes, AddressFormat,
+    DefaultShortBacktraceFormat, DefaultSymbolRenderer, FormatStyle, InlinePolicy, LineEnding,
+    MacroFramePolicy, ShortBacktraceFormat, ShortBacktraceFormatter, SliceWriteResult,
+    SubframeOrder, SymbolRenderer,
+};
+pub use gunk::{
+    abort_kind, catch_unwind_frame, is_alloc_failure, panic_context_frames,
+    short_frames_exclude_ip_range, short_frames_only_from, short_frames_until_catch_unwind,
+    short_frames_without_gunk, short_frames_without_symbols, short_frames_without_sync_glue,
+    short_frames_without_test_harness, AbortKind, ABORT_SYMBOLS, ALLOC_ERROR_SYMBOLS,
+    CATCH_UNWIND_SYMBOLS, GUNK_SYMBOLS, NOUNWIND_PANIC_SYMBOLS, PANIC_SYMBOLS, SYNC_GLUE_SYMBOLS,
+    TEST_HARNESS_SYMBOLS,
+};
+#[cfg(unix)]
+pub use gunk::{signal_trampoline_frame, SIGNAL_TRAMPOLINE_SYMBOLS};
+pub use json::short_backtrace_jsonl;
+pub use markdown::format_short_backtrace_markdown;
+pub use offsets::{
+    capture_short_ips, short_frame_offsets, short_frames_with_modules, short_ips, FrameOffset,
+    IpScan, ModuleInfo,
+};
+#[cfg(feature = "std-backtrace")]
+pub use parse::from_std_backtrace;
+pub use parse::{
+    parse_formatted, parse_std_backtrace, ParsedFrame, ParsedShortBacktrace, ParsedSymbol,
+    StreamingParser,
+};
+pub use query::{
+    deepest_user_frame, find_frame_by_name, nth_real_frame, panic_in_user_code, panic_location,
+    panic_location_named, reaches_main, short_frames_in_line_range, thread_entry_frame, top_crate,
+    trace_touches_file, PathMatch, SourceLocation, MAIN_SYMBOLS, THREAD_ENTRY_SYMBOLS,
+};
+pub use recursion::{
+    recursion_cycles, same_call_site, short_frame_groups, RecursionCycle, ShortFrameGroup,
+};
+pub use report::format_panic_report;
+pub use resolved::{resolved_short_symbols, short_symbols, ResolvedFrame};
+#[cfg(feature = "serde")]
+pub use serialize::{SerializableFrame, SerializableShortBacktrace, SerializableSymbol};
+#[cfg(feature = "source-snippets")]
+pub use snippet::source_snippet;
+pub use stats::{
+    common_suffix_len, crate_distribution, distinct_source_files, frames_by_directory,
+    meaningful_frame_count, new_frames_since, short_backtrace_similarity, UNKNOWN_CRATE,
+};
+pub use summary::{
+    distinguishing_frames, short_backtrace_folded, short_backtrace_id, short_backtrace_id_with,
+    short_backtrace_names, short_backtrace_oneline, short_debug, unique_frames, UnknownFramePolicy,
+};
+
+/// The symbol that marks the newest end of the short backtrace.
+pub(crate) const END_MARKER: &str = "rust_end_short_backtrace";
+/// The symbol that marks the oldest end of the short backtrace.
+pub(crate) const BEGIN_MARKER: &str = "rust_begin_short_backtrace";
+
 /// Gets an iterator over the frames that are part of Rust's "short backtrace" range.
 /// If no such range is found, the full stack is yielded.
 ///
@@ -22,11 +150,23 @@ mod test;
 /// (I've seen it in the wild), we will pick the "innermost" ones, producing the smallest
 /// possible backtrace (and excluding all special frames from the output).
 ///
-/// Each element of the iterator includes a Range which you should use to slice
-/// the frame's `symbols()` array. This handles the theoretical situation where "real" frames
+/// The markers are found by name, so the backtrace has to be resolved. An unresolved one
+/// (from [`Backtrace::new_unresolved`][]) has no symbols for any frame, so this yields
+/// the full stack with no names. Capture with [`Backtrace::new`][], call
+/// [`Backtrace::resolve`][] first, or use [`capture_short_resolved`][], which resolves
+/// just the frames needed to find the short backtrace.
+///
+/// The frames are yielded newest first. The iterator knows how many frames there are
+/// ([`ExactSizeIterator`][]) and can be reversed ([`DoubleEndedIterator`][]) to walk them
+/// oldest first, without collecting them.
+///
+/// Each element of the iterator is a [`ShortFrame`][], which includes a Range
+/// (`sub_frames`) that you should use to slice the frame's `symbols()` array (or call
+/// [`ShortFrame::symbols`][], which does that for you). This handles the theoretical situation where "real" frames
 /// got inlined together with the special marker frames. I want to believe this can't happen
 /// but you can never trust backtraces to be reasonable! We will never yield a Frame to you
-/// with an empty Range.
+/// with an empty Range, even when the markers are right next to each other, unless the
+/// frame has no symbols at all (because it couldn't be resolved).
 ///
 /// Note that some "gunk" frames may still be found within the short backtrace, as there is still some
 /// platform-specific and optimization-specific glue around the edges because compilers are
@@ -37,20 +177,22 @@ mod test;
 /// * `core::panicking::panic_fmt`
 /// * `rust_begin_unwind`
 ///
-/// In the future we may introduce a non-strict short_frames which heuristically filters
-/// those frames out too. Until then, the strict approach is safe.
+/// (This list is available as [`GUNK_SYMBOLS`][].)
+///
+/// [`short_frames_without_gunk`][] filters those out too, but this strict approach is
+/// the safe one.
 ///
 /// # Example
 ///
 /// Here's an example simple "short backtrace" implementation.
 /// Note the use of `sub_frames` for the inner loop to restrict `symbols`!
+/// (If this is all you want, [`format_short_backtrace`][] does exactly this in one call,
+/// and [`format_short_backtrace_env`][] also handles `RUST_BACKTRACE=full`. For more
+/// control over the output, use [`ShortBacktraceFormatter`][].)
 ///
 /// This example is based off of code found in `miette` (Apache-2.0), which itself
 /// copied the logic from `human-panic` (MIT/Apache-2.0).
 ///
-/// FIXME: it would be nice if this example consulted `RUST_BACKTRACE=full`,
-/// and maybe other vars used by rust's builtin panic handler..?
-///
 /// ```
 /// fn backtrace() -> String {
 ///     use std::fmt::Write;
@@ -62,7 +204,7 @@ mod test;
 ///             let mut backtrace = String::new();
 ///             let trace = backtrace::Backtrace::new();
 ///             let frames = backtrace_ext::short_frames_strict(&trace).enumerate();
-///             for (idx, (frame, subframes)) in frames {
+///             for (idx, backtrace_ext::ShortFrame { frame, sub_frames }) in frames {
 ///                 let ip = frame.ip();
 ///                 let _ = write!(backtrace, "\n{:4}: {:2$?}", idx, ip, HEX_WIDTH);
 ///     
@@ -72,7 +214,7 @@ mod test;
 ///                     continue;
 ///                 }
 ///     
-///                 for (idx, symbol) in symbols[subframes].iter().enumerate() {
+///                 for (idx, symbol) in symbols[sub_frames].iter().enumerate() {
 ///                     // Print symbols from this address,
 ///                     // if there are several addresses
 ///                     // we need to put it on next line
@@ -107,17 +249,232 @@ mod test;
 /// ```
 pub fn short_frames_strict(
     backtrace: &Backtrace,
-) -> impl Iterator<Item = (&BacktraceFrame, Range<usize>)> {
-    short_frames_strict_impl(backtrace)
+) -> impl DoubleEndedIterator<Item = ShortFrame<'_>> + ExactSizeIterator {
+    adapters::short_frames_strict_with_markers(backtrace, END_MARKER, BEGIN_MARKER)
+}
+
+/// [`short_frames_strict`][] for frames that aren't in a [`Backtrace`][], like ones you
+/// collected yourself with [`backtrace::trace`][] (see also [`FrameCollector`][]),
+/// deserialized, or want to clamp a sub-slice of.
+///
+/// This does exactly the same thing to the slice as [`short_frames_strict`][] does to
+/// `backtrace.frames()`.
+pub fn short_frames_strict_slice(
+    frames: &[BacktraceFrame],
+) -> impl DoubleEndedIterator<Item = ShortFrame<'_>> + ExactSizeIterator {
+    clamped_frames(short_slice_of(frames, MarkerBoundary::AdjacentFrame))
+        .map(|(_, frame, sub_frames)| ShortFrame { frame, sub_frames })
+}
+
+/// A frame yielded by [`short_frames_strict`][] (or any of its variants, like
+/// [`short_frames_without_gunk`][]), along with the range of its symbols that are in the
+/// short backtrace.
+///
+/// Code that used to destructure the `(frame, sub_frames)` tuples these functions yielded
+/// can convert back with `.map(Into::into)`:
+///
+/// ```
+/// use backtrace::BacktraceFrame;
+/// use std::ops::Range;
+///
+/// let backtrace = backtrace::Backtrace::new();
+/// for frame in backtrace_ext::short_frames_strict(&backtrace) {
+///     for symbol in frame.symbols() {
+///         println!("{:?}: {:?}", frame.ip(), symbol.name());
+///     }
+/// }
+/// let tuples: Vec<(&BacktraceFrame, Range<usize>)> =
+///     backtrace_ext::short_frames_strict(&backtrace).map(Into::into).collect();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ShortFrame<'a> {
+    /// The frame.
+    pub frame: &'a BacktraceFrame,
+    /// The range of the frame's symbols that are in the short backtrace.
+    pub sub_frames: Range<usize>,
+}
+
+impl<'a> ShortFrame<'a> {
+    /// The symbols of the frame that are in the short backtrace.
+    pub fn symbols(&self) -> &'a [BacktraceSymbol] {
+        &self.frame.symbols()[self.sub_frames.clone()]
+    }
+
+    /// The frame's instruction pointer.
+    pub fn ip(&self) -> *mut c_void {
+        self.frame.ip()
+    }
+}
+
+impl<'a> From<(&'a BacktraceFrame, Range<usize>)> for ShortFrame<'a> {
+    fn from((frame, sub_frames): (&'a BacktraceFrame, Range<usize>)) -> Self {
+        ShortFrame { frame, sub_frames }
+    }
+}
+
+impl<'a> From<ShortFrame<'a>> for (&'a BacktraceFrame, Range<usize>) {
+    fn from(frame: ShortFrame<'a>) -> Self {
+        (frame.frame, frame.sub_frames)
+    }
 }
 
 pub(crate) fn short_frames_strict_impl<B: Backtraceish>(
     backtrace: &B,
-) -> impl Iterator<Item = (&B::Frame, Range<usize>)> {
+) -> impl DoubleEndedIterator<Item = (&B::Frame, Range<usize>)> + ExactSizeIterator {
+    short_frames_indexed_impl(backtrace).map(|(_, frame, range)| (frame, range))
+}
+
+/// The same as [`short_frames_strict_impl`][] but also yields the index of each
+/// frame in `backtrace.frames()`.
+pub(crate) fn short_frames_indexed_impl<B: Backtraceish>(
+    backtrace: &B,
+) -> impl DoubleEndedIterator<Item = (usize, &B::Frame, Range<usize>)> + ExactSizeIterator {
+    clamped_frames(short_slice_impl(backtrace))
+}
+
+/// Iterates over the result of [`short_slice_impl`][] (or [`clamp_slice`][]), yielding the
+/// index of each frame in the whole backtrace along with the frame and its subframe range.
+pub(crate) fn clamped_frames<F: Frameish>(
+    (final_frames, frame_range, first_subframe, last_subframe_excl): ClampedSlice<'_, F>,
+) -> impl DoubleEndedIterator<Item = (usize, &F, Range<usize>)> + ExactSizeIterator {
+    // Get the index of the last frame when starting from the first frame
+    let adjusted_last_frame = final_frames.len().saturating_sub(1);
+
+    // finally do the iteration
+    final_frames.iter().enumerate().map(move |(idx, frame)| {
+        // Default to all subframes being yielded
+        let mut sub_start = 0;
+        let mut sub_end_excl = frame.symbols().len();
+        // If we're on first frame, apply its subframe clamp
+        if idx == 0 {
+            sub_start = first_subframe;
+        }
+        // If we're on the last frame, apply its subframe clamp
+        if idx == adjusted_last_frame {
+            sub_end_excl = last_subframe_excl;
+        }
+        (frame_range.start + idx, frame, sub_start..sub_end_excl)
+    })
+}
+
+/// Gets the raw results of the clamping that [`short_frames_strict`][] does, so you
+/// can iterate over them however you like.
+///
+/// Returns `(frames, frame_range, first_subframe, last_subframe_excl)`, where:
+///
+/// * `frames` is the slice of frames in the short backtrace (`&backtrace.frames()[frame_range]`)
+/// * `frame_range` is where that slice is in `backtrace.frames()`
+/// * `first_subframe` is the first symbol of `frames[0]` that's in the short backtrace
+/// * `last_subframe_excl` is the (exclusive) end of the symbols of the last frame that are
+///   in the short backtrace
+///
+/// Every other frame is entirely in the short backtrace. If there's only one frame, both
+/// bounds apply to it. If the short backtrace is empty, `frames` and `frame_range` are
+/// empty and the subframe bounds are meaningless.
+pub fn short_slice(backtrace: &Backtrace) -> (&[BacktraceFrame], Range<usize>, usize, usize) {
+    short_slice_impl(backtrace)
+}
+
+/// Gets the frames of the short backtrace as a slice of `backtrace.frames()`, for batch
+/// processing with your own loop.
+///
+/// The first and last frames may only be partially in the short backtrace; use
+/// [`short_subframe_bounds`][] to find which of their symbols are (or [`short_slice`][]
+/// to get everything at once).
+pub fn short_frame_slice(backtrace: &Backtrace) -> &[BacktraceFrame] {
+    short_slice_impl(backtrace).0
+}
+
+/// Gets `(first_subframe, last_subframe_excl)`: the first symbol of the first frame of
+/// [`short_frame_slice`][] that's in the short backtrace, and the (exclusive) end of the
+/// symbols of the last frame that are.
+///
+/// See [`short_slice`][] for the details.
+pub fn short_subframe_bounds(backtrace: &Backtrace) -> (usize, usize) {
+    let (_, _, first_subframe, last_subframe_excl) = short_slice_impl(backtrace);
+    (first_subframe, last_subframe_excl)
+}
+
+/// Where the short backtrace is in the whole backtrace, from [`short_range_with_context`][].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ShortRangeContext {
+    /// The indices of the frames of the short backtrace in `backtrace.frames()`.
+    pub frames: Range<usize>,
+    /// How many frames are before (newer than) the short backtrace, like the panic runtime.
+    pub hidden_before: usize,
+    /// How many frames are after (older than) the short backtrace, like the code that
+    /// started the thread.
+    pub hidden_after: usize,
+}
+
+/// Gets where the short backtrace is in the whole backtrace, and how many frames were
+/// hidden on either side of it, for a viewer with "show 12 more frames" buttons.
+///
+/// Frames that are only partially in the short backtrace (see [`short_slice`][]) count as
+/// part of it. If the short backtrace is empty, `frames` is empty and every frame is
+/// counted on one side or the other.
+pub fn short_range_with_context(backtrace: &Backtrace) -> ShortRangeContext {
+    short_range_with_context_impl(backtrace)
+}
+
+pub(crate) fn short_range_with_context_impl<B: Backtraceish>(backtrace: &B) -> ShortRangeContext {
+    let (_, frames, _, _) = short_slice_impl(backtrace);
+    ShortRangeContext {
+        hidden_before: frames.start,
+        hidden_after: backtrace.frames().len() - frames.end,
+        frames,
+    }
+}
+
+/// The frames left by clamping, see [`short_slice`][].
+pub(crate) type ClampedSlice<'a, F> = (&'a [F], Range<usize>, usize, usize);
+
+/// Where a marker is, as `(frame_idx, subframe_idx)`, see [`find_markers`][].
+pub(crate) type MarkerPosition = (usize, usize);
+
+pub(crate) fn short_slice_impl<B: Backtraceish>(backtrace: &B) -> ClampedSlice<'_, B::Frame> {
+    short_slice_with_impl(backtrace, MarkerBoundary::AdjacentFrame)
+}
+
+/// [`short_slice_impl`][] with a choice of how to clamp at the markers.
+pub(crate) fn short_slice_with_impl<B: Backtraceish>(
+    backtrace: &B,
+    boundary: MarkerBoundary,
+) -> ClampedSlice<'_, B::Frame> {
+    short_slice_of(backtrace.frames(), boundary)
+}
+
+/// [`short_slice_with_impl`][] for a slice of frames.
+pub(crate) fn short_slice_of<F: Frameish>(
+    frames: &[F],
+    boundary: MarkerBoundary,
+) -> ClampedSlice<'_, F> {
+    let (short_start, short_end) = find_markers(frames);
+    clamp_slice_with(frames, short_start, short_end, boundary)
+}
+
+/// Finds the `(frame_idx, subframe_idx)` positions of the `rust_end_short_backtrace` and
+/// `rust_begin_short_backtrace` markers that bound the short backtrace, for
+/// [`clamp_slice`][] to clamp to. This is just the search: it doesn't look at anything but
+/// the names of the symbols, so it can be tested with any frames.
+///
+/// If the markers are in the wrong order, neither of them is returned.
+pub(crate) fn find_markers<F: Frameish>(
+    frames: &[F],
+) -> (Option<MarkerPosition>, Option<MarkerPosition>) {
+    find_markers_named(frames, END_MARKER, BEGIN_MARKER)
+}
+
+/// [`find_markers`][] with other names in place of `rust_end_short_backtrace` and
+/// `rust_begin_short_backtrace`, in the same (newest to oldest) order.
+pub(crate) fn find_markers_named<F: Frameish>(
+    frames: &[F],
+    start_marker: &str,
+    end_marker: &str,
+) -> (Option<MarkerPosition>, Option<MarkerPosition>) {
     // Search for the special frames
     let mut short_start = None;
     let mut short_end = None;
-    let frames = backtrace.frames();
     for (frame_idx, frame) in frames.iter().enumerate() {
         let symbols = frame.symbols();
         for (subframe_idx, frame) in symbols.iter().enumerate() {
@@ -131,10 +488,10 @@ pub(crate) fn short_frames_strict_impl<B: Backtraceish>(
                 // that contain these names in sequence. If that happens we just want to pick the two
                 // that are closest together. For the start that means just using the last one we found,
                 // and for the end that means taking the first one we find.
-                if name.contains("rust_end_short_backtrace") {
+                if name.contains(start_marker) {
                     short_start = Some((frame_idx, subframe_idx));
                 }
-                if name.contains("rust_begin_short_backtrace") && short_end.is_none() {
+                if name.contains(end_marker) && short_end.is_none() {
                     short_end = Some((frame_idx, subframe_idx));
                 }
             }
@@ -151,14 +508,24 @@ pub(crate) fn short_frames_strict_impl<B: Backtraceish>(
         }
     }
 
+    (short_start, short_end)
+}
+
+/// Clamps the frames to everything after `short_start` and before `short_end`, which are
+/// `(frame_idx, subframe_idx)` positions of the (excluded) symbols to clamp to. A missing
+/// bound doesn't clamp that side at all, and `short_start` must come before `short_end`.
+pub(crate) fn clamp_slice<F: Frameish>(
+    frames: &[F],
+    short_start: Option<(usize, usize)>,
+    short_end: Option<(usize, usize)>,
+) -> ClampedSlice<'_, F> {
     // By default we want to produce a full stack trace and now we'll try to clamp it.
     let mut first_frame = 0usize;
     let mut first_subframe = 0usize;
     // NOTE: this is INCLUSIVE
     let mut last_frame = frames.len().saturating_sub(1);
     // NOTE: this is EXCLUSIVE
-    let mut last_subframe_excl = backtrace
-        .frames()
+    let mut last_subframe_excl = frames
         .last()
         .map(|frame| frame.symbols().len())
         .unwrap_or(0);
@@ -210,34 +577,41 @@ pub(crate) fn short_frames_strict_impl<B: Backtraceish>(
     // throw everything out and yield an empty range. We don't need to fix any
     // other values at this point as they won't be used for anything with an
     // empty iterator
-    let final_frames = {
+    // (A frame without any symbols is always "lined up", but it's still a frame to yield.)
+    let frame_range = {
         let start = (first_frame, first_subframe);
         let end = (last_frame, last_subframe_excl);
-        if start == end {
-            &frames[0..0]
+        let unresolved = frames
+            .get(first_frame)
+            .map_or(false, |frame| frame.symbols().is_empty());
+        if (start == end && !unresolved) || first_frame > last_frame {
+            first_frame..first_frame
         } else {
-            &frames[first_frame..=last_frame]
+            first_frame..last_frame + 1
         }
     };
+    (
+        &frames[frame_range.clone()],
+        frame_range,
+        first_subframe,
+        last_subframe_excl,
+    )
+}
 
-    // Get the index of the last frame when starting from the first frame
-    let adjusted_last_frame = last_frame.saturating_sub(first_frame);
-
-    // finally do the iteration
-    final_frames.iter().enumerate().map(move |(idx, frame)| {
-        // Default to all subframes being yielded
-        let mut sub_start = 0;
-        let mut sub_end_excl = frame.symbols().len();
-        // If we're on first frame, apply its subframe clamp
-        if idx == 0 {
-            sub_start = first_subframe;
-        }
-        // If we're on the last frame, apply its subframe clamp
-        if idx == adjusted_last_frame {
-            sub_end_excl = last_subframe_excl;
-        }
-        (frame, sub_start..sub_end_excl)
-    })
+/// [`clamp_slice`][] with a choice of how to clamp when a bound is at the edge of its frame.
+pub(crate) fn clamp_slice_with<F: Frameish>(
+    frames: &[F],
+    short_start: Option<(usize, usize)>,
+    short_end: Option<(usize, usize)>,
+    boundary: MarkerBoundary,
+) -> ClampedSlice<'_, F> {
+    match boundary {
+        MarkerBoundary::AdjacentFrame => clamp_slice(frames, short_start, short_end),
+        // Clamping at a bound that's the first or last symbol of its frame leaves that frame
+        // with none of its symbols, and skipping it is exactly the "use the whole
+        // next/previous frame" case of `clamp_slice`
+        MarkerBoundary::Subframe => clamp_slice(frames, short_start, short_end),
+    }
 }
 
 pub(crate) trait Backtraceish {
@@ -248,10 +622,47 @@ pub(crate) trait Backtraceish {
 pub(crate) trait Frameish {
     type Symbol: Symbolish;
     fn symbols(&self) -> &[Self::Symbol];
+    fn ip(&self) -> *mut c_void;
+    fn symbol_address(&self) -> *mut c_void;
+    fn module_base_address(&self) -> Option<*mut c_void>;
 }
 
 pub(crate) trait Symbolish {
     fn name_str(&self) -> Option<&str>;
+    /// The raw (probably mangled) name, as the platform reported it
+    fn name_bytes(&self) -> Option<&[u8]>;
+    fn has_name(&self) -> bool;
+    /// Writes the demangled name, only called if `has_name` is true
+    fn fmt_name(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+    /// Whether the name demangled as a Rust symbol, only called if `has_name` is true
+    fn is_rust(&self) -> bool;
+    fn filename(&self) -> Option<&Path>;
+    fn lineno(&self) -> Option<u32>;
+    fn colno(&self) -> Option<u32>;
+}
+
+/// Displays a Symbolish's name the way `SymbolName` would
+/// (so `{:#}` will strip the hash off of a demangled rust symbol).
+pub(crate) struct SymbolNameDisplay<'a, S>(pub(crate) &'a S);
+
+impl<'a, S: Symbolish> fmt::Display for SymbolNameDisplay<'a, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt_name(f)
+    }
+}
+
+/// Gets the demangled name of the symbol (without the hash), if it has one.
+pub(crate) fn demangled_name<S: Symbolish>(symbol: &S) -> Option<String> {
+    if symbol.has_name() {
+        Some(format!("{:#}", SymbolNameDisplay(symbol)))
+    } else {
+        None
+    }
+}
+
+/// Whether debuginfo told us anything about the symbol (its name or its source file).
+pub(crate) fn is_resolved<S: Symbolish>(symbol: &S) -> bool {
+    symbol.has_name() || symbol.filename().is_some()
 }
 
 impl Backtraceish for Backtrace {
@@ -266,6 +677,15 @@ impl Frameish for BacktraceFrame {
     fn symbols(&self) -> &[Self::Symbol] {
         self.symbols()
     }
+    fn ip(&self) -> *mut c_void {
+        self.ip()
+    }
+    fn symbol_address(&self) -> *mut c_void {
+        self.symbol_address()
+    }
+    fn module_base_address(&self) -> Option<*mut c_void> {
+        self.module_base_address()
+    }
 }
 
 impl Symbolish for BacktraceSymbol {
@@ -274,4 +694,32 @@ impl Symbolish for BacktraceSymbol {
     fn name_str(&self) -> Option<&str> {
         self.name().and_then(|n| n.as_str())
     }
+    fn name_bytes(&self) -> Option<&[u8]> {
+        self.name().map(|n| n.as_bytes())
+    }
+    fn has_name(&self) -> bool {
+        self.name().is_some()
+    }
+    fn fmt_name(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => fmt::Display::fmt(&name, f),
+            None => Ok(()),
+        }
+    }
+    fn is_rust(&self) -> bool {
+        // SymbolName displays the raw name if it couldn't demangle it
+        match self.name() {
+            Some(name) => name.as_str().map_or(false, |raw| name.to_string() != raw),
+            None => false,
+        }
+    }
+    fn filename(&self) -> Option<&Path> {
+        self.filename()
+    }
+    fn lineno(&self) -> Option<u32> {
+        self.lineno()
+    }
+    fn colno(&self) -> Option<u32> {
+        self.colno()
+    }
 }
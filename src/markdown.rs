@@ -0,0 +1,49 @@
+//! Rendering short backtraces for pasting into Markdown (like GitHub issues).
+
+use crate::*;
+
+/// Formats the short backtrace as a fenced Markdown code block, ready to paste into
+/// a bug report.
+///
+/// The frames are printed by a default [`ShortBacktraceFormatter`][]. The fence is made
+/// longer than any run of backticks in the backtrace, so nothing in it can close the
+/// block early. If `collapsible` is set, the block is wrapped in a
+/// `<details><summary>Backtrace</summary>` section that GitHub shows folded:
+///
+/// ````text
+/// <details><summary>Backtrace</summary>
+///
+/// ```text
+///    0: 0x55d4159a3b2f - app::parse
+///                 at src/parse.rs:10
+/// ```
+///
+/// </details>
+/// ````
+pub fn format_short_backtrace_markdown(backtrace: &Backtrace, collapsible: bool) -> String {
+    format_short_backtrace_markdown_impl(backtrace, collapsible)
+}
+
+pub(crate) fn format_short_backtrace_markdown_impl<B: Backtraceish>(
+    backtrace: &B,
+    collapsible: bool,
+) -> String {
+    let formatted = ShortBacktraceFormatter::new().format_impl(backtrace);
+    // The formatter starts every frame with a newline, including the first
+    let formatted = formatted.strip_prefix('\n').unwrap_or(&formatted);
+    let fence = "`".repeat(longest_backtick_run(formatted).max(2) + 1);
+
+    let mut out = String::new();
+    if collapsible {
+        out.push_str("<details><summary>Backtrace</summary>\n\n");
+    }
+    out.push_str(&format!("{}text\n{}\n{}\n", fence, formatted, fence));
+    if collapsible {
+        out.push_str("\n</details>\n");
+    }
+    out
+}
+
+fn longest_backtick_run(text: &str) -> usize {
+    text.split(|c| c != '`').map(str::len).max().unwrap_or(0)
+}
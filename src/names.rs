@@ -0,0 +1,226 @@
+//! Utilities for picking apart demangled symbol names.
+
+/// Splits a demangled path on `::`, ignoring any `::` nested inside of `<...>`.
+pub(crate) fn path_segments(name: &str) -> Vec<&str> {
+    let mut segments = vec![];
+    let mut depth = 0usize;
+    let mut seg_start = 0;
+    let bytes = name.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'<' => depth += 1,
+            b'>' => depth = depth.saturating_sub(1),
+            b':' if depth == 0 && bytes.get(i + 1) == Some(&b':') => {
+                segments.push(&name[seg_start..i]);
+                i += 2;
+                seg_start = i;
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    segments.push(&name[seg_start..]);
+    segments
+}
+
+/// Gets the function's own name out of a full path, so `app::parse::<T>` becomes `parse`.
+///
+/// Closures keep the name of the function they're in, so `app::parse::{{closure}}`
+/// becomes `parse::{{closure}}`.
+pub(crate) fn leaf_name(name: &str) -> String {
+    let mut segments = path_segments(name);
+    // Drop turbofish generics
+    while segments.len() > 1 && segments.last().map_or(false, |s| s.starts_with('<')) {
+        segments.pop();
+    }
+    let last = segments.len() - 1;
+    if last > 0 && segments[last].starts_with("{{") {
+        format!("{}::{}", segments[last - 1], segments[last])
+    } else {
+        segments[last].to_owned()
+    }
+}
+
+/// Replaces every list of generic arguments with `_`, so `app::parse::<u32>` and
+/// `app::parse::<alloc::string::String>` both become `app::parse::<_>`.
+///
+/// This handles nested generics (the whole list is replaced), and leaves the `<...>` of
+/// qualified paths like `<app::Foo<T> as core::Bar>::baz` alone, although generics inside
+/// of them are still folded (giving `<app::Foo<_> as core::Bar>::baz`).
+pub(crate) fn fold_generics(name: &str) -> String {
+    let bytes = name.as_bytes();
+    let mut out = String::with_capacity(name.len());
+    let mut copied = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'<' && is_generic_args(name, i) {
+            out.push_str(&name[copied..i]);
+            out.push_str("<_>");
+            i = matching_close(bytes, i) + 1;
+            copied = i;
+            continue;
+        }
+        i += 1;
+    }
+    out.push_str(&name[copied..]);
+    out
+}
+
+/// Whether the `<` at `idx` starts a list of generic arguments (as opposed to a
+/// qualified path like `<T as Trait>` or `<impl Foo>`).
+fn is_generic_args(name: &str, idx: usize) -> bool {
+    let before = &name[..idx];
+    let after = &name[idx + 1..];
+    match before.bytes().last() {
+        // `Vec<T>`
+        Some(c) if c.is_ascii_alphanumeric() || c == b'_' => true,
+        // `parse::<T>`
+        Some(b':') => before.ends_with("::") && !after.starts_with("impl "),
+        _ => false,
+    }
+}
+
+/// Finds the `>` that closes the `<` at `open` (or the end of the string, if it's never
+/// closed). The `>` of a `->` doesn't count.
+fn matching_close(bytes: &[u8], open: usize) -> usize {
+    let mut depth = 0usize;
+    for (idx, &c) in bytes.iter().enumerate().skip(open) {
+        match c {
+            b'<' => depth += 1,
+            b'>' if idx > 0 && bytes[idx - 1] == b'-' => {}
+            b'>' => {
+                depth -= 1;
+                if depth == 0 {
+                    return idx;
+                }
+            }
+            _ => {}
+        }
+    }
+    bytes.len() - 1
+}
+
+/// Replaces every closure in the path with a single `{closure}` at the end, so
+/// `app::main::{{closure}}::{{closure}}` becomes `app::main::{closure}`.
+///
+/// This handles both the legacy (`{{closure}}`) and v0 (`{closure#1}`) manglings. Names
+/// without closures are left alone.
+pub(crate) fn simplify_closures(name: &str) -> String {
+    let segments = path_segments(name);
+    let is_closure =
+        |segment: &&str| segment.starts_with("{{closure}}") || segment.starts_with("{closure");
+    if !segments.iter().any(is_closure) {
+        return name.to_owned();
+    }
+    let mut simplified: Vec<&str> = segments.into_iter().filter(|s| !is_closure(s)).collect();
+    simplified.push("{closure}");
+    simplified.join("::")
+}
+
+/// Truncates the name to at most `max` chars, ending it with `…` if anything was cut
+/// (so a `max` of 0 gives an empty string).
+pub(crate) fn truncate_name(name: &str, max: usize) -> String {
+    if name.chars().count() <= max {
+        return name.to_owned();
+    }
+    if max == 0 {
+        return String::new();
+    }
+    let mut out: String = name.chars().take(max.saturating_sub(1)).collect();
+    out.push('…');
+    out
+}
+
+/// Truncates the name to at most `max` chars by replacing its middle with `…`, keeping a
+/// bit more of the end (where the function's own name is) than of the start (so a `max`
+/// of 0 gives an empty string).
+pub(crate) fn middle_truncate_name(name: &str, max: usize) -> String {
+    let len = name.chars().count();
+    if len <= max {
+        return name.to_owned();
+    }
+    if max == 0 {
+        return String::new();
+    }
+    let kept = max.saturating_sub(1);
+    let tail = (kept + 1) / 2;
+    let head = kept - tail;
+    let mut out: String = name.chars().take(head).collect();
+    out.push('…');
+    out.extend(name.chars().skip(len - tail));
+    out
+}
+
+/// Strips the leading `<`, references, pointers, and `dyn` off a qualified path
+/// like `<&mut app::Foo as core::Bar>::baz`, so it starts with the type's own path.
+fn strip_qualified_self(mut name: &str) -> &str {
+    loop {
+        let trimmed = name
+            .trim_start_matches('<')
+            .trim_start_matches('&')
+            .trim_start_matches('*')
+            .trim_start();
+        let trimmed = ["mut ", "const ", "dyn "]
+            .iter()
+            .fold(trimmed, |name, prefix| {
+                name.strip_prefix(prefix).unwrap_or(name)
+            });
+        if trimmed.len() == name.len() {
+            return name;
+        }
+        name = trimmed;
+    }
+}
+
+/// Gets the name of the crate a demangled symbol belongs to, like `app` for
+/// `app::parse::{{closure}}`.
+///
+/// For trait methods (`<app::Foo as core::fmt::Debug>::fmt`) this is the crate of the
+/// type. Returns `None` for names that don't look like a Rust path (including the
+/// self type being something like a tuple or slice).
+pub(crate) fn crate_name(name: &str) -> Option<&str> {
+    let path = strip_qualified_self(name);
+    let end = path
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(path.len());
+    let krate = &path[..end];
+    let rest = &path[end..];
+    let is_path = rest.is_empty()
+        || rest.starts_with("::")
+        || rest.starts_with('<')
+        || rest.starts_with(' ')
+        || rest.starts_with('>');
+    let is_ident = krate
+        .chars()
+        .next()
+        .map_or(false, |c| c.is_alphabetic() || c == '_');
+    if is_ident && is_path {
+        Some(krate)
+    } else {
+        None
+    }
+}
+
+/// Whether a demangled symbol is inside the given path prefix (like `app` or `app::parse`).
+///
+/// The prefix has to match whole path segments, so `app` doesn't match `apple::foo`.
+/// As with [`crate_name`][], a trait method is considered part of its type's path.
+pub(crate) fn matches_path_prefix(name: &str, prefix: &str) -> bool {
+    let prefix = prefix.trim_end_matches("::");
+    if prefix.is_empty() {
+        return false;
+    }
+    let path = strip_qualified_self(name);
+    match path.strip_prefix(prefix) {
+        Some(rest) => {
+            rest.is_empty()
+                || rest.starts_with("::")
+                || rest.starts_with('<')
+                || rest.starts_with(' ')
+                || rest.starts_with('>')
+        }
+        None => false,
+    }
+}
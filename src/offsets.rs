@@ -0,0 +1,250 @@
+//! Addresses of short frames relative to their function and module.
+
+use crate::*;
+use std::path::PathBuf;
+
+/// Where a frame's instruction pointer is, relative to the start of its function and
+/// the module (executable or shared library) it was loaded from.
+///
+/// Unlike the raw instruction pointer, these offsets don't depend on where the module
+/// happened to be loaded, so a crash reporter can record them (as `function+0x1c`, or
+/// `module+0x4a1c`) and symbolicate them later against the unstripped binary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FrameOffset {
+    /// The index of the frame in the short backtrace.
+    pub index: usize,
+    /// The instruction pointer of the frame.
+    pub ip: usize,
+    /// The starting address of the frame's function.
+    pub symbol_address: usize,
+    /// `ip - symbol_address`, or `None` if the function's address isn't known.
+    pub offset: Option<usize>,
+    /// The base address of the frame's module, if the platform reports it.
+    pub module_base: Option<usize>,
+    /// `ip - module_base`, or `None` if the module's base address isn't known.
+    pub module_offset: Option<usize>,
+}
+
+/// Gets the [`FrameOffset`][] of every frame in the short backtrace.
+///
+/// Inlined calls share their frame's addresses, so this yields one item per frame
+/// (not per symbol).
+pub fn short_frame_offsets(backtrace: &Backtrace) -> impl Iterator<Item = FrameOffset> + '_ {
+    short_frame_offsets_impl(backtrace)
+}
+
+pub(crate) fn short_frame_offsets_impl<B: Backtraceish>(
+    backtrace: &B,
+) -> impl Iterator<Item = FrameOffset> + '_ {
+    short_frames_strict_impl(backtrace)
+        .enumerate()
+        .map(|(index, (frame, _))| {
+            let ip = frame.ip() as usize;
+            let symbol_address = frame.symbol_address() as usize;
+            let module_base = frame.module_base_address().map(|base| base as usize);
+            FrameOffset {
+                index,
+                ip,
+                symbol_address,
+                offset: offset_from(ip, Some(symbol_address)),
+                module_base,
+                module_offset: offset_from(ip, module_base),
+            }
+        })
+}
+
+/// Gets the instruction pointer of every frame in the short backtrace.
+///
+/// This is the lightest view of the short backtrace, for things like a symbol cache that
+/// only want addresses. Note that finding the short backtrace still needs the symbols
+/// of the frames (to find the markers), so on an unresolved backtrace this yields every
+/// frame. To capture just the addresses, see [`capture_short_ips`][].
+pub fn short_ips(backtrace: &Backtrace) -> impl Iterator<Item = usize> + '_ {
+    short_ips_impl(backtrace)
+}
+
+pub(crate) fn short_ips_impl<B: Backtraceish>(backtrace: &B) -> impl Iterator<Item = usize> + '_ {
+    short_frames_strict_impl(backtrace).map(|(frame, _)| frame.ip() as usize)
+}
+
+/// How [`capture_short_ips`][] finds the frames to yield.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpScan {
+    /// Find the short backtrace by its markers, the same way [`short_frames_strict`][]
+    /// does (the default). Markers can only be found by name, so this resolves symbols
+    /// like [`capture_short_resolved`][] does.
+    Markers,
+    /// Yield every frame of the stack without looking for markers, for when you know there
+    /// aren't any (like on a thread that isn't panicking) or want the whole stack anyway.
+    /// This never resolves a symbol, and allocates nothing but the list of addresses.
+    FullStack,
+}
+
+impl Default for IpScan {
+    fn default() -> Self {
+        IpScan::Markers
+    }
+}
+
+/// Captures a backtrace and gets just the instruction pointers of its frames, newest
+/// first, without the frame of `capture_short_ips` itself.
+///
+/// This is the fast path of [`short_ips`][] for callers that only want addresses (like a
+/// sampling profiler or a symbol cache that resolves them later). Only
+/// [`IpScan::FullStack`][] is free of symbol resolution: finding the markers needs the
+/// names of every frame up to the `rust_begin_short_backtrace` marker, so
+/// [`IpScan::Markers`][] costs about as much as [`capture_short_resolved`][].
+#[inline(never)]
+pub fn capture_short_ips(scan: IpScan) -> impl Iterator<Item = usize> {
+    let own_address = capture_short_ips as *const () as usize;
+    let ips: Vec<usize> = match scan {
+        IpScan::Markers => {
+            let backtrace = capture_short_resolved();
+            let frames = backtrace.frames();
+            let start = frames
+                .iter()
+                .position(|frame| frame.symbol_address() as usize == own_address)
+                .map_or(0, |idx| idx + 1);
+            let backtrace = Backtrace::from(frames[start..].to_vec());
+            short_ips_impl(&backtrace).collect()
+        }
+        IpScan::FullStack => {
+            let mut ips = Vec::new();
+            let mut start = 0;
+            backtrace::trace(|frame| {
+                if frame.symbol_address() as usize == own_address {
+                    start = ips.len() + 1;
+                }
+                ips.push(frame.ip() as usize);
+                true
+            });
+            ips.drain(..start);
+            ips
+        }
+    };
+    ips.into_iter()
+}
+
+/// `ip - base`, if `base` is a real address below `ip`.
+fn offset_from(ip: usize, base: Option<usize>) -> Option<usize> {
+    base.filter(|&base| base != 0)
+        .and_then(|base| ip.checked_sub(base))
+}
+
+/// The module (executable or shared library) a frame's code was loaded from, as found by
+/// [`short_frames_with_modules`][].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ModuleInfo {
+    /// The path of the module's file.
+    pub name: PathBuf,
+    /// The address the module was loaded at, which is what its addresses are relative to.
+    pub base: usize,
+}
+
+/// Like [`short_frames_strict`][], but also yields the module each frame's code is in, for
+/// symbolicating the backtrace later (offline, or on another machine).
+///
+/// With the module's path and base address, `ip - base` can be looked up in the debuginfo
+/// of that file (see also [`short_frame_offsets`][]).
+///
+/// # Platform support
+///
+/// The backtrace crate only reports the base address of modules (not their path), and only
+/// on some platforms, so this looks the modules up in `/proc/self/maps` instead. That means
+/// this only works on Linux and Android, and yields `None` for every frame elsewhere
+/// (including Windows and macOS). It also yields `None` for code that isn't backed by a
+/// file, like the vDSO or JIT-compiled code, and if `/proc` isn't mounted.
+///
+/// The modules are read when this is called, so call it while the modules are still loaded.
+pub fn short_frames_with_modules(
+    backtrace: &Backtrace,
+) -> impl Iterator<Item = (&BacktraceFrame, Range<usize>, Option<ModuleInfo>)> {
+    short_frames_with_modules_impl(backtrace, &loaded_modules())
+}
+
+pub(crate) fn short_frames_with_modules_impl<'a, B: Backtraceish>(
+    backtrace: &'a B,
+    modules: &[MappedModule],
+) -> impl Iterator<Item = (&'a B::Frame, Range<usize>, Option<ModuleInfo>)> {
+    let infos: Vec<Option<ModuleInfo>> = short_frames_strict_impl(backtrace)
+        .map(|(frame, _)| {
+            let ip = frame.ip() as usize;
+            let module = modules.iter().find(|module| module.range.contains(&ip))?;
+            Some(ModuleInfo {
+                name: module.name.clone(),
+                // Trust the platform over our guess, if it told us
+                base: frame
+                    .module_base_address()
+                    .map_or(module.base, |base| base as usize),
+            })
+        })
+        .collect();
+    short_frames_strict_impl(backtrace)
+        .zip(infos)
+        .map(|((frame, subframes), info)| (frame, subframes, info))
+}
+
+/// A range of addresses mapped from a module's file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MappedModule {
+    pub(crate) range: Range<usize>,
+    pub(crate) name: PathBuf,
+    pub(crate) base: usize,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn loaded_modules() -> Vec<MappedModule> {
+    std::fs::read_to_string("/proc/self/maps")
+        .map(|maps| parse_proc_maps(&maps))
+        .unwrap_or_default()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn loaded_modules() -> Vec<MappedModule> {
+    vec![]
+}
+
+/// Parses the lines of `/proc/self/maps`, like
+/// `55d4159a3000-55d4159a5000 r-xp 00001000 fd:01 1234    /usr/bin/app`.
+///
+/// Mappings that aren't of a file are skipped. A module's base is the lowest
+/// `start - file offset` of any of its mappings, which is where it would start if it was
+/// mapped in one piece.
+pub(crate) fn parse_proc_maps(maps: &str) -> Vec<MappedModule> {
+    let mut modules: Vec<MappedModule> = maps
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(6, ' ');
+            let (start, end) = {
+                let mut range = fields.next()?.splitn(2, '-');
+                let start = usize::from_str_radix(range.next()?, 16).ok()?;
+                let end = usize::from_str_radix(range.next()?, 16).ok()?;
+                (start, end)
+            };
+            let offset = usize::from_str_radix(fields.nth(1)?, 16).ok()?;
+            // The path is padded with spaces, and can have spaces of its own
+            let name = fields.nth(2)?.trim_start();
+            if !name.starts_with('/') {
+                return None;
+            }
+            Some(MappedModule {
+                range: start..end,
+                name: PathBuf::from(name),
+                base: start.saturating_sub(offset),
+            })
+        })
+        .collect();
+    let bases: Vec<(PathBuf, usize)> = modules
+        .iter()
+        .map(|module| (module.name.clone(), module.base))
+        .collect();
+    for module in &mut modules {
+        module.base = bases
+            .iter()
+            .filter(|(name, _)| *name == module.name)
+            .map(|&(_, base)| base)
+            .min()
+            .unwrap_or(module.base);
+    }
+    modules
+}
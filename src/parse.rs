@@ -0,0 +1,329 @@
+//! Turning formatted backtraces back into data.
+
+use crate::*;
+use std::path::PathBuf;
+
+/// A frame recovered from a formatted backtrace by [`parse_formatted`][].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ParsedFrame {
+    /// The index printed for the frame.
+    pub index: usize,
+    /// The address printed for the frame, if it could be parsed.
+    pub ip: Option<usize>,
+    /// The symbols of the frame, in the order they were printed.
+    /// Empty if the frame was `<unresolved>`.
+    pub symbols: Vec<ParsedSymbol>,
+}
+
+/// One of the symbols of a [`ParsedFrame`][].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ParsedSymbol {
+    /// The name of the symbol, or `None` if it was `<unknown>`.
+    pub name: Option<String>,
+    /// The source file, if a location was printed.
+    pub file: Option<PathBuf>,
+    /// The line in the source file, if a location was printed.
+    pub line: Option<u32>,
+    /// The column in the source file, if one was printed.
+    pub col: Option<u32>,
+}
+
+/// Parses the output of a default [`ShortBacktraceFormatter`][crate::ShortBacktraceFormatter]
+/// back into frames.
+///
+/// This is for post-processing logs that only kept the text of a backtrace. Lines that
+/// aren't part of a frame (like a message before the backtrace) are ignored, as are
+/// the extra lines some formatter options add. Inlined symbols (the indented ` - name`
+/// lines) are added to the frame above them.
+pub fn parse_formatted(text: &str) -> Vec<ParsedFrame> {
+    let mut frames = vec![];
+    for line in text.lines() {
+        parse_line(&mut frames, line);
+    }
+    frames
+}
+
+/// A whole backtrace found by a [`StreamingParser`][].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ParsedShortBacktrace {
+    /// The frames of the backtrace, as [`parse_formatted`][] would return them.
+    pub frames: Vec<ParsedFrame>,
+}
+
+/// Finds the backtraces in a stream of log lines as they come in, like the
+/// incremental version of [`parse_formatted`][].
+///
+/// Feed it lines with [`push_line`][Self::push_line], which returns a backtrace once
+/// it's complete. A backtrace ends at the first line after it that isn't part of a
+/// frame, or when a new backtrace starts (at a frame numbered 0). Indented lines that
+/// aren't part of a frame (like the ones some formatter options add) don't end it.
+/// Call [`finish`][Self::finish] at the end of the stream to get the last backtrace.
+///
+/// ```
+/// let mut parser = backtrace_ext::StreamingParser::new();
+/// let log = "thread 'main' panicked at 'oh no'\n   0: 0x20 - app::main\nexiting";
+/// let traces: Vec<_> = log.lines().filter_map(|line| parser.push_line(line)).collect();
+/// assert_eq!(traces.len(), 1);
+/// assert!(parser.finish().is_none());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StreamingParser {
+    frames: Vec<ParsedFrame>,
+}
+
+impl StreamingParser {
+    /// Makes a parser that hasn't seen any lines yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses the next line of the stream, returning a backtrace if this completed one.
+    pub fn push_line(&mut self, line: &str) -> Option<ParsedShortBacktrace> {
+        let in_trace = !self.frames.is_empty();
+        if in_trace && parse_header(line.trim()).map_or(false, |frame| frame.index == 0) {
+            let done = self.finish();
+            parse_line(&mut self.frames, line);
+            return done;
+        }
+        if parse_line(&mut self.frames, line)
+            || (in_trace && line.starts_with(char::is_whitespace) && !line.trim().is_empty())
+        {
+            None
+        } else {
+            self.finish()
+        }
+    }
+
+    /// Returns the backtrace that was still being parsed, if there was one.
+    ///
+    /// The parser can keep being used afterwards.
+    pub fn finish(&mut self) -> Option<ParsedShortBacktrace> {
+        if self.frames.is_empty() {
+            None
+        } else {
+            Some(ParsedShortBacktrace {
+                frames: std::mem::take(&mut self.frames),
+            })
+        }
+    }
+}
+
+/// Parses the [`Display`][std::fmt::Display] output of a `std::backtrace::Backtrace` into
+/// the frames of its short backtrace (see [`short_frames_strict`][]), for error types that
+/// only captured the standard library's backtrace.
+///
+/// The standard library prints every symbol with its own number, so this can only tell
+/// which symbols were inlined into the same frame from the addresses, which are only
+/// printed by the alternate format (`{:#}`, see `from_std_backtrace`). Consecutive
+/// symbols with the same address are put in one frame, unless the name was already seen in
+/// that frame (which is recursion through the same call, not inlining). Without addresses
+/// every symbol is its own frame. The `::h0123456789abcdef` hashes of the alternate
+/// format are removed from the names, and the `index` of each frame is the number printed
+/// for its first symbol. Unresolved symbols are printed as `<unknown>`, and become
+/// symbols without a name.
+///
+/// A `disabled backtrace` or `unsupported backtrace` has no frames.
+pub fn parse_std_backtrace(text: &str) -> Vec<ParsedFrame> {
+    let mut frames: Vec<ParsedFrame> = vec![];
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some(location) = line.strip_prefix("at ") {
+            if let Some(symbol) = frames.last_mut().and_then(|frame| frame.symbols.last_mut()) {
+                parse_location(symbol, location);
+            }
+            continue;
+        }
+        let (index, rest) = match line.split_once(": ") {
+            Some((index, rest)) => match index.parse() {
+                Ok(index) => (index, rest.trim()),
+                Err(_) => continue,
+            },
+            None => continue,
+        };
+        let (ip, name) = match rest.split_once(" - ") {
+            Some((ip, name)) if ip.starts_with("0x") => {
+                (usize::from_str_radix(&ip[2..], 16).ok(), name)
+            }
+            _ => (None, rest),
+        };
+        let mut symbol = parse_symbol_name(name);
+        symbol.name = symbol.name.map(strip_hash);
+        if let Some(frame) = frames.last_mut() {
+            let inlined = ip.is_some()
+                && frame.ip == ip
+                && !frame.symbols.iter().any(|seen| seen.name == symbol.name);
+            if inlined {
+                frame.symbols.push(symbol);
+                continue;
+            }
+        }
+        frames.push(ParsedFrame {
+            index,
+            ip,
+            symbols: vec![symbol],
+        });
+    }
+    short_frames_strict_impl(&frames)
+        .map(|(frame, subframes)| ParsedFrame {
+            index: frame.index,
+            ip: frame.ip,
+            symbols: frame.symbols[subframes].to_vec(),
+        })
+        .collect()
+}
+
+/// Gets the short backtrace of a `std::backtrace::Backtrace`, like [`parse_std_backtrace`][]
+/// on its alternate format (which has the addresses, so inlined symbols end up in the
+/// same frame).
+///
+/// This needs the `std-backtrace` feature, and Rust 1.65 or later.
+#[cfg(feature = "std-backtrace")]
+#[clippy::msrv = "1.65"]
+pub fn from_std_backtrace(backtrace: &std::backtrace::Backtrace) -> Vec<ParsedFrame> {
+    parse_std_backtrace(&format!("{:#}", backtrace))
+}
+
+/// Removes the `::h0123456789abcdef` hash from the end of a symbol name, if it has one
+fn strip_hash(name: String) -> String {
+    match name.rsplit_once("::h") {
+        Some((path, hash)) if hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit()) => {
+            path.to_owned()
+        }
+        _ => name,
+    }
+}
+
+impl Backtraceish for Vec<ParsedFrame> {
+    type Frame = ParsedFrame;
+    fn frames(&self) -> &[Self::Frame] {
+        self
+    }
+}
+
+impl Frameish for ParsedFrame {
+    type Symbol = ParsedSymbol;
+    fn symbols(&self) -> &[Self::Symbol] {
+        &self.symbols
+    }
+    fn ip(&self) -> *mut c_void {
+        self.ip.unwrap_or(0) as *mut c_void
+    }
+    fn symbol_address(&self) -> *mut c_void {
+        self.ip()
+    }
+    fn module_base_address(&self) -> Option<*mut c_void> {
+        None
+    }
+}
+
+impl Symbolish for ParsedSymbol {
+    fn name_str(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+    fn name_bytes(&self) -> Option<&[u8]> {
+        self.name.as_deref().map(str::as_bytes)
+    }
+    fn has_name(&self) -> bool {
+        self.name.is_some()
+    }
+    fn fmt_name(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name.as_deref().unwrap_or(""))
+    }
+    // The names were printed demangled, so guess by whether it's a path
+    fn is_rust(&self) -> bool {
+        self.name
+            .as_deref()
+            .map_or(false, |name| name.contains("::"))
+    }
+    fn filename(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+    fn lineno(&self) -> Option<u32> {
+        self.line
+    }
+    fn colno(&self) -> Option<u32> {
+        self.col
+    }
+}
+
+/// Parses one line of a formatted backtrace, either starting a new frame or adding to the
+/// last one. Returns whether the line was part of a frame.
+pub(crate) fn parse_line(frames: &mut Vec<ParsedFrame>, line: &str) -> bool {
+    let line = line.trim();
+    if let Some(symbol) = line.strip_prefix("- ") {
+        // An inlined symbol of the current frame
+        if let Some(frame) = frames.last_mut() {
+            frame.symbols.push(parse_symbol_name(symbol));
+            return true;
+        }
+    } else if let Some(location) = line.strip_prefix("at ") {
+        // The location of the last symbol of the current frame
+        if let Some(symbol) = frames.last_mut().and_then(|frame| frame.symbols.last_mut()) {
+            parse_location(symbol, location);
+            return true;
+        }
+    } else if let Some(frame) = parse_header(line) {
+        frames.push(frame);
+        return true;
+    }
+    false
+}
+
+/// Parses `{index}: {ip} - {name}`
+fn parse_header(line: &str) -> Option<ParsedFrame> {
+    let (index, rest) = line.split_once(':')?;
+    let index = index.parse().ok()?;
+    let rest = rest.trim_start();
+    let (ip, symbol) = match rest.split_once(" - ") {
+        Some((ip, symbol)) => (ip, Some(symbol)),
+        None => (rest, None),
+    };
+    let ip = ip.trim();
+    let ip = ip
+        .strip_prefix("0x")
+        .and_then(|hex| usize::from_str_radix(hex, 16).ok());
+    let mut frame = ParsedFrame {
+        index,
+        ip,
+        symbols: vec![],
+    };
+    match symbol {
+        Some("<unresolved>") | None => {}
+        Some(symbol) => frame.symbols.push(parse_symbol_name(symbol)),
+    }
+    Some(frame)
+}
+
+fn parse_symbol_name(name: &str) -> ParsedSymbol {
+    let name = name.trim();
+    ParsedSymbol {
+        name: if name == "<unknown>" {
+            None
+        } else {
+            Some(name.to_owned())
+        },
+        ..ParsedSymbol::default()
+    }
+}
+
+/// Parses `file:line` or `file:line:col`
+fn parse_location(symbol: &mut ParsedSymbol, location: &str) {
+    let mut parts = location.rsplitn(3, ':');
+    let last = parts.next().and_then(|n| n.parse::<u32>().ok());
+    let middle = parts.next();
+    let first = parts.next();
+    match (first, middle.and_then(|n| n.parse::<u32>().ok()), last) {
+        (Some(file), Some(line), Some(col)) => {
+            symbol.file = Some(file.into());
+            symbol.line = Some(line);
+            symbol.col = Some(col);
+        }
+        (_, _, Some(line)) => {
+            // Put the file back together, it might have had a `:` in it (like `C:\`)
+            let file_len = location.len() - location.rsplit(':').next().map_or(0, str::len) - 1;
+            symbol.file = Some(location[..file_len].into());
+            symbol.line = Some(line);
+        }
+        _ => symbol.file = Some(location.into()),
+    }
+}
@@ -0,0 +1,374 @@
+//! Helpers for asking questions about a short backtrace.
+
+use crate::*;
+use std::path::{Path, PathBuf};
+
+/// A place in the source code.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SourceLocation {
+    /// The source file.
+    pub file: PathBuf,
+    /// The line in the source file.
+    pub line: u32,
+    /// The column in the source file, if debuginfo knows it.
+    pub col: Option<u32>,
+}
+
+/// How [`trace_touches_file`][] compares a symbol's file with the given path.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PathMatch {
+    /// The path must equal the symbol's file.
+    Full,
+    /// The path must be a suffix of the symbol's file, compared by whole components,
+    /// so `lib.rs` and `src/lib.rs` match `/home/me/app/src/lib.rs` but `b.rs` doesn't
+    /// match `/home/me/app/src/lib.rs`.
+    Suffix,
+}
+
+/// Checks whether any symbol in the short backtrace is in the given file.
+///
+/// This is handy for tests asserting that a panic went through a particular module.
+/// Keep in mind that debuginfo usually records absolute paths, so you'll usually want
+/// [`PathMatch::Suffix`][].
+pub fn trace_touches_file(backtrace: &Backtrace, path: &Path, matching: PathMatch) -> bool {
+    trace_touches_file_impl(backtrace, path, matching)
+}
+
+pub(crate) fn trace_touches_file_impl<B: Backtraceish>(
+    backtrace: &B,
+    path: &Path,
+    matching: PathMatch,
+) -> bool {
+    short_frames_strict_impl(backtrace).any(|(frame, subframes)| {
+        frame.symbols()[subframes].iter().any(|symbol| {
+            symbol.filename().map_or(false, |file| match matching {
+                PathMatch::Full => file == path,
+                PathMatch::Suffix => file.ends_with(path),
+            })
+        })
+    })
+}
+
+/// Finds the symbols of the short backtrace whose source location is in `lines` of `file`,
+/// for checking whether a panic went through a particular region of code.
+///
+/// Yields each matching frame with the index of the matching symbol in `frame.symbols()`
+/// (a frame with several matching inlined symbols is yielded once for each). `file` is
+/// matched like [`PathMatch::Suffix`][], since debuginfo usually records absolute paths.
+/// Symbols without a file or line are never yielded.
+pub fn short_frames_in_line_range<'a>(
+    backtrace: &'a Backtrace,
+    file: &'a Path,
+    lines: Range<u32>,
+) -> impl Iterator<Item = (&'a BacktraceFrame, usize)> {
+    short_frames_in_line_range_impl(backtrace, file, lines)
+}
+
+pub(crate) fn short_frames_in_line_range_impl<'a, B: Backtraceish>(
+    backtrace: &'a B,
+    file: &'a Path,
+    lines: Range<u32>,
+) -> impl Iterator<Item = (&'a B::Frame, usize)> {
+    short_frames_strict_impl(backtrace).flat_map(move |(frame, subframes)| {
+        let lines = lines.clone();
+        subframes
+            .filter(move |&subframe_idx| {
+                let symbol = &frame.symbols()[subframe_idx];
+                match (symbol.filename(), symbol.lineno()) {
+                    (Some(symbol_file), Some(line)) => {
+                        symbol_file.ends_with(file) && lines.contains(&line)
+                    }
+                    _ => false,
+                }
+            })
+            .map(move |subframe_idx| (frame, subframe_idx))
+    })
+}
+
+/// Symbols that only show up under the main thread, where the standard library
+/// calls your `main`.
+///
+/// These are matched against whole path segments at the start of the demangled symbol
+/// name, so `std::rt::lang_start` also matches `std::rt::lang_start::{{closure}}`.
+pub const MAIN_SYMBOLS: &[&str] = &["std::rt::lang_start", "main"];
+
+/// Symbols of the standard library's code that starts a thread made with
+/// `std::thread::spawn` (or `std::thread::Builder`) and calls its closure.
+///
+/// These are matched against the demangled symbol name with `contains`, since the paths
+/// have moved around between Rust versions and some are printed as qualified paths (like
+/// `<std::sys::thread::unix::Thread>::new::thread_start`).
+pub const THREAD_ENTRY_SYMBOLS: &[&str] = &[
+    "std::thread::Builder::spawn_unchecked_",
+    "std::thread::lifecycle::spawn_unchecked",
+    "Thread::new::thread_start",
+    "Thread>::new::thread_start",
+];
+
+/// Whether this (demangled) symbol name is one of the [`THREAD_ENTRY_SYMBOLS`][].
+pub(crate) fn is_thread_entry_name(name: &str) -> bool {
+    THREAD_ENTRY_SYMBOLS
+        .iter()
+        .any(|entry| name.contains(entry))
+}
+
+/// Finds where a spawned thread was started: the first frame with one of the
+/// [`THREAD_ENTRY_SYMBOLS`][], looking at the last frame of the short backtrace and every
+/// frame older than it (like [`reaches_main`][]).
+///
+/// Returns the frame and the index of the matching symbol in `frame.symbols()`. If this
+/// returns `None` the backtrace is probably from the main thread, or from a thread that
+/// wasn't started by the standard library.
+pub fn thread_entry_frame(backtrace: &Backtrace) -> Option<(&BacktraceFrame, usize)> {
+    thread_entry_frame_impl(backtrace)
+}
+
+pub(crate) fn thread_entry_frame_impl<B: Backtraceish>(
+    backtrace: &B,
+) -> Option<(&B::Frame, usize)> {
+    bottom_symbols(backtrace)
+        .find(|(_, _, name)| is_thread_entry_name(name))
+        .map(|(frame, idx, _)| (frame, idx))
+}
+
+/// The symbols of the last frame of the short backtrace and every frame older than it,
+/// with their frame, index in `frame.symbols()` and demangled name. `main` and the start of
+/// a thread are usually clamped away along with `rust_begin_short_backtrace`, so this is
+/// where to look for them.
+fn bottom_symbols<B: Backtraceish>(
+    backtrace: &B,
+) -> impl Iterator<Item = (&B::Frame, usize, String)> {
+    let (_, frame_range, _, _) = short_slice_impl(backtrace);
+    let start = frame_range.end.saturating_sub(1);
+    backtrace.frames()[start..].iter().flat_map(|frame| {
+        frame
+            .symbols()
+            .iter()
+            .enumerate()
+            .filter_map(move |(idx, symbol)| Some((frame, idx, demangled_name(symbol)?)))
+    })
+}
+
+/// Checks whether the backtrace goes all the way down to `main`, to tell panics on the
+/// main thread apart from ones on other threads (which bottom out in the thread's
+/// closure instead).
+///
+/// This looks for [`MAIN_SYMBOLS`][] in the last frame of the short backtrace and every
+/// frame older than it, since `main` itself is usually clamped away along with
+/// `rust_begin_short_backtrace`. If the start of a spawned thread (see
+/// [`thread_entry_frame`][]) comes first, this is false. It's a heuristic based on the
+/// standard library's internals, so a `#[no_main]` program or a runtime that starts threads
+/// differently might not be detected.
+pub fn reaches_main(backtrace: &Backtrace) -> bool {
+    reaches_main_impl(backtrace)
+}
+
+pub(crate) fn reaches_main_impl<B: Backtraceish>(backtrace: &B) -> bool {
+    bottom_symbols(backtrace)
+        .find_map(|(_, _, name)| {
+            if is_thread_entry_name(&name) {
+                Some(false)
+            } else if MAIN_SYMBOLS
+                .iter()
+                .any(|main| names::matches_path_prefix(&name, main))
+            {
+                Some(true)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(false)
+}
+
+/// Finds the first (newest) frame in the short backtrace with a symbol whose name
+/// contains `name`.
+///
+/// Returns the frame and the index of the matching symbol in `frame.symbols()`.
+/// Matching is done on the demangled name (without the hash), so you can search for
+/// something like `my_module::validate`. Only the range yielded by [`short_frames_strict`][]
+/// is searched, so startup and panic runtime frames will never match.
+pub fn find_frame_by_name<'a>(
+    backtrace: &'a Backtrace,
+    name: &str,
+) -> Option<(&'a BacktraceFrame, usize)> {
+    find_frame_by_name_impl(backtrace, name)
+}
+
+/// Finds the `n`th (counting from 0) frame of the short backtrace that actually tells you
+/// something, for attributing a crash to a specific depth of the call path.
+///
+/// Frames are skipped the same way as in [`meaningful_frame_count`][]: if they're entirely
+/// [`GUNK_SYMBOLS`][] or have no name or source file at all. Returns the frame and the index
+/// of its first such symbol in `frame.symbols()`, or `None` if there are `n` or fewer
+/// meaningful frames. This stops as soon as it finds the frame.
+pub fn nth_real_frame(backtrace: &Backtrace, n: usize) -> Option<(&BacktraceFrame, usize)> {
+    nth_real_frame_impl(backtrace, n)
+}
+
+/// Finds the oldest frame in the short backtrace that is part of your code.
+///
+/// This is the last point where your code had control before the backtrace descended
+/// into it (or, read the other way, where your code was entered from a library).
+/// See [`classify_symbol`][] for how `user_prefix` is matched. Returns the frame and the
+/// index of the matching symbol in `frame.symbols()`.
+pub fn deepest_user_frame<'a>(
+    backtrace: &'a Backtrace,
+    user_prefix: &str,
+) -> Option<(&'a BacktraceFrame, usize)> {
+    deepest_user_frame_impl(backtrace, user_prefix)
+}
+
+/// Gets the source location of the newest symbol in the short backtrace that has one,
+/// skipping over any [`GUNK_SYMBOLS`][].
+///
+/// For a backtrace captured in a panic hook this is where the panic happened, which is
+/// handy for "jump to the panic" features in editors. Returns `None` if no symbol in the
+/// short backtrace has both a file and a line.
+pub fn panic_location(backtrace: &Backtrace) -> Option<SourceLocation> {
+    panic_location_impl(backtrace)
+}
+
+/// Like [`panic_location`][], but also gets the demangled name of the symbol at that
+/// location, for one-line summaries like `app::parse at src/parse.rs:10`.
+///
+/// The name is `<unknown>` if debuginfo had a location for the symbol but no name.
+pub fn panic_location_named(backtrace: &Backtrace) -> Option<(String, SourceLocation)> {
+    panic_location_named_impl(backtrace)
+}
+
+/// Checks whether the newest interesting frame of the short backtrace is part of your code,
+/// for a quick "is this my bug or a dependency's" signal when routing crashes.
+///
+/// Symbols without a name, [`GUNK_SYMBOLS`][], [`SYNC_GLUE_SYMBOLS`][] and the rest of the
+/// standard library are skipped, so that a panic in something like `Option::unwrap` is
+/// blamed on the code that called it. The first symbol left is then classified as
+/// described in [`classify_symbol`][]. Returns `false` if there's no such symbol.
+pub fn panic_in_user_code(backtrace: &Backtrace, crate_prefix: &str) -> bool {
+    panic_in_user_code_impl(backtrace, crate_prefix)
+}
+
+pub(crate) fn panic_in_user_code_impl<B: Backtraceish>(backtrace: &B, crate_prefix: &str) -> bool {
+    interesting_symbols(backtrace, crate_prefix)
+        .next()
+        .map_or(false, |(_, kind)| kind == FrameKind::User)
+}
+
+/// Gets the crate of the newest interesting symbol of the short backtrace that's part of
+/// your code, or of the newest interesting symbol at all if none of them are, for routing
+/// crash reports to whoever owns that crate.
+///
+/// Symbols are skipped like in [`panic_in_user_code`][] (so the standard library never
+/// counts), and `user_prefix` is matched as described in [`classify_symbol`][]. Code that
+/// isn't Rust doesn't have a crate, so it's skipped too. Returns `None` if no symbol is
+/// left.
+pub fn top_crate(backtrace: &Backtrace, user_prefix: &str) -> Option<String> {
+    top_crate_impl(backtrace, user_prefix)
+}
+
+pub(crate) fn top_crate_impl<B: Backtraceish>(backtrace: &B, user_prefix: &str) -> Option<String> {
+    let mut first = None;
+    for (name, kind) in interesting_symbols(backtrace, user_prefix) {
+        let crate_name = match names::crate_name(&name) {
+            Some(crate_name) if kind != FrameKind::Foreign => crate_name.to_owned(),
+            _ => continue,
+        };
+        if kind == FrameKind::User {
+            return Some(crate_name);
+        }
+        first.get_or_insert(crate_name);
+    }
+    first
+}
+
+/// The demangled names and kinds of the symbols in the short backtrace, skipping the ones
+/// without a name, [`GUNK_SYMBOLS`][], [`SYNC_GLUE_SYMBOLS`][] and the standard library
+fn interesting_symbols<'a, B: Backtraceish>(
+    backtrace: &'a B,
+    user_prefix: &'a str,
+) -> impl Iterator<Item = (String, FrameKind)> + 'a {
+    short_frames_strict_impl(backtrace)
+        .flat_map(|(frame, subframes)| frame.symbols()[subframes].iter())
+        .filter_map(move |symbol| {
+            let name = demangled_name(symbol)?;
+            let is_glue = gunk::is_gunk_name(&name)
+                || SYNC_GLUE_SYMBOLS.iter().any(|glue| name.contains(glue));
+            match classify::classify_symbol_impl(symbol, user_prefix) {
+                _ if is_glue => None,
+                FrameKind::Std => None,
+                kind => Some((name, kind)),
+            }
+        })
+}
+
+pub(crate) fn panic_location_impl<B: Backtraceish>(backtrace: &B) -> Option<SourceLocation> {
+    panic_location_named_impl(backtrace).map(|(_, location)| location)
+}
+
+pub(crate) fn panic_location_named_impl<B: Backtraceish>(
+    backtrace: &B,
+) -> Option<(String, SourceLocation)> {
+    for (frame, subframes) in short_frames_strict_impl(backtrace) {
+        for symbol in &frame.symbols()[subframes] {
+            let name = demangled_name(symbol);
+            if name.as_deref().map_or(false, gunk::is_gunk_name) {
+                continue;
+            }
+            if let (Some(file), Some(line)) = (symbol.filename(), symbol.lineno()) {
+                let location = SourceLocation {
+                    file: file.to_owned(),
+                    line,
+                    col: symbol.colno(),
+                };
+                return Some((name.unwrap_or_else(|| "<unknown>".to_owned()), location));
+            }
+        }
+    }
+    None
+}
+
+pub(crate) fn nth_real_frame_impl<B: Backtraceish>(
+    backtrace: &B,
+    n: usize,
+) -> Option<(&B::Frame, usize)> {
+    short_frames_strict_impl(backtrace)
+        .filter_map(|(frame, subframes)| {
+            let symbols = frame.symbols();
+            subframes
+                .clone()
+                .find(|&idx| stats::is_meaningful(&symbols[idx]))
+                .map(|idx| (frame, idx))
+        })
+        .nth(n)
+}
+
+pub(crate) fn deepest_user_frame_impl<'a, B: Backtraceish>(
+    backtrace: &'a B,
+    user_prefix: &str,
+) -> Option<(&'a B::Frame, usize)> {
+    let mut deepest = None;
+    for (frame, subframes) in short_frames_strict_impl(backtrace) {
+        for subframe_idx in subframes {
+            let symbol = &frame.symbols()[subframe_idx];
+            if classify::classify_symbol_impl(symbol, user_prefix) == FrameKind::User {
+                deepest = Some((frame, subframe_idx));
+            }
+        }
+    }
+    deepest
+}
+
+pub(crate) fn find_frame_by_name_impl<'a, B: Backtraceish>(
+    backtrace: &'a B,
+    name: &str,
+) -> Option<(&'a B::Frame, usize)> {
+    for (frame, subframes) in short_frames_strict_impl(backtrace) {
+        for subframe_idx in subframes {
+            if let Some(symbol_name) = demangled_name(&frame.symbols()[subframe_idx]) {
+                if symbol_name.contains(name) {
+                    return Some((frame, subframe_idx));
+                }
+            }
+        }
+    }
+    None
+}
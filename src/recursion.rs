@@ -0,0 +1,184 @@
+//! Finding (possibly mutual) recursion in a short backtrace.
+
+use crate::*;
+
+/// A run of frames in the short backtrace that repeats the same cycle of functions,
+/// found by [`recursion_cycles`][].
+///
+/// For a backtrace of `a → b → a → b → a → b → main` this is
+/// `RecursionCycle { start: 0, len: 2, repeats: 3 }`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RecursionCycle {
+    /// The index of the cycle's first (newest) frame in the short backtrace.
+    pub start: usize,
+    /// How many frames one pass through the cycle takes.
+    pub len: usize,
+    /// How many times the cycle repeats back to back (at least 2).
+    pub repeats: usize,
+}
+
+impl RecursionCycle {
+    /// The range of indices in the short backtrace covered by every pass of the cycle.
+    pub fn frames(&self) -> Range<usize> {
+        self.start..self.start + self.len * self.repeats
+    }
+}
+
+/// Finds the runs of the short backtrace where the same cycle of at most `max_cycle_len`
+/// frames repeats, like the `a → b → a → b` of mutual recursion.
+///
+/// Frames are compared by the demangled names of all of their symbols, and frames with
+/// an unnamed symbol (or no symbols at all) never match anything. The backtrace is
+/// scanned from newest to oldest, taking at each point the cycle that covers the most
+/// frames (preferring shorter cycles for ties, so `a → a → a → a` is `a` 4 times rather
+/// than `a → a` twice). The cycles returned don't overlap and are in order.
+pub fn recursion_cycles(backtrace: &Backtrace, max_cycle_len: usize) -> Vec<RecursionCycle> {
+    recursion_cycles_impl(backtrace, max_cycle_len)
+}
+
+pub(crate) fn recursion_cycles_impl<B: Backtraceish>(
+    backtrace: &B,
+    max_cycle_len: usize,
+) -> Vec<RecursionCycle> {
+    let keys: Vec<_> = short_frames_strict_impl(backtrace)
+        .map(|(frame, subframes)| frame_key(frame, subframes))
+        .collect();
+    find_cycles(&keys, max_cycle_len)
+}
+
+/// Whether two symbols are the same call site: the same function, at the same line of
+/// the same file.
+///
+/// Addresses aren't compared, so this matches a call that was inlined into one frame with
+/// the same call in a frame of its own, or the same line in two copies of a generic
+/// function. Names are compared demangled and without the hash, and columns are ignored.
+///
+/// A symbol without a name is never the same call site as anything (not even itself),
+/// since there's no telling what it was. A named symbol without a file or line only
+/// matches symbols with the same name that are also missing it.
+pub fn same_call_site(a: &BacktraceSymbol, b: &BacktraceSymbol) -> bool {
+    same_call_site_impl(a, b)
+}
+
+pub(crate) fn same_call_site_impl<S: Symbolish>(a: &S, b: &S) -> bool {
+    match (demangled_name(a), demangled_name(b)) {
+        (Some(a_name), Some(b_name)) => {
+            a_name == b_name && a.filename() == b.filename() && a.lineno() == b.lineno()
+        }
+        _ => false,
+    }
+}
+
+/// A frame of the short backtrace, or a run of the same frame repeated back to back,
+/// from [`short_frame_groups`][].
+#[derive(Debug, Clone)]
+pub enum ShortFrameGroup<'a> {
+    /// A frame that isn't the same as the frames around it.
+    Single(ShortFrame<'a>),
+    /// The same frame, `count` times in a row (at least 2). `frame` is the first (newest)
+    /// of them.
+    Repeated {
+        /// The first frame of the run.
+        frame: ShortFrame<'a>,
+        /// How many frames the run has.
+        count: usize,
+    },
+}
+
+/// Like [`short_frames_strict`][], but groups runs of the same frame repeated back to back
+/// (like plain recursion) into a single [`ShortFrameGroup::Repeated`][].
+///
+/// Two frames are the same if their symbols in the short backtrace are the same call
+/// sites, as described in [`same_call_site`][]: names and file:line are compared, not
+/// addresses. So frames without a name never repeat, and mutual recursion like
+/// `a → b → a → b` isn't grouped at all (see [`recursion_cycles`][] for that).
+pub fn short_frame_groups(backtrace: &Backtrace) -> impl Iterator<Item = ShortFrameGroup<'_>> {
+    short_frame_groups_impl(backtrace).map(|(frame, sub_frames, count)| {
+        let frame = ShortFrame { frame, sub_frames };
+        if count == 1 {
+            ShortFrameGroup::Single(frame)
+        } else {
+            ShortFrameGroup::Repeated { frame, count }
+        }
+    })
+}
+
+/// Yields the first frame of each run of [`short_frame_groups`][], and the length of the run
+pub(crate) fn short_frame_groups_impl<B: Backtraceish>(
+    backtrace: &B,
+) -> impl Iterator<Item = (&B::Frame, Range<usize>, usize)> {
+    let mut frames = short_frames_strict_impl(backtrace).peekable();
+    std::iter::from_fn(move || {
+        let (frame, subframes) = frames.next()?;
+        let mut count = 1;
+        while frames
+            .next_if(|(next, next_subframes)| {
+                same_frame(frame, subframes.clone(), *next, next_subframes.clone())
+            })
+            .is_some()
+        {
+            count += 1;
+        }
+        Some((frame, subframes, count))
+    })
+}
+
+/// Whether the symbols of two frames are all the same call sites (see [`same_call_site`][]).
+/// Frames without symbols are never the same.
+pub(crate) fn same_frame<F: Frameish>(
+    a: &F,
+    a_subframes: Range<usize>,
+    b: &F,
+    b_subframes: Range<usize>,
+) -> bool {
+    let a = &a.symbols()[a_subframes];
+    let b = &b.symbols()[b_subframes];
+    !a.is_empty() && a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_call_site_impl(a, b))
+}
+
+/// What frames are compared by, `None` if it can't be compared
+pub(crate) fn frame_key<F: Frameish>(frame: &F, subframes: Range<usize>) -> Option<Vec<String>> {
+    let symbols = &frame.symbols()[subframes];
+    if symbols.is_empty() {
+        return None;
+    }
+    symbols.iter().map(demangled_name).collect()
+}
+
+/// Finds the cycles in a list of frame keys (see [`recursion_cycles`][])
+pub(crate) fn find_cycles<K: PartialEq>(
+    keys: &[Option<K>],
+    max_cycle_len: usize,
+) -> Vec<RecursionCycle> {
+    let mut cycles = vec![];
+    let mut start = 0;
+    while start < keys.len() {
+        let mut best: Option<RecursionCycle> = None;
+        for len in 1..=max_cycle_len {
+            let cycle = &keys[start..keys.len().min(start + len)];
+            if cycle.len() < len || cycle.iter().any(Option::is_none) {
+                break;
+            }
+            let repeats = 1 + keys[start + len..]
+                .chunks(len)
+                .take_while(|&chunk| chunk == cycle)
+                .count();
+            let covered = len * repeats;
+            if repeats >= 2 && best.map_or(true, |best| covered > best.len * best.repeats) {
+                best = Some(RecursionCycle {
+                    start,
+                    len,
+                    repeats,
+                });
+            }
+        }
+        match best {
+            Some(cycle) => {
+                start = cycle.frames().end;
+                cycles.push(cycle);
+            }
+            None => start += 1,
+        }
+    }
+    cycles
+}
@@ -0,0 +1,74 @@
+//! Complete reports for panic hooks.
+
+use crate::*;
+use std::any::Any;
+
+/// Formats a panic the way the standard library's panic hook does, but with the short
+/// backtrace, for a panic hook that just wants to print (or log) one string:
+///
+/// ```text
+/// thread 'main' panicked at src/main.rs:17:5:
+/// ooopssie!!!
+/// stack backtrace:
+///    0: 0x55d4159a3b2f - app::do_thing
+///                 at src/main.rs:17
+///    1: 0x55d4159a3c40 - app::main
+///                 at src/main.rs:12
+/// ```
+///
+/// The thread is the current one, so call this from inside the hook. If the panic's payload
+/// isn't a string (because of `panic_any`) the message is `Box<dyn Any>`, like std prints.
+/// The frames are printed by a default [`ShortBacktraceFormatter`][], and the report
+/// doesn't end with a newline.
+///
+/// ```
+/// std::panic::set_hook(Box::new(|info| {
+///     let backtrace = backtrace::Backtrace::new();
+///     eprintln!("{}", backtrace_ext::format_panic_report(info, &backtrace));
+/// }));
+/// # let _ = std::panic::take_hook();
+/// ```
+// PanicInfo was renamed to PanicHookInfo long after our MSRV
+#[allow(deprecated)]
+pub fn format_panic_report(info: &std::panic::PanicInfo<'_>, backtrace: &Backtrace) -> String {
+    let location = info
+        .location()
+        .map(|location| (location.file(), location.line(), location.column()));
+    let thread = std::thread::current();
+    format_panic_report_impl(
+        thread.name(),
+        panic_message(info.payload()),
+        location,
+        backtrace,
+    )
+}
+
+pub(crate) fn format_panic_report_impl<B: Backtraceish>(
+    thread: Option<&str>,
+    message: &str,
+    location: Option<(&str, u32, u32)>,
+    backtrace: &B,
+) -> String {
+    let mut report = format!("thread '{}' panicked", thread.unwrap_or("<unnamed>"));
+    if let Some((file, line, col)) = location {
+        report.push_str(&format!(" at {}:{}:{}", file, line, col));
+    }
+    report.push_str(":\n");
+    report.push_str(message);
+    report.push_str("\nstack backtrace:");
+    // Writing to a String can't fail
+    let _ = ShortBacktraceFormatter::new().write_impl(&mut report, backtrace);
+    report
+}
+
+/// Gets the message out of a panic's payload, which is a `&str` or `String` unless
+/// `panic_any` was used.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "Box<dyn Any>"
+    }
+}
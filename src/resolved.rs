@@ -0,0 +1,93 @@
+//! Owned snapshots of a short backtrace.
+
+use crate::*;
+use std::path::PathBuf;
+
+/// An owned copy of one symbol of a short backtrace.
+///
+/// Unlike the frames and symbols of a [`Backtrace`][], this doesn't borrow anything,
+/// so it can be sent off to another thread (like a background crash reporter).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResolvedFrame {
+    /// The index of the frame this symbol came from, in the short backtrace.
+    /// Several symbols can share an index if they were inlined into one frame.
+    pub index: usize,
+    /// The instruction pointer of the frame.
+    pub ip: usize,
+    /// The demangled name of the symbol (without the hash).
+    pub name: Option<String>,
+    /// The source file of the symbol.
+    pub file: Option<PathBuf>,
+    /// The line in the source file.
+    pub line: Option<u32>,
+}
+
+/// Gets an iterator over every symbol in the short backtrace, flattening out the frames.
+///
+/// Each item is the index of the frame in the short backtrace, the frame, and the symbol.
+/// This does the `symbols()[sub_frames]` slicing from [`short_frames_strict`][] for you.
+/// Note that frames which have no symbols at all don't yield anything.
+pub fn short_symbols(
+    backtrace: &Backtrace,
+) -> impl Iterator<Item = (usize, &BacktraceFrame, &BacktraceSymbol)> {
+    short_symbols_impl(backtrace)
+}
+
+pub(crate) fn short_symbols_impl<B: Backtraceish>(
+    backtrace: &B,
+) -> impl Iterator<
+    Item = (
+        usize,
+        &B::Frame,
+        &<<B as Backtraceish>::Frame as Frameish>::Symbol,
+    ),
+> {
+    short_frames_strict_impl(backtrace)
+        .enumerate()
+        .flat_map(|(idx, (frame, subframes))| {
+            frame.symbols()[subframes]
+                .iter()
+                .map(move |symbol| (idx, frame, symbol))
+        })
+}
+
+/// Gets an owned copy of every symbol in the short backtrace.
+///
+/// This is the owned counterpart of [`short_symbols`][], except that frames with no
+/// symbols at all still produce one [`ResolvedFrame`][] (with no name, file, or line),
+/// so that the snapshot doesn't silently lose frames.
+pub fn resolved_short_symbols(backtrace: &Backtrace) -> impl Iterator<Item = ResolvedFrame> + '_ {
+    resolved_short_symbols_impl(backtrace)
+}
+
+pub(crate) fn resolved_short_symbols_impl<B: Backtraceish>(
+    backtrace: &B,
+) -> impl Iterator<Item = ResolvedFrame> + '_ {
+    short_frames_strict_impl(backtrace)
+        .enumerate()
+        .flat_map(|(index, (frame, subframes))| {
+            let ip = frame.ip() as usize;
+            let symbols = &frame.symbols()[subframes];
+            let unresolved = if symbols.is_empty() {
+                Some(ResolvedFrame {
+                    index,
+                    ip,
+                    name: None,
+                    file: None,
+                    line: None,
+                })
+            } else {
+                None
+            };
+            symbols
+                .iter()
+                .map(move |symbol| ResolvedFrame {
+                    index,
+                    ip,
+                    name: demangled_name(symbol),
+                    file: symbol.filename().map(|file| file.to_owned()),
+                    line: symbol.lineno(),
+                })
+                .chain(unresolved)
+        })
+}
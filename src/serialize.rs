@@ -0,0 +1,71 @@
+//! Serializing a short backtrace with serde, for shipping it somewhere else to be rendered.
+
+use crate::*;
+use serde::{Deserialize, Serialize};
+
+/// The short backtrace as plain data that can be serialized with serde (with the `serde`
+/// feature), so a crash reporter can send it to a server that renders it later.
+///
+/// This has every frame that [`short_frames_strict`][] yields, with the symbols in each
+/// frame's range. Unlike [`ResolvedFrame`][], the symbols stay grouped by frame, so
+/// inlined calls can be told apart from real frames after the round trip.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SerializableShortBacktrace {
+    /// The frames, newest first.
+    pub frames: Vec<SerializableFrame>,
+}
+
+/// One frame of a [`SerializableShortBacktrace`][].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SerializableFrame {
+    /// The instruction pointer as a hex string (like `0x55d4159a3b2f`), since plenty of
+    /// formats (like JSON) can't reliably hold a 64-bit number.
+    pub ip: String,
+    /// The symbols of the frame that are in the short backtrace. This is empty if the
+    /// frame couldn't be resolved.
+    pub symbols: Vec<SerializableSymbol>,
+}
+
+/// One symbol of a [`SerializableFrame`][].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SerializableSymbol {
+    /// The demangled name of the symbol (without the hash).
+    pub name: Option<String>,
+    /// The source file of the symbol. Paths that aren't valid UTF-8 have the invalid parts
+    /// replaced with `�`, so that serializing can't fail.
+    pub file: Option<String>,
+    /// The line in the source file.
+    pub line: Option<u32>,
+}
+
+impl SerializableShortBacktrace {
+    /// Copies the short backtrace out of `backtrace`.
+    pub fn new(backtrace: &Backtrace) -> Self {
+        Self::new_impl(backtrace)
+    }
+
+    pub(crate) fn new_impl<B: Backtraceish>(backtrace: &B) -> Self {
+        let frames = short_frames_strict_impl(backtrace)
+            .map(|(frame, subframes)| SerializableFrame {
+                ip: format!("{:#x}", frame.ip() as usize),
+                symbols: frame.symbols()[subframes]
+                    .iter()
+                    .map(|symbol| SerializableSymbol {
+                        name: demangled_name(symbol),
+                        file: symbol
+                            .filename()
+                            .map(|file| file.to_string_lossy().into_owned()),
+                        line: symbol.lineno(),
+                    })
+                    .collect(),
+            })
+            .collect();
+        SerializableShortBacktrace { frames }
+    }
+}
+
+impl From<&Backtrace> for SerializableShortBacktrace {
+    fn from(backtrace: &Backtrace) -> Self {
+        Self::new(backtrace)
+    }
+}
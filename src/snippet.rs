@@ -0,0 +1,40 @@
+//! Showing the source code around a location, like `color-eyre` does.
+
+use std::path::Path;
+
+/// Reads the lines of `file` around `line`, for showing the code of a frame.
+///
+/// Every line from `line - radius` to `line + radius` (clamped to the file) is prefixed
+/// with its number, and `line` itself is marked with a `>`:
+///
+/// ```text
+///   15 |     let parsed = parse(input);
+/// > 16 |     panic!("ooopssie!!!");
+///   17 | }
+/// ```
+///
+/// The lines are joined with `\n`, without one at the start or end. Returns `None` if the
+/// file can't be read (debuginfo paths often point to where the code was *built*, like
+/// `/rustc/...` for std) or doesn't have that line. Invalid UTF-8 is replaced with `�`.
+pub fn source_snippet(file: &Path, line: u32, radius: usize) -> Option<String> {
+    let target = (line as usize).checked_sub(1)?;
+    let bytes = std::fs::read(file).ok()?;
+    let source = String::from_utf8_lossy(&bytes);
+    let lines: Vec<&str> = source.lines().collect();
+    if target >= lines.len() {
+        return None;
+    }
+    let first = target.saturating_sub(radius);
+    let last = target.saturating_add(radius).min(lines.len() - 1);
+    let width = (last + 1).to_string().len();
+    let snippet: Vec<String> = (first..=last)
+        .map(|idx| {
+            let mark = if idx == target { '>' } else { ' ' };
+            let code = lines[idx].trim_end();
+            format!("{} {:>3$} | {}", mark, idx + 1, code, width)
+                .trim_end()
+                .to_owned()
+        })
+        .collect();
+    Some(snippet.join("\n"))
+}
@@ -0,0 +1,209 @@
+//! Numbers that summarize a short backtrace.
+
+use crate::*;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+/// Counts how many different source files the short backtrace passes through.
+///
+/// Symbols without a filename are ignored. This is a cheap way to tell whether a panic
+/// is localized to one part of the codebase or spread all over it.
+pub fn distinct_source_files(backtrace: &Backtrace) -> usize {
+    distinct_source_files_impl(backtrace)
+}
+
+pub(crate) fn distinct_source_files_impl<B: Backtraceish>(backtrace: &B) -> usize {
+    let mut files = HashSet::new();
+    for (frame, subframes) in short_frames_strict_impl(backtrace) {
+        for symbol in &frame.symbols()[subframes] {
+            if let Some(file) = symbol.filename() {
+                files.insert(file);
+            }
+        }
+    }
+    files.len()
+}
+
+/// Counts the frames of the short backtrace in each source directory (the parent
+/// directory of each symbol's filename), to see where a panic spends its time.
+///
+/// A frame with inlined symbols from several directories is counted once in each of them.
+/// Frames that don't have any filenames are counted under `None`, and bare filenames
+/// (with no directory at all) under an empty path.
+pub fn frames_by_directory(backtrace: &Backtrace) -> HashMap<Option<PathBuf>, usize> {
+    frames_by_directory_impl(backtrace)
+}
+
+pub(crate) fn frames_by_directory_impl<B: Backtraceish>(
+    backtrace: &B,
+) -> HashMap<Option<PathBuf>, usize> {
+    let mut counts = HashMap::new();
+    for (frame, subframes) in short_frames_strict_impl(backtrace) {
+        let dirs: HashSet<_> = frame.symbols()[subframes]
+            .iter()
+            .filter_map(|symbol| symbol.filename())
+            .map(|file| file.parent().map(Path::to_owned).unwrap_or_default())
+            .collect();
+        if dirs.is_empty() {
+            *counts.entry(None).or_insert(0) += 1;
+        }
+        for dir in dirs {
+            *counts.entry(Some(dir)).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+/// Counts the frames of the short backtrace that actually tell you something.
+///
+/// Frames are skipped if they're entirely [`GUNK_SYMBOLS`][] or have no name or source
+/// file at all. A count of 0 means there's nothing useful in the backtrace, so it might
+/// not be worth logging.
+pub fn meaningful_frame_count(backtrace: &Backtrace) -> usize {
+    meaningful_frame_count_impl(backtrace)
+}
+
+pub(crate) fn meaningful_frame_count_impl<B: Backtraceish>(backtrace: &B) -> usize {
+    short_frames_strict_impl(backtrace)
+        .filter(|(frame, subframes)| frame.symbols()[subframes.clone()].iter().any(is_meaningful))
+        .count()
+}
+
+/// Whether the symbol has a name or source file, and isn't [gunk][GUNK_SYMBOLS]
+pub(crate) fn is_meaningful<S: Symbolish>(symbol: &S) -> bool {
+    is_resolved(symbol) && !demangled_name(symbol).map_or(false, |name| gunk::is_gunk_name(&name))
+}
+
+/// What [`crate_distribution`][] calls the frames that aren't from a known crate.
+pub const UNKNOWN_CRATE: &str = "<unknown>";
+
+/// Gets the percentage of the short backtrace's frames that are in each crate, like
+/// `[("tokio", 60.0), ("app", 30.0), ("std", 10.0)]`, for a quick summary of where a
+/// crash happened.
+///
+/// Frames are counted under the crate found the same way as [`short_backtrace_tree`][]
+/// does. Frames without a Rust name (unresolved frames and code that isn't Rust) are
+/// counted under [`UNKNOWN_CRATE`][]. The percentages add up to 100 and are sorted from
+/// largest to smallest, with ties sorted by name. An empty short backtrace gives an
+/// empty list.
+pub fn crate_distribution(backtrace: &Backtrace) -> Vec<(String, f64)> {
+    crate_distribution_impl(backtrace)
+}
+
+pub(crate) fn crate_distribution_impl<B: Backtraceish>(backtrace: &B) -> Vec<(String, f64)> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let mut total = 0;
+    for (frame, subframes) in short_frames_strict_impl(backtrace) {
+        let crate_name = classify::frame_crate(frame, subframes);
+        *counts
+            .entry(crate_name.unwrap_or_else(|| UNKNOWN_CRATE.to_owned()))
+            .or_insert(0) += 1;
+        total += 1;
+    }
+    let mut distribution: Vec<_> = counts
+        .into_iter()
+        .map(|(crate_name, count)| (crate_name, count, 100.0 * count as f64 / total as f64))
+        .collect();
+    distribution.sort_by(|(a_name, a_count, _), (b_name, b_count, _)| {
+        b_count.cmp(a_count).then_with(|| a_name.cmp(b_name))
+    });
+    distribution
+        .into_iter()
+        .map(|(crate_name, _, percent)| (crate_name, percent))
+        .collect()
+}
+
+/// Scores how similar two short backtraces are, from 0.0 (nothing in common) to 1.0 (the
+/// same sequence of functions).
+///
+/// The score is based on the longest common subsequence of the demangled names (without
+/// the hash) of the two backtraces' symbols: twice its length, divided by the total
+/// number of names. So two backtraces that differ by one frame still score highly,
+/// which is handy for clustering crash reports. Symbols without a name are ignored, and two
+/// backtraces with no names at all are considered identical.
+///
+/// This takes O(n * m) time for backtraces with n and m symbols, so you may want to
+/// limit how long the backtraces you compare can be.
+pub fn short_backtrace_similarity(a: &Backtrace, b: &Backtrace) -> f64 {
+    short_backtrace_similarity_impl(a, b)
+}
+
+pub(crate) fn short_backtrace_similarity_impl<B: Backtraceish>(a: &B, b: &B) -> f64 {
+    let a = short_symbol_names(a);
+    let b = short_symbol_names(b);
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    let common = longest_common_subsequence(&a, &b);
+    (2 * common) as f64 / (a.len() + b.len()) as f64
+}
+
+/// Counts how many of the oldest frames of two short backtraces are the same, for grouping
+/// crashes that came in through the same entry point but failed differently.
+///
+/// Frames are compared by the demangled names of all of their symbols, starting from the
+/// oldest frame of each. A frame with an unnamed symbol (or no symbols at all) never
+/// matches, since there's no telling what it was.
+pub fn common_suffix_len(a: &Backtrace, b: &Backtrace) -> usize {
+    common_suffix_len_impl(a, b)
+}
+
+pub(crate) fn common_suffix_len_impl<B: Backtraceish>(a: &B, b: &B) -> usize {
+    let keys = |backtrace| -> Vec<_> {
+        short_frames_strict_impl(backtrace)
+            .map(|(frame, subframes)| recursion::frame_key(frame, subframes))
+            .collect()
+    };
+    let (a, b) = (keys(a), keys(b));
+    a.iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take_while(|(a, b)| a.is_some() && a == b)
+        .count()
+}
+
+/// Gets the frames of the short backtrace of `new` that aren't in `old`: the newest
+/// frames of `new`, above the frames the two have in common (see [`common_suffix_len`][]).
+///
+/// This is for seeing how the call path deepened between two points where you captured a
+/// backtrace. The frames are yielded like [`short_frames_strict`][] yields them, newest
+/// first. If `new` returned from some of the frames of `old` and called something else,
+/// everything above the frames they share is yielded.
+pub fn new_frames_since<'a>(
+    old: &Backtrace,
+    new: &'a Backtrace,
+) -> impl Iterator<Item = ShortFrame<'a>> + 'a {
+    new_frames_since_impl(old, new).map(ShortFrame::from)
+}
+
+pub(crate) fn new_frames_since_impl<'a, B: Backtraceish>(
+    old: &B,
+    new: &'a B,
+) -> impl Iterator<Item = (&'a B::Frame, Range<usize>)> + 'a {
+    let common = common_suffix_len_impl(old, new);
+    let len = short_frames_strict_impl(new).count();
+    short_frames_strict_impl(new).take(len - common)
+}
+
+fn short_symbol_names<B: Backtraceish>(backtrace: &B) -> Vec<String> {
+    short_frames_strict_impl(backtrace)
+        .flat_map(|(frame, subframes)| frame.symbols()[subframes].iter().filter_map(demangled_name))
+        .collect()
+}
+
+/// The length of the longest common subsequence, keeping just two rows of the table.
+fn longest_common_subsequence(a: &[String], b: &[String]) -> usize {
+    let mut prev = vec![0; b.len() + 1];
+    let mut cur = vec![0; b.len() + 1];
+    for a_name in a {
+        for (j, b_name) in b.iter().enumerate() {
+            cur[j + 1] = if a_name == b_name {
+                prev[j] + 1
+            } else {
+                cur[j].max(prev[j + 1])
+            };
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
@@ -0,0 +1,283 @@
+//! Compact textual summaries of a short backtrace.
+
+use crate::*;
+use std::collections::HashSet;
+
+/// Names longer than this are truncated in [`short_backtrace_oneline`][].
+const ONELINE_MAX_NAME_LEN: usize = 48;
+
+/// Gets a single line that summarizes the call path, like `main → handle → parse`.
+///
+/// This joins the function names of the short backtrace with `sep`, from oldest to newest
+/// (so it reads in the order the calls happened). Only the function's own name is kept
+/// (`app::parse::<T>` is shown as `parse`), very long names are truncated, and
+/// [`GUNK_SYMBOLS`][] are skipped. Symbols without a name are shown as `<unknown>`, so
+/// the line still shows that something was called there, but frames without any symbols
+/// at all are omitted.
+pub fn short_backtrace_oneline(backtrace: &Backtrace, sep: &str) -> String {
+    short_backtrace_oneline_impl(backtrace, sep)
+}
+
+pub(crate) fn short_backtrace_oneline_impl<B: Backtraceish>(backtrace: &B, sep: &str) -> String {
+    let mut names = vec![];
+    for (frame, subframes) in short_frames_strict_impl(backtrace) {
+        for symbol in &frame.symbols()[subframes] {
+            let name = match demangled_name(symbol) {
+                Some(name) => name,
+                None => {
+                    names.push("<unknown>".to_owned());
+                    continue;
+                }
+            };
+            if gunk::is_gunk_name(&name) {
+                continue;
+            }
+            let leaf = names::leaf_name(&name);
+            names.push(names::truncate_name(&leaf, ONELINE_MAX_NAME_LEN));
+        }
+    }
+    names.reverse();
+    names.join(sep)
+}
+
+/// Gets the demangled names (without the hash) of the symbols in the short backtrace,
+/// newest first, for attaching to a structured log as a list.
+///
+/// [`GUNK_SYMBOLS`][] and symbols without a name are left out.
+pub fn short_backtrace_names(backtrace: &Backtrace) -> Vec<String> {
+    short_backtrace_names_impl(backtrace)
+}
+
+pub(crate) fn short_backtrace_names_impl<B: Backtraceish>(backtrace: &B) -> Vec<String> {
+    short_frames_strict_impl(backtrace)
+        .flat_map(|(frame, subframes)| frame.symbols()[subframes].iter().filter_map(demangled_name))
+        .filter(|name| !gunk::is_gunk_name(name))
+        .collect()
+}
+
+/// Gets each distinct name in the short backtrace once, in the order they first show up
+/// (newest first), for a compact list of the functions involved.
+///
+/// This is [`short_backtrace_names`][] with every repeat removed, not just consecutive
+/// ones, so recursion and functions that were called from several places are listed
+/// once. The order of the calls is lost, so use it when that doesn't matter.
+pub fn unique_frames(backtrace: &Backtrace) -> Vec<String> {
+    unique_frames_impl(backtrace)
+}
+
+pub(crate) fn unique_frames_impl<B: Backtraceish>(backtrace: &B) -> Vec<String> {
+    let mut seen = HashSet::new();
+    short_backtrace_names_impl(backtrace)
+        .into_iter()
+        .filter(|name| seen.insert(name.clone()))
+        .collect()
+}
+
+/// Picks a small set of names from the short backtrace that, taken together, tell it apart
+/// from every backtrace in `others`: each of the others is missing at least one of them.
+///
+/// This is for dedup keys in a big crash database that are still readable, unlike
+/// [`short_backtrace_id`][]. The names come from [`unique_frames`][] and are returned in
+/// the same order. A backtrace in `others` that has every name this one does can't be
+/// told apart by names alone, so it's ignored.
+///
+/// Finding the smallest such set is the set cover problem, so this picks the name that
+/// tells apart the most remaining backtraces, over and over (the usual greedy
+/// approximation). That's `O(n * k * m)` for `n` names in this backtrace, `m` backtraces
+/// in `others`, and `k` names in the result, plus resolving the names of all of `others`.
+/// The result is at most `ln(m) + 1` times bigger than the smallest possible one.
+pub fn distinguishing_frames(backtrace: &Backtrace, others: &[Backtrace]) -> Vec<String> {
+    distinguishing_frames_impl(backtrace, others)
+}
+
+pub(crate) fn distinguishing_frames_impl<B: Backtraceish>(
+    backtrace: &B,
+    others: &[B],
+) -> Vec<String> {
+    let names = unique_frames_impl(backtrace);
+    let mut remaining: Vec<HashSet<String>> = others
+        .iter()
+        .map(|other| short_backtrace_names_impl(other).into_iter().collect())
+        .filter(|other: &HashSet<String>| names.iter().any(|name| !other.contains(name)))
+        .collect();
+    let mut picked = vec![false; names.len()];
+    while !remaining.is_empty() {
+        // The earliest name wins ties, so the picks favour the newest frames
+        let mut best = None;
+        for (idx, name) in names.iter().enumerate() {
+            let count = remaining
+                .iter()
+                .filter(|other| !other.contains(name))
+                .count();
+            if best.map_or(true, |(_, best_count)| count > best_count) {
+                best = Some((idx, count));
+            }
+        }
+        // Every remaining backtrace is missing one of the names, so the best one
+        // always tells apart at least one of them
+        let (idx, _) = best.expect("remaining backtraces without any names");
+        picked[idx] = true;
+        remaining.retain(|other| other.contains(&names[idx]));
+    }
+    names
+        .into_iter()
+        .zip(picked)
+        .filter_map(|(name, picked)| if picked { Some(name) } else { None })
+        .collect()
+}
+
+/// How unresolved symbols (without a name) are treated when comparing or hashing short
+/// backtraces by their names, like [`short_backtrace_id_with`][] and [`DedupPrinter`][] do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnknownFramePolicy {
+    /// Leave them out, so only the names that are known matter (the default). A stripped
+    /// build and an unstripped one get the same key as long as they share the names that
+    /// are left, but so do call paths that only differ in unresolved frames.
+    Skip,
+    /// Count each of them (and each frame without any symbols) as a `<unknown>` name, so
+    /// the number and position of unresolved frames has to match too.
+    Placeholder,
+}
+
+impl Default for UnknownFramePolicy {
+    fn default() -> Self {
+        UnknownFramePolicy::Skip
+    }
+}
+
+/// Gets a short ID for the call path of the short backtrace, like `5b1e0c7a`, for logging
+/// inline so the full backtrace can be stored elsewhere and looked up by it.
+///
+/// This is the first 8 hex digits of a hash of [`short_backtrace_names`][], so backtraces
+/// through the same functions get the same ID, even from a different build or a different
+/// process. Like any short hash, unrelated backtraces can occasionally collide.
+///
+/// Unresolved symbols are skipped, see [`short_backtrace_id_with`][] to change that.
+pub fn short_backtrace_id(backtrace: &Backtrace) -> String {
+    short_backtrace_id_impl(backtrace, UnknownFramePolicy::Skip)
+}
+
+/// Like [`short_backtrace_id`][], but with a choice of how unresolved symbols are treated.
+pub fn short_backtrace_id_with(backtrace: &Backtrace, unknown: UnknownFramePolicy) -> String {
+    short_backtrace_id_impl(backtrace, unknown)
+}
+
+pub(crate) fn short_backtrace_id_impl<B: Backtraceish>(
+    backtrace: &B,
+    unknown: UnknownFramePolicy,
+) -> String {
+    format!("{:016x}", fingerprint_impl(backtrace, unknown))[..8].to_owned()
+}
+
+/// The names that identify the call path of the short backtrace, for comparing and hashing.
+///
+/// These are the [`short_backtrace_names`][], with placeholders for unresolved symbols if
+/// `unknown` asks for them.
+pub(crate) fn fingerprint_names_impl<B: Backtraceish>(
+    backtrace: &B,
+    unknown: UnknownFramePolicy,
+) -> Vec<String> {
+    let placeholders = unknown == UnknownFramePolicy::Placeholder;
+    let mut names = vec![];
+    for (frame, subframes) in short_frames_strict_impl(backtrace) {
+        let symbols = &frame.symbols()[subframes];
+        if symbols.is_empty() && placeholders {
+            names.push("<unknown>".to_owned());
+        }
+        for symbol in symbols {
+            match demangled_name(symbol) {
+                Some(name) if gunk::is_gunk_name(&name) => {}
+                Some(name) => names.push(name),
+                None if placeholders => names.push("<unknown>".to_owned()),
+                None => {}
+            }
+        }
+    }
+    names
+}
+
+/// Hashes the [`fingerprint_names_impl`][] with 64-bit FNV-1a, which (unlike std's
+/// hashers) is guaranteed to give the same result everywhere.
+pub(crate) fn fingerprint_impl<B: Backtraceish>(backtrace: &B, unknown: UnknownFramePolicy) -> u64 {
+    const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+    let mut hash = FNV_OFFSET;
+    for name in fingerprint_names_impl(backtrace, unknown) {
+        // Names can't contain a nul, so it keeps `ab`,`c` apart from `a`,`bc`
+        for byte in name.bytes().chain(Some(0)) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+    hash
+}
+
+/// Gets the short backtrace as a line in the "folded stacks" format used by FlameGraph
+/// and `inferno`, like `app::main;app::handle;app::parse 1`.
+///
+/// The demangled names (without the hash) are joined with `;` from oldest to newest, with
+/// a count of 1 at the end, so the lines of many backtraces can just be concatenated and
+/// fed to those tools. Any `;` in a name (like in `<[u8; 4] as Trait>::method`) is replaced
+/// with `,` so it can't split the name, and symbols without a name or frames without any
+/// symbols are shown as `[unknown]` like `perf` does.
+pub fn short_backtrace_folded(backtrace: &Backtrace) -> String {
+    short_backtrace_folded_impl(backtrace)
+}
+
+pub(crate) fn short_backtrace_folded_impl<B: Backtraceish>(backtrace: &B) -> String {
+    let mut names = vec![];
+    for (frame, subframes) in short_frames_strict_impl(backtrace) {
+        let symbols = &frame.symbols()[subframes];
+        if symbols.is_empty() {
+            names.push("[unknown]".to_owned());
+        }
+        for symbol in symbols {
+            names.push(match demangled_name(symbol) {
+                Some(name) => name.replace(';', ","),
+                None => "[unknown]".to_owned(),
+            });
+        }
+    }
+    names.reverse();
+    format!("{} 1", names.join(";"))
+}
+
+/// Gets a compact version of the short backtrace, meant as a replacement for the (huge)
+/// output of `format!("{:?}", backtrace)` in size-sensitive logs.
+///
+/// Every symbol gets one line of `index: name at file:line`, with the index of its frame in
+/// the short backtrace, and without addresses or the hashes of names. For example:
+///
+/// ```text
+/// 0: app::parse at src/parse.rs:10
+/// 0: app::handle at src/lib.rs:20
+/// 1: app::main at src/main.rs:5
+/// ```
+///
+/// Unlike the formatter's output this doesn't start with a newline.
+pub fn short_debug(backtrace: &Backtrace) -> String {
+    short_debug_impl(backtrace)
+}
+
+pub(crate) fn short_debug_impl<B: Backtraceish>(backtrace: &B) -> String {
+    use std::fmt::Write;
+
+    let mut out = String::new();
+    for (idx, (frame, subframes)) in short_frames_strict_impl(backtrace).enumerate() {
+        let symbols = &frame.symbols()[subframes];
+        if symbols.is_empty() {
+            let _ = writeln!(out, "{}: <unresolved>", idx);
+        }
+        for symbol in symbols {
+            let name = demangled_name(symbol);
+            let _ = write!(out, "{}: {}", idx, name.as_deref().unwrap_or("<unknown>"));
+            if let (Some(file), Some(line)) = (symbol.filename(), symbol.lineno()) {
+                let _ = write!(out, " at {}:{}", file.display(), line);
+            }
+            out.push('\n');
+        }
+    }
+    // Drop the final newline
+    out.pop();
+    out
+}
@@ -1,4 +1,5 @@
 use super::*;
+use std::collections::HashMap;
 
 type BT = &'static [&'static [&'static str]];
 
@@ -14,12 +15,162 @@ impl Frameish for &'static [&'static str] {
     fn symbols(&self) -> &[Self::Symbol] {
         self
     }
+    fn ip(&self) -> *mut c_void {
+        std::ptr::null_mut()
+    }
+    fn symbol_address(&self) -> *mut c_void {
+        std::ptr::null_mut()
+    }
+    fn module_base_address(&self) -> Option<*mut c_void> {
+        None
+    }
 }
 
 impl Symbolish for &'static str {
     fn name_str(&self) -> Option<&str> {
         Some(self)
     }
+    fn name_bytes(&self) -> Option<&[u8]> {
+        Some(self.as_bytes())
+    }
+    fn has_name(&self) -> bool {
+        true
+    }
+    fn fmt_name(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self)
+    }
+    // Our fake names are already demangled, so pretend anything that isn't
+    // a path is from C
+    fn is_rust(&self) -> bool {
+        self.contains("::")
+    }
+    fn filename(&self) -> Option<&Path> {
+        None
+    }
+    fn lineno(&self) -> Option<u32> {
+        None
+    }
+    fn colno(&self) -> Option<u32> {
+        None
+    }
+}
+
+/// A fake frame for tests that care about more than symbol names
+struct FakeFrame {
+    ip: usize,
+    symbol_address: usize,
+    module_base: Option<usize>,
+    symbols: Vec<FakeSymbol>,
+}
+
+struct FakeSymbol {
+    name: Option<&'static str>,
+    file: Option<&'static str>,
+    line: Option<u32>,
+    col: Option<u32>,
+}
+
+type FakeBT = Vec<FakeFrame>;
+
+impl Backtraceish for FakeBT {
+    type Frame = FakeFrame;
+    fn frames(&self) -> &[Self::Frame] {
+        self
+    }
+}
+
+impl Frameish for FakeFrame {
+    type Symbol = FakeSymbol;
+    fn symbols(&self) -> &[Self::Symbol] {
+        &self.symbols
+    }
+    fn ip(&self) -> *mut c_void {
+        self.ip as *mut c_void
+    }
+    fn symbol_address(&self) -> *mut c_void {
+        self.symbol_address as *mut c_void
+    }
+    fn module_base_address(&self) -> Option<*mut c_void> {
+        self.module_base.map(|base| base as *mut c_void)
+    }
+}
+
+impl Symbolish for FakeSymbol {
+    fn name_str(&self) -> Option<&str> {
+        self.name
+    }
+    fn name_bytes(&self) -> Option<&[u8]> {
+        self.name.map(str::as_bytes)
+    }
+    fn has_name(&self) -> bool {
+        self.name.is_some()
+    }
+    fn fmt_name(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name.unwrap_or(""))
+    }
+    fn is_rust(&self) -> bool {
+        self.name.map_or(false, |name| name.contains("::"))
+    }
+    fn filename(&self) -> Option<&Path> {
+        self.file.map(Path::new)
+    }
+    fn lineno(&self) -> Option<u32> {
+        self.line
+    }
+    fn colno(&self) -> Option<u32> {
+        self.col
+    }
+}
+
+fn sym(name: &'static str, file: &'static str, line: u32) -> FakeSymbol {
+    FakeSymbol {
+        name: Some(name),
+        file: Some(file),
+        line: Some(line),
+        col: None,
+    }
+}
+
+fn unknown_sym() -> FakeSymbol {
+    FakeSymbol {
+        name: None,
+        file: None,
+        line: None,
+        col: None,
+    }
+}
+
+fn frame(ip: usize, symbols: Vec<FakeSymbol>) -> FakeFrame {
+    FakeFrame {
+        ip,
+        symbol_address: ip,
+        module_base: None,
+        symbols,
+    }
+}
+
+/// A small trace with a bit of everything, including gunk on both sides
+fn fake_trace() -> FakeBT {
+    vec![
+        frame(
+            0x10,
+            vec![sym("std::rust_end_short_backtrace", "std.rs", 1)],
+        ),
+        frame(
+            0x20,
+            vec![
+                sym("app::inner", "src/inner.rs", 10),
+                sym("app::outer", "src/lib.rs", 20),
+            ],
+        ),
+        frame(0x30, vec![unknown_sym()]),
+        frame(0x40, vec![]),
+        frame(0x50, vec![sym("app::main", "src/main.rs", 5)]),
+        frame(
+            0x60,
+            vec![sym("std::rust_begin_short_backtrace", "std.rs", 2)],
+        ),
+    ]
 }
 
 fn process(bt: BT) -> Vec<&'static str> {
@@ -428,6 +579,160 @@ fn test_one_super_frame_0() {
     assert_eq!(process(bt), expected);
 }
 
+#[test]
+fn test_straddled_0() {
+    let bt: BT = &[
+        &["hello", "rust_end_short_backtrace"],
+        &["rust_begin_short_backtrace", "case"],
+    ];
+    let expected: Vec<&str> = vec![];
+    assert_eq!(process(bt), expected);
+}
+
+#[test]
+fn test_straddled_1() {
+    let bt: BT = &[
+        &["hello", "rust_end_short_backtrace", "real"],
+        &["rust_begin_short_backtrace", "case"],
+    ];
+    assert_eq!(process(bt), vec!["real"]);
+    let bt: BT = &[
+        &["hello", "rust_end_short_backtrace"],
+        &["real", "rust_begin_short_backtrace", "case"],
+    ];
+    assert_eq!(process(bt), vec!["real"]);
+}
+
+#[test]
+fn test_straddled_2() {
+    let bt: BT = &[
+        &["hello", "rust_end_short_backtrace", "real"],
+        &["frames", "rust_begin_short_backtrace", "case"],
+    ];
+    assert_eq!(process(bt), vec!["real", "frames"]);
+    let bt: BT = &[
+        &["hello", "rust_end_short_backtrace"],
+        &["real", "frames"],
+        &["rust_begin_short_backtrace", "case"],
+    ];
+    assert_eq!(process(bt), vec!["real", "frames"]);
+}
+
+#[test]
+fn test_unresolved_between_markers() {
+    // A frame without symbols has nothing to slice, so it's the one empty range we yield
+    let bt: BT = &[
+        &["rust_end_short_backtrace"],
+        &[],
+        &["rust_begin_short_backtrace"],
+    ];
+    let ranges: Vec<_> = short_frames_strict_impl(&bt)
+        .map(|(_, range)| range)
+        .collect();
+    assert_eq!(ranges, vec![0..0]);
+}
+
+#[test]
+fn test_find_markers_table() {
+    const E: &str = "std::rust_end_short_backtrace";
+    const B: &str = "std::rust_begin_short_backtrace";
+    type Bound = Option<MarkerPosition>;
+    let cases: &[(BT, Bound, Bound)] = &[
+        // Missing markers
+        (&[&["a"], &["b"]], None, None),
+        (&[], None, None),
+        (&[&[E], &["a"]], Some((0, 0)), None),
+        (&[&["a"], &[B]], None, Some((1, 0))),
+        // Wrong order
+        (&[&[B], &["a"], &[E]], None, None),
+        (&[&["a", B, E]], None, None),
+        // Both in one symbol
+        (
+            &[&["std::rust_end_short_backtrace::rust_begin_short_backtrace"]],
+            None,
+            None,
+        ),
+        // In the same frame
+        (&[&[E, B]], Some((0, 0)), Some((0, 1))),
+        (&[&["a", E, "b", B, "c"]], Some((0, 1)), Some((0, 3))),
+        // As the first and last subframes
+        (&[&[E, "a"], &["b", B]], Some((0, 0)), Some((1, 1))),
+        (&[&["a", E], &[B, "b"]], Some((0, 1)), Some((1, 0))),
+        // Several of each, the closest ones win
+        (
+            &[&[E], &[E], &["a"], &[B], &[B]],
+            Some((1, 0)),
+            Some((3, 0)),
+        ),
+    ];
+    for (bt, start, end) in cases {
+        assert_eq!(find_markers(bt), (*start, *end), "{:?}", bt);
+    }
+}
+
+#[test]
+fn test_short_frames_strict_slice() {
+    let backtrace = Backtrace::new();
+    let from_backtrace: Vec<_> = short_frames_strict(&backtrace)
+        .map(|short| (short.ip(), short.sub_frames))
+        .collect();
+    let from_slice: Vec<_> = short_frames_strict_slice(backtrace.frames())
+        .map(|short| (short.ip(), short.sub_frames))
+        .collect();
+    assert!(!from_slice.is_empty());
+    assert_eq!(from_slice, from_backtrace);
+
+    // Without the end marker's frames it's clamped on one side only
+    let (_, frame_range, _, _) = short_slice(&backtrace);
+    let rest = &backtrace.frames()[frame_range.start..];
+    let clamped: Vec<_> = short_frames_strict_slice(rest)
+        .map(|short| (short.ip(), short.sub_frames))
+        .collect();
+    assert_eq!(clamped, from_backtrace);
+    assert_eq!(short_frames_strict_slice(&[]).len(), 0);
+}
+
+#[test]
+fn test_short_frame() {
+    let backtrace = Backtrace::new();
+    for short in short_frames_strict(&backtrace) {
+        let (frame, sub_frames): (&BacktraceFrame, Range<usize>) = short.clone().into();
+        assert_eq!(short.ip(), frame.ip());
+        assert_eq!(short.symbols().len(), sub_frames.len());
+        let back = ShortFrame::from((frame, sub_frames.clone()));
+        assert_eq!(back.ip(), frame.ip());
+        assert_eq!(back.sub_frames, sub_frames);
+    }
+}
+
+#[test]
+fn test_reversed() {
+    let trace = fake_trace();
+    let forward: Vec<_> = short_frames_strict_impl(&trace)
+        .map(|(frame, range)| (frame.ip, range))
+        .collect();
+    let mut reverse: Vec<_> = short_frames_strict_impl(&trace)
+        .rev()
+        .map(|(frame, range)| (frame.ip, range))
+        .collect();
+    reverse.reverse();
+    assert_eq!(forward, reverse);
+    assert_eq!(forward.len(), 4);
+    assert_eq!(short_frames_strict_impl(&trace).len(), 4);
+
+    let bt: BT = &[
+        &["junk", "__rust_end_short_backtrace", "real"],
+        &["frames"],
+        &["here", "__rust_begin_short_backtrace", "junk"],
+    ];
+    let reverse: Vec<_> = short_frames_strict_impl(&bt)
+        .rev()
+        .flat_map(|(frame, range)| frame[range].to_vec())
+        .collect();
+    assert_eq!(reverse, vec!["here", "frames", "real"]);
+    assert_eq!(short_frames_strict_impl(&bt).len(), 3);
+}
+
 #[test]
 fn test_complex1() {
     let bt: BT = &[
@@ -522,3 +827,4204 @@ fn test_complex7() {
     let expected = vec!["real", "frames", "here"];
     assert_eq!(process(bt), expected);
 }
+
+const FAKE_TRACE_FORMATTED: &str = "
+   0:       0x20 - app::inner
+                at src/inner.rs:10
+                 - app::outer
+                at src/lib.rs:20
+   1:       0x30 - <unknown>
+   2:       0x40 - <unresolved>
+   3:       0x50 - app::main
+                at src/main.rs:5";
+
+#[test]
+fn test_format_default() {
+    let out = ShortBacktraceFormatter::new().format_impl(&fake_trace());
+    assert_eq!(out, FAKE_TRACE_FORMATTED);
+}
+
+#[test]
+fn test_format_show_addresses_and_file_lines() {
+    let out = ShortBacktraceFormatter::new()
+        .show_addresses(false)
+        .format_impl(&fake_trace());
+    assert_eq!(
+        out,
+        "
+   0:            - app::inner
+                at src/inner.rs:10
+                 - app::outer
+                at src/lib.rs:20
+   1:            - <unknown>
+   2:            - <unresolved>
+   3:            - app::main
+                at src/main.rs:5"
+    );
+
+    let formatter = ShortBacktraceFormatter::new().show_file_lines(false);
+    assert_eq!(
+        formatter.format_impl(&fake_trace()),
+        "
+   0:       0x20 - app::inner
+                 - app::outer
+   1:       0x30 - <unknown>
+   2:       0x40 - <unresolved>
+   3:       0x50 - app::main"
+    );
+    let out = formatter
+        .clone()
+        .style(FormatStyle::SingleLine)
+        .format_impl(&fake_trace());
+    assert!(!out.contains("src/"));
+    let out = formatter
+        .style(FormatStyle::GdbLike)
+        .format_impl(&fake_trace());
+    assert!(out.contains("#0  app::inner ()"));
+    assert!(!out.contains("src/"));
+}
+
+#[test]
+fn test_format_to_slice_fits() {
+    let mut buf = [0u8; 1024];
+    let result = ShortBacktraceFormatter::new().format_to_slice_impl(&mut buf, &fake_trace());
+    assert!(!result.truncated);
+    assert_eq!(result.written, FAKE_TRACE_FORMATTED.len());
+    assert_eq!(&buf[..result.written], FAKE_TRACE_FORMATTED.as_bytes());
+}
+
+#[test]
+fn test_append_bytes() {
+    let mut buf = b"error: oops".to_vec();
+    ShortBacktraceFormatter::new().append_bytes_impl(&mut buf, &fake_trace());
+    assert_eq!(
+        buf,
+        format!("error: oops{}", FAKE_TRACE_FORMATTED).into_bytes()
+    );
+}
+
+#[test]
+fn test_format_to_slice_truncated() {
+    let mut buf = [0u8; 40];
+    let result = ShortBacktraceFormatter::new().format_to_slice_impl(&mut buf, &fake_trace());
+    assert!(result.truncated);
+    assert_eq!(result.written, 40);
+    assert_eq!(&buf[..], &FAKE_TRACE_FORMATTED.as_bytes()[..40]);
+}
+
+#[test]
+fn test_format_to_slice_char_boundary() {
+    let bt: FakeBT = vec![frame(0x10, vec![sym("app::héllo", "src/lib.rs", 1)])];
+    let full = ShortBacktraceFormatter::new().format_impl(&bt);
+    let cut = full.find('é').unwrap() + 1;
+    let mut buf = vec![0u8; cut];
+    let result = ShortBacktraceFormatter::new().format_to_slice_impl(&mut buf, &bt);
+    assert!(result.truncated);
+    assert_eq!(result.written, cut - 1);
+    assert!(std::str::from_utf8(&buf[..result.written]).is_ok());
+}
+
+#[test]
+fn test_format_to_slice_empty_buf() {
+    let mut buf = [0u8; 0];
+    let result = ShortBacktraceFormatter::new().format_to_slice_impl(&mut buf, &fake_trace());
+    assert!(result.truncated);
+    assert_eq!(result.written, 0);
+}
+
+#[test]
+fn test_find_frame_by_name() {
+    let bt = fake_trace();
+    let (frame, idx) = query::find_frame_by_name_impl(&bt, "outer").unwrap();
+    assert_eq!(frame.ip, 0x20);
+    assert_eq!(idx, 1);
+
+    let (frame, idx) = query::find_frame_by_name_impl(&bt, "app::").unwrap();
+    assert_eq!(frame.ip, 0x20);
+    assert_eq!(idx, 0);
+
+    let (frame, idx) = query::find_frame_by_name_impl(&bt, "main").unwrap();
+    assert_eq!(frame.ip, 0x50);
+    assert_eq!(idx, 0);
+}
+
+#[test]
+fn test_find_frame_by_name_outside_short_range() {
+    let bt = fake_trace();
+    assert!(query::find_frame_by_name_impl(&bt, "short_backtrace").is_none());
+    assert!(query::find_frame_by_name_impl(&bt, "nope").is_none());
+}
+
+#[test]
+#[inline(never)]
+fn test_find_frame_by_name_real() {
+    let bt = Backtrace::new();
+    assert!(find_frame_by_name(&bt, "test_find_frame_by_name_real").is_some());
+    assert!(find_frame_by_name(&bt, "rust_begin_short_backtrace").is_none());
+}
+
+#[test]
+fn test_path_segments() {
+    assert_eq!(names::path_segments("a::b::c"), vec!["a", "b", "c"]);
+    assert_eq!(
+        names::path_segments("<a::B as c::D>::e"),
+        vec!["<a::B as c::D>", "e"]
+    );
+    assert_eq!(
+        names::path_segments("a::b::<c::D>"),
+        vec!["a", "b", "<c::D>"]
+    );
+    assert_eq!(names::path_segments("main"), vec!["main"]);
+}
+
+#[test]
+fn test_leaf_name() {
+    assert_eq!(names::leaf_name("app::parse"), "parse");
+    assert_eq!(names::leaf_name("app::parse::<u32>"), "parse");
+    assert_eq!(names::leaf_name("<app::Foo as core::Bar>::baz"), "baz");
+    assert_eq!(
+        names::leaf_name("app::run::{{closure}}"),
+        "run::{{closure}}"
+    );
+    assert_eq!(names::leaf_name("main"), "main");
+}
+
+#[test]
+fn test_truncate_name() {
+    assert_eq!(names::truncate_name("short", 10), "short");
+    assert_eq!(names::truncate_name("exactly10!", 10), "exactly10!");
+    assert_eq!(names::truncate_name("a_very_long_name", 10), "a_very_lo…");
+    assert_eq!(names::truncate_name("ééééééé", 4), "ééé…");
+    assert_eq!(names::truncate_name("a", 1), "a");
+    assert_eq!(names::truncate_name("ab", 1), "…");
+    assert_eq!(names::truncate_name("ab", 0), "");
+}
+
+#[test]
+fn test_oneline() {
+    let bt = fake_trace();
+    assert_eq!(
+        summary::short_backtrace_oneline_impl(&bt, " → "),
+        "main → <unknown> → outer → inner"
+    );
+}
+
+#[test]
+fn test_oneline_unnamed() {
+    let bt: FakeBT = vec![
+        frame(0x10, vec![sym("app::parse", "src/parse.rs", 1)]),
+        frame(
+            0x20,
+            vec![unknown_sym(), sym("app::handle", "src/lib.rs", 2)],
+        ),
+        frame(0x30, vec![]),
+        frame(0x40, vec![unknown_sym()]),
+        frame(0x50, vec![sym("app::main", "src/main.rs", 3)]),
+    ];
+    assert_eq!(
+        summary::short_backtrace_oneline_impl(&bt, " → "),
+        "main → <unknown> → handle → <unknown> → parse"
+    );
+}
+
+#[test]
+fn test_oneline_skips_gunk() {
+    let bt: BT = &[
+        &["rust_begin_unwind"],
+        &["core::panicking::panic_fmt"],
+        &["app::parse"],
+        &["core::ops::function::FnOnce::call_once"],
+        &["app::main"],
+    ];
+    assert_eq!(
+        summary::short_backtrace_oneline_impl(&bt, ";"),
+        "main;parse"
+    );
+}
+
+#[test]
+fn test_format_columns() {
+    let bt: FakeBT = vec![frame(
+        0x10,
+        vec![
+            FakeSymbol {
+                col: Some(7),
+                ..sym("app::inner", "src/inner.rs", 10)
+            },
+            sym("app::outer", "src/lib.rs", 20),
+        ],
+    )];
+    let with_columns = ShortBacktraceFormatter::new().show_columns(true);
+    assert_eq!(
+        with_columns.format_impl(&bt),
+        "
+   0:       0x10 - app::inner
+                at src/inner.rs:10:7
+                 - app::outer
+                at src/lib.rs:20"
+    );
+    // Columns are ignored by default
+    assert_eq!(
+        ShortBacktraceFormatter::new().format_impl(&bt),
+        "
+   0:       0x10 - app::inner
+                at src/inner.rs:10
+                 - app::outer
+                at src/lib.rs:20"
+    );
+}
+
+#[test]
+fn test_format_custom_renderer() {
+    let bt = fake_trace();
+    let formatter = ShortBacktraceFormatter::new();
+    let mut out = String::new();
+    formatter
+        .write_frames_impl(&mut out, &bt, &|out, index, symbol, _| {
+            write!(out, " [{}] {}", index, symbol.name.unwrap_or("?"))
+        })
+        .unwrap();
+    assert_eq!(
+        out,
+        "
+   0:       0x20 [0] app::inner [1] app::outer
+   1:       0x30 [0] ?
+   2:       0x40 - <unresolved>
+   3:       0x50 [0] app::main"
+    );
+}
+
+#[test]
+fn test_without_test_harness() {
+    let bt: BT = &[
+        &["rust_end_short_backtrace"],
+        &["app::tests::my_test"],
+        &["core::ops::function::FnOnce::call_once"],
+        &[
+            "test::types::RunnableTest::run",
+            "test::run_test_in_process",
+        ],
+        &["test::run_test::{{closure}}"],
+        &["rust_begin_short_backtrace"],
+    ];
+    let result: Vec<&str> = gunk::short_frames_without_test_harness_impl(&bt)
+        .flat_map(|(frame, range)| frame[range].iter().copied())
+        .collect();
+    assert_eq!(
+        result,
+        vec![
+            "app::tests::my_test",
+            "core::ops::function::FnOnce::call_once"
+        ]
+    );
+}
+
+#[test]
+fn test_without_test_harness_mid_frame() {
+    let bt: BT = &[
+        &["app::tests::my_test", "test::run_test_in_process"],
+        &["test::run_test"],
+    ];
+    let result: Vec<_> = gunk::short_frames_without_test_harness_impl(&bt).collect();
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].1, 0..1);
+}
+
+#[test]
+fn test_without_test_harness_only_prefix() {
+    // The user's own `test` module shouldn't be confused with libtest
+    let bt: BT = &[&["app::test::run_test_thing"], &["main"]];
+    assert_eq!(gunk::short_frames_without_test_harness_impl(&bt).count(), 2);
+}
+
+#[test]
+fn test_format_threads() {
+    let main: BT = &[&["app::main"]];
+    let worker: BT = &[&["app::work"], &["app::spawn"]];
+    let out = ShortBacktraceFormatter::new()
+        .format_threads_impl(&[("thread 'main'", main), ("thread 'worker'", worker)]);
+    assert_eq!(
+        out,
+        "thread 'main':
+   0:        0x0 - app::main
+
+thread 'worker':
+   0:        0x0 - app::work
+   1:        0x0 - app::spawn"
+    );
+}
+
+#[test]
+fn test_short_symbols() {
+    let bt = fake_trace();
+    let result: Vec<(usize, usize, Option<&str>)> = resolved::short_symbols_impl(&bt)
+        .map(|(idx, frame, symbol)| (idx, frame.ip, symbol.name))
+        .collect();
+    assert_eq!(
+        result,
+        vec![
+            (0, 0x20, Some("app::inner")),
+            (0, 0x20, Some("app::outer")),
+            (1, 0x30, None),
+            (3, 0x50, Some("app::main")),
+        ]
+    );
+}
+
+#[test]
+fn test_resolved_short_symbols() {
+    fn assert_send_static<T: Send + 'static>(_: &T) {}
+
+    let bt = fake_trace();
+    let result: Vec<ResolvedFrame> = resolved::resolved_short_symbols_impl(&bt).collect();
+    drop(bt);
+    assert_send_static(&result);
+
+    let expected = vec![
+        ResolvedFrame {
+            index: 0,
+            ip: 0x20,
+            name: Some("app::inner".to_owned()),
+            file: Some("src/inner.rs".into()),
+            line: Some(10),
+        },
+        ResolvedFrame {
+            index: 0,
+            ip: 0x20,
+            name: Some("app::outer".to_owned()),
+            file: Some("src/lib.rs".into()),
+            line: Some(20),
+        },
+        ResolvedFrame {
+            index: 1,
+            ip: 0x30,
+            name: None,
+            file: None,
+            line: None,
+        },
+        ResolvedFrame {
+            index: 2,
+            ip: 0x40,
+            name: None,
+            file: None,
+            line: None,
+        },
+        ResolvedFrame {
+            index: 3,
+            ip: 0x50,
+            name: Some("app::main".to_owned()),
+            file: Some("src/main.rs".into()),
+            line: Some(5),
+        },
+    ];
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_format_inline_policy() {
+    let bt: BT = &[&["inner", "middle", "outer"], &["main"]];
+    let format = |policy| {
+        ShortBacktraceFormatter::new()
+            .inline_policy(policy)
+            .format_impl(&bt)
+    };
+    assert_eq!(
+        format(InlinePolicy::All),
+        "
+   0:        0x0 - inner
+                 - middle
+                 - outer
+   1:        0x0 - main"
+    );
+    assert_eq!(
+        format(InlinePolicy::InnermostOnly),
+        "
+   0:        0x0 - inner
+   1:        0x0 - main"
+    );
+    assert_eq!(
+        format(InlinePolicy::OutermostOnly),
+        "
+   0:        0x0 - outer
+   1:        0x0 - main"
+    );
+}
+
+#[test]
+fn test_inline_policy_respects_clamp() {
+    assert_eq!(InlinePolicy::InnermostOnly.apply(2..5), 2..3);
+    assert_eq!(InlinePolicy::OutermostOnly.apply(2..5), 4..5);
+    assert_eq!(InlinePolicy::OutermostOnly.apply(0..0), 0..0);
+}
+
+#[test]
+fn test_all_marker_positions() {
+    let bt: BT = &[
+        &["hello"],
+        &["__rust_end_short_backtrace"],
+        &["junk", "core::rust_end_short_backtrace"],
+        &["real"],
+        &["rust_begin_short_backtrace"],
+        &["__rust_end_short_backtrace_rust_begin_short_backtrace"],
+    ];
+    assert_eq!(
+        diagnostics::all_marker_positions_impl(&bt),
+        vec![
+            (1, 0, MarkerKind::End),
+            (2, 1, MarkerKind::End),
+            (4, 0, MarkerKind::Begin),
+            (5, 0, MarkerKind::End),
+            (5, 0, MarkerKind::Begin),
+        ]
+    );
+    let bt: BT = &[&["hello"]];
+    assert_eq!(diagnostics::all_marker_positions_impl(&bt), vec![]);
+}
+
+#[test]
+fn test_format_frame_annotations() {
+    let bt = fake_trace();
+    let mut annotations = HashMap::new();
+    // Index 0 is outside of the short backtrace, so it's never printed
+    annotations.insert(0, "hidden".to_owned());
+    annotations.insert(1, "12.5ms".to_owned());
+    annotations.insert(3, "0.1ms".to_owned());
+    let out = ShortBacktraceFormatter::new()
+        .frame_annotations(annotations)
+        .format_impl(&bt);
+    assert_eq!(
+        out,
+        "
+   0:       0x20 - app::inner
+                at src/inner.rs:10
+                 - app::outer
+                at src/lib.rs:20
+                [12.5ms]
+   1:       0x30 - <unknown>
+   2:       0x40 - <unresolved>
+                [0.1ms]
+   3:       0x50 - app::main
+                at src/main.rs:5"
+    );
+}
+
+#[test]
+fn test_short_frames_indexed() {
+    let bt = fake_trace();
+    let indices: Vec<usize> = short_frames_indexed_impl(&bt)
+        .map(|(idx, _, _)| idx)
+        .collect();
+    assert_eq!(indices, vec![1, 2, 3, 4]);
+}
+
+#[test]
+fn test_format_into_reuses_buffer() {
+    let formatter = ShortBacktraceFormatter::new();
+    let mut buf = String::with_capacity(1024);
+    buf.push_str("old contents");
+    formatter.format_into_impl(&mut buf, &fake_trace());
+    assert_eq!(buf, FAKE_TRACE_FORMATTED);
+    assert!(buf.capacity() >= 1024);
+
+    let bt: BT = &[&["app::main"]];
+    formatter.format_into_impl(&mut buf, &bt);
+    assert_eq!(buf, "\n   0:        0x0 - app::main");
+}
+
+#[test]
+fn test_crate_name() {
+    assert_eq!(names::crate_name("app::parse"), Some("app"));
+    assert_eq!(names::crate_name("app::parse::{{closure}}"), Some("app"));
+    assert_eq!(names::crate_name("main"), Some("main"));
+    assert_eq!(names::crate_name("alloc::vec::Vec<T>::push"), Some("alloc"));
+    assert_eq!(
+        names::crate_name("<app::Foo as core::fmt::Debug>::fmt"),
+        Some("app")
+    );
+    assert_eq!(
+        names::crate_name("<&mut app::Foo as core::Bar>::baz"),
+        Some("app")
+    );
+    assert_eq!(names::crate_name("<dyn app::Trait>::method"), Some("app"));
+    assert_eq!(names::crate_name("<app::Foo>::new"), Some("app"));
+    assert_eq!(names::crate_name("<(A, B) as core::Bar>::baz"), None);
+    assert_eq!(names::crate_name("<[T] as core::Bar>::baz"), None);
+    assert_eq!(names::crate_name(""), None);
+}
+
+#[test]
+fn test_matches_path_prefix() {
+    assert!(names::matches_path_prefix("app::parse", "app"));
+    assert!(names::matches_path_prefix("app::parse", "app::"));
+    assert!(names::matches_path_prefix(
+        "app::parse::inner",
+        "app::parse"
+    ));
+    assert!(names::matches_path_prefix("app", "app"));
+    assert!(names::matches_path_prefix(
+        "<app::Foo as core::Bar>::baz",
+        "app"
+    ));
+    assert!(!names::matches_path_prefix("apple::parse", "app"));
+    assert!(!names::matches_path_prefix("app::parser", "app::parse"));
+    assert!(!names::matches_path_prefix(
+        "<core::Foo as app::Bar>::baz",
+        "app"
+    ));
+    assert!(!names::matches_path_prefix("app::parse", ""));
+}
+
+#[test]
+fn test_classify() {
+    assert_eq!(classify::classify_name("app::main", "app"), FrameKind::User);
+    assert_eq!(
+        classify::classify_name("std::rt::lang_start", "app"),
+        FrameKind::Std
+    );
+    assert_eq!(
+        classify::classify_name("core::panicking::panic_fmt", "app"),
+        FrameKind::Std
+    );
+    assert_eq!(
+        classify::classify_name("tokio::runtime::run", "app"),
+        FrameKind::Dependency
+    );
+    assert_eq!(
+        classify::classify_name("<(A, B) as Foo>::bar", "app"),
+        FrameKind::Unknown
+    );
+
+    let sym: &[&str] = &["malloc"];
+    assert_eq!(
+        classify::classify_symbol_impl(&sym[0], "app"),
+        FrameKind::Foreign
+    );
+
+    let frame: &[&str] = &["core::option::Option<T>::unwrap", "app::main", "tokio::run"];
+    assert_eq!(
+        classify::classify_frame_impl(&frame, 0..3, "app"),
+        FrameKind::User
+    );
+    assert_eq!(
+        classify::classify_frame_impl(&frame, 0..1, "app"),
+        FrameKind::Std
+    );
+    assert_eq!(
+        classify::classify_frame_impl(&frame, 2..3, "app"),
+        FrameKind::Dependency
+    );
+    assert_eq!(
+        classify::classify_frame_impl(&frame, 0..0, "app"),
+        FrameKind::Unknown
+    );
+}
+
+#[test]
+fn test_deepest_user_frame() {
+    let bt: BT = &[
+        &["core::panicking::panic_fmt"],
+        &["app::parse", "serde::de::deserialize"],
+        &["app::handle"],
+        &["tokio::runtime::run", "app::main::{{closure}}"],
+        &["tokio::runtime::block_on"],
+    ];
+    let (frame, idx) = query::deepest_user_frame_impl(&bt, "app").unwrap();
+    assert_eq!(frame[idx], "app::main::{{closure}}");
+    assert!(query::deepest_user_frame_impl(&bt, "nope").is_none());
+}
+
+#[test]
+fn test_format_gdb_like() {
+    let out = ShortBacktraceFormatter::new()
+        .style(FormatStyle::GdbLike)
+        .format_impl(&fake_trace());
+    assert_eq!(
+        out,
+        "
+#0  app::inner at src/inner.rs:10
+#1  app::outer at src/lib.rs:20
+#2  ?? ()
+#3  ?? ()
+#4  app::main at src/main.rs:5"
+    );
+}
+
+#[test]
+fn test_distinct_source_files() {
+    assert_eq!(stats::distinct_source_files_impl(&fake_trace()), 3);
+
+    let bt: FakeBT = vec![
+        frame(
+            0x10,
+            vec![
+                sym("app::a", "src/lib.rs", 1),
+                sym("app::b", "src/lib.rs", 2),
+            ],
+        ),
+        frame(0x20, vec![sym("app::c", "src/lib.rs", 3), unknown_sym()]),
+    ];
+    assert_eq!(stats::distinct_source_files_impl(&bt), 1);
+
+    let bt: BT = &[&["no"], &["files"]];
+    assert_eq!(stats::distinct_source_files_impl(&bt), 0);
+}
+
+#[test]
+fn test_short_frames_sorted_by() {
+    let bt: BT = &[
+        &["tokio::poll"],
+        &["app::parse"],
+        &["serde::de"],
+        &["app::main"],
+    ];
+    // User frames first, otherwise keep the original order
+    let result: Vec<&str> = adapters::short_frames_sorted_by_impl(&bt, |a, b| {
+        let a_user = a.0[0].starts_with("app::");
+        let b_user = b.0[0].starts_with("app::");
+        b_user.cmp(&a_user)
+    })
+    .map(|(frame, range)| frame[range][0])
+    .collect();
+    assert_eq!(
+        result,
+        vec!["app::parse", "app::main", "tokio::poll", "serde::de"]
+    );
+}
+
+#[test]
+fn test_short_slice() {
+    let bt: BT = &[
+        &["junk"],
+        &["junk", "__rust_end_short_backtrace", "real"],
+        &["frames"],
+        &["here", "__rust_begin_short_backtrace"],
+        &["junk"],
+    ];
+    let (frames, range, first, last) = short_slice_impl(&bt);
+    assert_eq!(frames, &bt[1..4]);
+    assert_eq!(range, 1..4);
+    assert_eq!(first, 2);
+    assert_eq!(last, 1);
+}
+
+#[test]
+fn test_short_slice_empty() {
+    let bt: BT = &[
+        &["hello"],
+        &["__rust_end_short_backtrace"],
+        &["rust_begin_short_backtrace"],
+        &["case"],
+    ];
+    let (frames, range, _, _) = short_slice_impl(&bt);
+    assert!(frames.is_empty());
+    assert!(range.is_empty());
+
+    let bt: BT = &[];
+    let (frames, range, _, _) = short_slice_impl(&bt);
+    assert!(frames.is_empty());
+    assert_eq!(range, 0..0);
+}
+
+#[test]
+fn test_format_dependency_boundary() {
+    let bt: BT = &[
+        &["serde::de::error"],
+        &["app::parse"],
+        &["std::io::read"],
+        &["app::main"],
+        &["tokio::block_on"],
+        &["app::start"],
+        &["tokio::run"],
+    ];
+    let formatter = ShortBacktraceFormatter::new()
+        .user_prefix("app")
+        .mark_dependency_boundary(true);
+    assert_eq!(
+        formatter.format_impl(&bt),
+        "
+   0:        0x0 - serde::de::error
+   1:        0x0 - app::parse
+   2:        0x0 - std::io::read
+   3:        0x0 - app::main
+--- dependency frames ---
+   4:        0x0 - tokio::block_on
+   5:        0x0 - app::start
+   6:        0x0 - tokio::run"
+    );
+
+    // Nothing is marked if we never see user code
+    let formatter = ShortBacktraceFormatter::new().mark_dependency_boundary(true);
+    assert!(!formatter.format_impl(&bt).contains("---"));
+}
+
+#[test]
+fn test_panic_context_frames() {
+    let bt: BT = &[
+        &["app::panic_hook"],
+        &["std::panicking::begin_panic_handler"],
+        &["core::panicking::panic_fmt"],
+        &["app::parse"],
+        &["app::handle"],
+        &["app::main"],
+    ];
+    let names = |radius| -> Vec<&str> {
+        gunk::panic_context_frames_impl(&bt, radius)
+            .map(|(frame, range)| frame[range][0])
+            .collect()
+    };
+    assert_eq!(names(0), vec!["core::panicking::panic_fmt"]);
+    assert_eq!(
+        names(1),
+        vec![
+            "std::panicking::begin_panic_handler",
+            "core::panicking::panic_fmt",
+            "app::parse"
+        ]
+    );
+    assert_eq!(names(100).len(), 6);
+
+    let bt: BT = &[&["app::parse"], &["app::main"]];
+    assert_eq!(gunk::panic_context_frames_impl(&bt, 3).count(), 0);
+}
+
+fn parsed_symbol(name: Option<&str>, file: Option<&str>, line: Option<u32>) -> ParsedSymbol {
+    ParsedSymbol {
+        name: name.map(|name| name.to_owned()),
+        file: file.map(|file| file.into()),
+        line,
+        col: None,
+    }
+}
+
+#[test]
+fn test_parse_formatted_round_trip() {
+    let parsed = parse_formatted(FAKE_TRACE_FORMATTED);
+    let expected = vec![
+        ParsedFrame {
+            index: 0,
+            ip: Some(0x20),
+            symbols: vec![
+                parsed_symbol(Some("app::inner"), Some("src/inner.rs"), Some(10)),
+                parsed_symbol(Some("app::outer"), Some("src/lib.rs"), Some(20)),
+            ],
+        },
+        ParsedFrame {
+            index: 1,
+            ip: Some(0x30),
+            symbols: vec![parsed_symbol(None, None, None)],
+        },
+        ParsedFrame {
+            index: 2,
+            ip: Some(0x40),
+            symbols: vec![],
+        },
+        ParsedFrame {
+            index: 3,
+            ip: Some(0x50),
+            symbols: vec![parsed_symbol(
+                Some("app::main"),
+                Some("src/main.rs"),
+                Some(5),
+            )],
+        },
+    ];
+    assert_eq!(parsed, expected);
+}
+
+#[test]
+fn test_parse_formatted_locations() {
+    let text = "Error: oh no
+   0: 0x7ff6a1b2c3d4 - app::main
+                at C:\\src\\main.rs:5
+                 - app::inlined
+                at src/lib.rs:20:7
+  12: 0x10 - <T as app::Trait>::method
+                at weird";
+    let parsed = parse_formatted(text);
+    assert_eq!(parsed.len(), 2);
+    assert_eq!(parsed[0].ip, Some(0x7ff6a1b2c3d4));
+    assert_eq!(
+        parsed[0].symbols,
+        vec![
+            parsed_symbol(Some("app::main"), Some("C:\\src\\main.rs"), Some(5)),
+            ParsedSymbol {
+                col: Some(7),
+                ..parsed_symbol(Some("app::inlined"), Some("src/lib.rs"), Some(20))
+            },
+        ]
+    );
+    assert_eq!(parsed[1].index, 12);
+    assert_eq!(
+        parsed[1].symbols,
+        vec![parsed_symbol(
+            Some("<T as app::Trait>::method"),
+            Some("weird"),
+            None
+        )]
+    );
+}
+
+#[test]
+#[inline(never)]
+fn test_frame_collector() {
+    let mut collector = FrameCollector::new();
+    assert!(collector.is_empty());
+    backtrace::trace(|frame| {
+        collector.push(frame);
+        true
+    });
+    assert!(!collector.is_empty());
+    let len = collector.len();
+
+    let backtrace = collector.finish();
+    assert_eq!(backtrace.frames().len(), len);
+    let found = short_frames_strict(&backtrace).any(|short| {
+        short.symbols().iter().any(|symbol| {
+            demangled_name(symbol).map_or(false, |name| name.contains("test_frame_collector"))
+        })
+    });
+    assert!(found);
+}
+
+// A named function instead of a closure, so it still has a frame of its own with
+// optimizations on
+#[inline(never)]
+fn trace_on_thread() -> Vec<backtrace::Frame> {
+    let mut frames = vec![];
+    backtrace::trace(|frame| {
+        frames.push(frame.clone());
+        true
+    });
+    frames
+}
+
+#[test]
+fn test_frame_collector_from_frames() {
+    let frames = std::thread::spawn(trace_on_thread).join().unwrap();
+    let len = frames.len();
+
+    let backtrace = FrameCollector::from(frames).finish();
+    assert_eq!(backtrace.frames().len(), len);
+    let found = short_frames_strict(&backtrace).any(|short| {
+        short.symbols().iter().any(|symbol| {
+            demangled_name(symbol).map_or(false, |name| name.contains("trace_on_thread"))
+        })
+    });
+    assert!(found);
+}
+
+#[test]
+fn test_short_backtrace_attributes() {
+    let attributes = attributes::short_backtrace_attributes_impl(&fake_trace());
+    let expected = [
+        ("exception.stacktrace.frame.0.function", "app::inner"),
+        ("exception.stacktrace.frame.0.file", "src/inner.rs"),
+        ("exception.stacktrace.frame.0.line", "10"),
+        ("exception.stacktrace.frame.1.function", "app::outer"),
+        ("exception.stacktrace.frame.1.file", "src/lib.rs"),
+        ("exception.stacktrace.frame.1.line", "20"),
+        ("exception.stacktrace.frame.4.function", "app::main"),
+        ("exception.stacktrace.frame.4.file", "src/main.rs"),
+        ("exception.stacktrace.frame.4.line", "5"),
+    ];
+    let expected: Vec<_> = expected
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    assert_eq!(attributes, expected);
+}
+
+#[test]
+fn test_short_frames_without_sync_glue() {
+    let trace: FakeBT = vec![
+        frame(
+            0x10,
+            vec![sym("std::rust_end_short_backtrace", "std.rs", 1)],
+        ),
+        frame(
+            0x20,
+            vec![
+                sym("core::result::unwrap_failed", "src/result.rs", 1),
+                sym(
+                    "<std::sync::poison::PoisonError<T> as core::fmt::Debug>::fmt",
+                    "src/poison.rs",
+                    2,
+                ),
+            ],
+        ),
+        frame(
+            0x30,
+            vec![
+                sym("std::sync::mutex::Mutex<T>::lock", "src/mutex.rs", 3),
+                sym("app::locked", "src/lib.rs", 4),
+                sym("std::sync::poison::Flag::get", "src/poison.rs", 5),
+            ],
+        ),
+        frame(
+            0x40,
+            vec![sym(
+                "std::sync::rwlock::RwLock<T>::read",
+                "src/rwlock.rs",
+                6,
+            )],
+        ),
+        frame(0x50, vec![]),
+        frame(0x60, vec![sym("app::main", "src/main.rs", 5)]),
+        frame(
+            0x70,
+            vec![sym("std::rust_begin_short_backtrace", "std.rs", 2)],
+        ),
+    ];
+    let frames: Vec<_> = gunk::short_frames_without_symbols_impl(&trace, SYNC_GLUE_SYMBOLS)
+        .map(|(frame, range)| (frame.ip, range))
+        .collect();
+    assert_eq!(
+        frames,
+        vec![(0x20, 0..1), (0x30, 1..2), (0x50, 0..0), (0x60, 0..1)]
+    );
+}
+
+#[test]
+fn test_short_frames_without_gunk() {
+    let bt: BT = &[
+        &["rust_end_short_backtrace", "rust_begin_unwind"],
+        &["core::panicking::panic_fmt"],
+        &["app::inner", "core::panicking::panic_fmt"],
+        &["core::ops::function::FnOnce::call_once", "app::main"],
+        &[],
+        &["std::panicking::begin_panic_handler::{{closure}}"],
+        &["rust_begin_short_backtrace"],
+    ];
+    let frames: Vec<_> = gunk::short_frames_without_gunk_impl(&bt)
+        .map(|(frame, range)| frame[range].to_vec())
+        .collect();
+    assert_eq!(frames, vec![vec!["app::inner"], vec!["app::main"], vec![]]);
+}
+
+#[test]
+fn test_formatter_line_ending() {
+    let trace = fake_trace();
+    let default = ShortBacktraceFormatter::new().line_ending(LineEnding::Lf);
+    assert_eq!(default.format_impl(&trace), FAKE_TRACE_FORMATTED);
+
+    let crlf = ShortBacktraceFormatter::new().line_ending(LineEnding::CrLf);
+    let formatted = crlf.format_impl(&trace);
+    assert_eq!(formatted, FAKE_TRACE_FORMATTED.replace('\n', "\r\n"));
+
+    let threads = crlf.format_threads_impl(&[("a", fake_trace()), ("b", fake_trace())]);
+    assert!(!threads.replace("\r\n", "").contains('\n'));
+    assert!(threads.contains("\r\n\r\nb:\r\n"));
+}
+
+#[test]
+fn test_panic_location() {
+    let mut trace = fake_trace();
+    trace[1]
+        .symbols
+        .insert(0, sym("core::panicking::panic_fmt", "src/panicking.rs", 64));
+    trace[1].symbols[1].col = Some(7);
+    assert_eq!(
+        query::panic_location_impl(&trace),
+        Some(SourceLocation {
+            file: "src/inner.rs".into(),
+            line: 10,
+            col: Some(7),
+        })
+    );
+
+    let no_locations: FakeBT = vec![frame(0x10, vec![unknown_sym()]), frame(0x20, vec![])];
+    assert_eq!(query::panic_location_impl(&no_locations), None);
+
+    let (name, location) = query::panic_location_named_impl(&trace).unwrap();
+    assert_eq!(name, "app::inner");
+    assert_eq!(location.line, 10);
+    let mut unnamed = fake_trace();
+    unnamed[1].symbols[0].name = None;
+    assert_eq!(
+        query::panic_location_named_impl(&unnamed).unwrap().0,
+        "<unknown>"
+    );
+    assert_eq!(query::panic_location_named_impl(&no_locations), None);
+}
+
+#[test]
+#[cfg(feature = "test-util")]
+fn test_assert_backtrace_contains() {
+    assert::assert_backtrace_contains_impl(&fake_trace(), "app::outer");
+
+    let result = std::panic::catch_unwind(|| {
+        assert::assert_backtrace_contains_impl(&fake_trace(), "app::missing");
+    });
+    let payload = result.unwrap_err();
+    let message = payload.downcast_ref::<String>().unwrap();
+    assert!(message.contains("`app::missing`"));
+    assert!(message.ends_with(FAKE_TRACE_FORMATTED));
+}
+
+#[test]
+fn test_short_frame_offsets() {
+    let mut trace = fake_trace();
+    trace[1].symbol_address = 0x18;
+    trace[1].module_base = Some(0x8);
+    trace[2].symbol_address = 0;
+    trace[2].module_base = Some(0x1000);
+    let offsets: Vec<_> = offsets::short_frame_offsets_impl(&trace)
+        .map(|offset| (offset.ip, offset.offset, offset.module_offset))
+        .collect();
+    assert_eq!(
+        offsets,
+        vec![
+            (0x20, Some(0x8), Some(0x18)),
+            (0x30, None, None),
+            (0x40, Some(0), None),
+            (0x50, Some(0), None),
+        ]
+    );
+}
+
+#[test]
+fn test_short_frame_offsets_real() {
+    let backtrace = Backtrace::new();
+    for offset in short_frame_offsets(&backtrace) {
+        if let Some(delta) = offset.offset {
+            assert_eq!(offset.symbol_address + delta, offset.ip);
+        }
+    }
+}
+
+#[test]
+fn test_format_collapse_unresolved_runs() {
+    let formatter = ShortBacktraceFormatter::new().collapse_unresolved_runs(true);
+    let out = formatter.format_impl(&fake_trace());
+    assert_eq!(
+        out,
+        "
+   0:       0x20 - app::inner
+                at src/inner.rs:10
+                 - app::outer
+                at src/lib.rs:20
+      ... 2 unresolved frames ...
+   3:       0x50 - app::main
+                at src/main.rs:5"
+    );
+
+    // A lone unresolved frame is left alone, and a run at the end is still flushed
+    let trace: FakeBT = vec![
+        frame(0x10, vec![]),
+        frame(0x20, vec![sym("app::main", "src/main.rs", 5)]),
+        frame(0x30, vec![unknown_sym()]),
+        frame(0x40, vec![]),
+        frame(0x50, vec![]),
+    ];
+    let out = formatter.format_impl(&trace);
+    assert_eq!(
+        out,
+        "
+   0:       0x10 - <unresolved>
+   1:       0x20 - app::main
+                at src/main.rs:5
+      ... 3 unresolved frames ..."
+    );
+}
+
+#[test]
+fn test_short_frames_with_names() {
+    let trace = fake_trace();
+    let names: Vec<_> = adapters::short_frames_with_names_impl(&trace, InlinePolicy::All)
+        .map(|(frame, names)| (frame.ip, names))
+        .collect();
+    // Nothing needed demangling
+    assert!(names
+        .iter()
+        .flat_map(|(_, names)| names)
+        .all(|name| matches!(name, std::borrow::Cow::Borrowed(_))));
+    let names: Vec<(usize, Vec<&str>)> = names
+        .iter()
+        .map(|(ip, names)| (*ip, names.iter().map(|name| &**name).collect()))
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            (0x20, vec!["app::inner", "app::outer"]),
+            (0x30, vec![]),
+            (0x40, vec![]),
+            (0x50, vec!["app::main"]),
+        ]
+    );
+
+    let innermost: Vec<_> =
+        adapters::short_frames_with_names_impl(&trace, InlinePolicy::InnermostOnly)
+            .map(|(_, names)| names)
+            .collect();
+    assert_eq!(innermost[0], vec!["app::inner"]);
+}
+
+#[test]
+#[inline(never)]
+fn test_short_frames_with_names_real() {
+    let backtrace = Backtrace::new();
+    let found = short_frames_with_names(&backtrace, InlinePolicy::All).any(|(_, names)| {
+        names
+            .iter()
+            .any(|name| name.ends_with("test_short_frames_with_names_real"))
+    });
+    assert!(found);
+}
+
+#[test]
+fn test_format_short_backtrace() {
+    let trace = fake_trace();
+    for var in [None, Some(""), Some("0")].iter() {
+        assert_eq!(env::format_short_backtrace_impl(&trace, *var), "");
+    }
+    for var in ["1", "full"].iter() {
+        assert_eq!(
+            env::format_short_backtrace_impl(&trace, Some(var)),
+            FAKE_TRACE_FORMATTED
+        );
+    }
+}
+
+#[test]
+fn test_format_short_backtrace_env() {
+    let trace = fake_trace();
+    for var in [None, Some(""), Some("0")].iter() {
+        assert_eq!(env::format_short_backtrace_env_impl(&trace, *var), "");
+    }
+    for var in ["1", "true"].iter() {
+        assert_eq!(
+            env::format_short_backtrace_env_impl(&trace, Some(var)),
+            FAKE_TRACE_FORMATTED
+        );
+    }
+    assert_eq!(
+        env::format_short_backtrace_env_impl(&trace, Some("full")),
+        "
+   0:       0x10 - std::rust_end_short_backtrace
+                at std.rs:1
+   1:       0x20 - app::inner
+                at src/inner.rs:10
+                 - app::outer
+                at src/lib.rs:20
+   2:       0x30 - <unknown>
+   3:       0x40 - <unresolved>
+   4:       0x50 - app::main
+                at src/main.rs:5
+   5:       0x60 - std::rust_begin_short_backtrace
+                at std.rs:2"
+    );
+}
+
+#[test]
+fn test_meaningful_frame_count() {
+    assert_eq!(stats::meaningful_frame_count_impl(&fake_trace()), 2);
+
+    let bt: FakeBT = vec![
+        frame(0x10, vec![sym("rust_begin_unwind", "src/panicking.rs", 1)]),
+        frame(
+            0x20,
+            vec![
+                sym("core::panicking::panic_fmt", "src/panicking.rs", 2),
+                unknown_sym(),
+            ],
+        ),
+        frame(0x30, vec![]),
+    ];
+    assert_eq!(stats::meaningful_frame_count_impl(&bt), 0);
+
+    let mut nameless = unknown_sym();
+    nameless.file = Some("src/lib.rs");
+    let bt: FakeBT = vec![frame(0x10, vec![nameless])];
+    assert_eq!(stats::meaningful_frame_count_impl(&bt), 1);
+}
+
+#[test]
+fn test_format_header() {
+    let formatter =
+        ShortBacktraceFormatter::new().header(Some("Backtrace (most recent call first):".into()));
+    assert_eq!(
+        formatter.format_impl(&fake_trace()),
+        format!(
+            "\nBacktrace (most recent call first):{}",
+            FAKE_TRACE_FORMATTED
+        )
+    );
+
+    let formatter = ShortBacktraceFormatter::new().header(None);
+    assert_eq!(formatter.format_impl(&fake_trace()), FAKE_TRACE_FORMATTED);
+}
+
+#[test]
+fn test_skipped_frames() {
+    let trace = fake_trace();
+    assert_eq!(collect::skipped_frames_impl(&trace, 0), Vec::<usize>::new());
+    assert_eq!(collect::skipped_frames_impl(&trace, 2), vec![1, 2]);
+    assert_eq!(collect::skipped_frames_impl(&trace, 100), vec![1, 2, 3, 4]);
+
+    // The first short frame has the end marker inlined into it, so it has to stay
+    let bt: BT = &[&["rust_end_short_backtrace", "app::inner"], &["app::main"]];
+    assert_eq!(collect::skipped_frames_impl(&bt, 2), vec![1]);
+}
+
+#[inline(never)]
+fn capture_for_caller() -> Backtrace {
+    // Do something observable after the call, so release builds can't turn it into a tail
+    // call that takes this frame off the stack before the capture
+    static CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    let backtrace = capture_short_skip(1);
+    CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    backtrace
+}
+
+#[test]
+#[inline(never)]
+fn test_capture_short_skip() {
+    let names = |backtrace: &Backtrace| -> Vec<String> {
+        short_symbols(backtrace)
+            .filter_map(|(_, _, symbol)| demangled_name(symbol))
+            .collect()
+    };
+
+    let own = names(&capture_short_skip(0));
+    assert!(own[0].ends_with("test_capture_short_skip"));
+    assert!(!own
+        .iter()
+        .any(|name| name.ends_with("collect::capture_short_skip")));
+
+    let skipped = names(&capture_for_caller());
+    assert!(skipped[0].ends_with("test_capture_short_skip"));
+    assert!(!skipped
+        .iter()
+        .any(|name| name.contains("capture_for_caller")));
+}
+
+#[test]
+fn test_format_colorize() {
+    let trace: FakeBT = vec![
+        frame(
+            0x10,
+            vec![sym("std::rust_end_short_backtrace", "std.rs", 1)],
+        ),
+        frame(
+            0x20,
+            vec![sym("core::panicking::panic_fmt", "src/panicking.rs", 2)],
+        ),
+        frame(0x30, vec![sym("app::inner", "src/inner.rs", 10)]),
+        frame(0x40, vec![sym("serde::de::thing", "src/de.rs", 3)]),
+        frame(0x50, vec![sym("app::main", "src/main.rs", 5)]),
+        frame(0x60, vec![]),
+    ];
+    let formatter = ShortBacktraceFormatter::new()
+        .user_prefix("app")
+        .colorize(true);
+    assert_eq!(
+        formatter.format_impl(&trace),
+        "
+   0:       0x20 - \x1b[34mcore::panicking::panic_fmt\x1b[0m
+                at src/panicking.rs:2
+   1:       0x30 - \x1b[1;32mapp::inner\x1b[0m
+                at src/inner.rs:10
+   2:       0x40 - \x1b[2mserde::de::thing\x1b[0m
+                at src/de.rs:3
+   3:       0x50 - \x1b[32mapp::main\x1b[0m
+                at src/main.rs:5
+   4:       0x60 - <unresolved>"
+    );
+    assert_eq!(
+        formatter
+            .clone()
+            .style(FormatStyle::SingleLine)
+            .format_impl(&trace),
+        "
+0: \x1b[34mcore::panicking::panic_fmt\x1b[0m (src/panicking.rs:2)
+1: \x1b[1;32mapp::inner\x1b[0m (src/inner.rs:10)
+2: \x1b[2mserde::de::thing\x1b[0m (src/de.rs:3)
+3: \x1b[32mapp::main\x1b[0m (src/main.rs:5)
+4: <unresolved>"
+    );
+    assert!(formatter
+        .clone()
+        .style(FormatStyle::GdbLike)
+        .format_impl(&trace)
+        .starts_with("\n#0  \x1b[34mcore::panicking::panic_fmt\x1b[0m at src/panicking.rs:2\n"));
+
+    let colors = ColorMap {
+        std: String::new(),
+        panic_origin: "4".to_owned(),
+        ..ColorMap::default()
+    };
+    let out = formatter.color_map(colors).format_impl(&trace);
+    assert!(out.contains("0x20 - core::panicking::panic_fmt"));
+    assert!(out.contains("0x30 - \x1b[4;32mapp::inner\x1b[0m\n"));
+
+    // Off by default
+    let out = ShortBacktraceFormatter::new().format_impl(&trace);
+    assert!(!out.contains('\x1b'));
+}
+
+/// Just enough of an executor to run a future to completion
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct ThreadWaker(std::thread::Thread);
+    impl Wake for ThreadWaker {
+        fn wake(self: std::sync::Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(std::sync::Arc::new(ThreadWaker(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => std::thread::park(),
+        }
+    }
+}
+
+#[test]
+#[inline(never)]
+fn test_capture_deferred() {
+    let is_this_test = |(_, _, symbol): (usize, &BacktraceFrame, &BacktraceSymbol)| {
+        demangled_name(symbol).map_or(false, |name| name.ends_with("test_capture_deferred"))
+    };
+
+    let deferred = capture_deferred();
+    let clamped = block_on(deferred.clone().resolve_in_background());
+    assert!(short_symbols(&clamped).any(is_this_test));
+
+    let backtrace = deferred.resolve();
+    assert!(short_symbols(&backtrace).any(is_this_test));
+
+    // The background one is clamped to the frames with the markers, and clamps the same
+    let (_, frame_range, _, _) = short_slice(&backtrace);
+    assert!(clamped.frames().len() < backtrace.frames().len());
+    assert!(clamped.frames().len() <= frame_range.len() + 2);
+    let ips = |backtrace: &Backtrace| -> Vec<_> {
+        short_frames_strict(backtrace)
+            .map(|short| (short.frame.ip(), short.sub_frames))
+            .collect()
+    };
+    assert_eq!(ips(&clamped), ips(&backtrace));
+}
+
+#[test]
+fn test_resolve_in_background_panic() {
+    let resolving = capture_deferred().resolve_in_background_with(|_| panic!("resolving"));
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| block_on(resolving)));
+    assert_eq!(
+        result.unwrap_err().downcast_ref::<&str>(),
+        Some(&"resolving")
+    );
+
+    // The background thread is still there for the next one
+    let backtrace = block_on(capture_deferred().resolve_in_background());
+    assert!(!backtrace.frames().is_empty());
+}
+
+#[test]
+fn test_clamp_to_markers() {
+    let backtrace = Backtrace::new();
+    let clamped = deferred::clamp_to_markers(backtrace.clone());
+    let (_, frame_range, _, _) = short_slice(&backtrace);
+    let has_begin = |frame: &BacktraceFrame| {
+        frame
+            .symbols()
+            .iter()
+            .filter_map(demangled_name)
+            .any(|name| name.contains("rust_begin_short_backtrace"))
+    };
+    assert!(has_begin(clamped.frames().last().unwrap()));
+    assert_eq!(
+        clamped.frames()[0].ip(),
+        backtrace.frames()[frame_range.start].ip()
+    );
+}
+
+#[inline(never)]
+fn capture_in_helper() -> Backtrace {
+    let backtrace = capture_short_resolved();
+    // Do something after capturing, so this isn't a tail call
+    assert!(!backtrace.frames().is_empty());
+    backtrace
+}
+
+#[test]
+fn test_capture_short_resolved_names() {
+    let backtrace = capture_in_helper();
+    assert!(short_symbols(&backtrace).any(|(_, _, symbol)| {
+        demangled_name(symbol).map_or(false, |name| name.ends_with("capture_in_helper"))
+    }));
+
+    // Without resolving there's nothing to find the markers by
+    let unresolved = Backtrace::new_unresolved();
+    assert_eq!(
+        short_frames_strict(&unresolved).len(),
+        unresolved.frames().len()
+    );
+    assert!(short_symbols(&unresolved).next().is_none());
+}
+
+#[test]
+fn test_resolve_at_most() {
+    let deferred = capture_deferred();
+    let total = deferred.clone().resolve().frames().len();
+
+    let backtrace = deferred.clone().resolve_at_most(2);
+    assert_eq!(backtrace.frames().len(), total);
+    assert!(backtrace.frames()[2..]
+        .iter()
+        .all(|frame| frame.symbols().is_empty()));
+    assert!(!backtrace.frames()[0].symbols().is_empty());
+
+    let backtrace = deferred.resolve_at_most(usize::MAX);
+    assert_eq!(backtrace.frames().len(), total);
+}
+
+#[test]
+fn test_resolve_short() {
+    let deferred = capture_deferred();
+    let eager = deferred.clone().resolve();
+    let lazy = deferred.resolve_short();
+    assert_eq!(lazy.frames().len(), eager.frames().len());
+    assert_eq!(
+        summary::short_backtrace_names_impl(&lazy),
+        summary::short_backtrace_names_impl(&eager)
+    );
+
+    // The test harness runs every test under the begin marker, and nothing after the
+    // chunk it was resolved in should have been resolved
+    let (_, frame_range, _, _) = short_slice_impl(&lazy);
+    assert!(frame_range.end < lazy.frames().len());
+    let chunk_end = (frame_range.end / deferred::RESOLVE_CHUNK + 1) * deferred::RESOLVE_CHUNK;
+    assert!(lazy.frames()[chunk_end.min(lazy.frames().len())..]
+        .iter()
+        .all(|frame| frame.symbols().is_empty()));
+}
+
+#[test]
+#[inline(never)]
+fn test_resolve_short_limited() {
+    let deferred = capture_deferred();
+    let total = deferred.clone().resolve().frames().len();
+
+    let backtrace = deferred.clone().resolve_short_limited(3);
+    assert_eq!(backtrace.frames().len(), total);
+    assert!(backtrace.frames()[..3]
+        .iter()
+        .all(|frame| !frame.symbols().is_empty()));
+    assert!(backtrace.frames()[3..]
+        .iter()
+        .all(|frame| frame.symbols().is_empty()));
+
+    let backtrace = deferred.clone().resolve_short_limited(0);
+    assert!(backtrace
+        .frames()
+        .iter()
+        .all(|frame| frame.symbols().is_empty()));
+
+    // With a big enough budget it's the same as resolve_short
+    let limited = deferred.clone().resolve_short_limited(total);
+    let short = deferred.resolve_short();
+    assert_eq!(
+        summary::short_backtrace_names_impl(&limited),
+        summary::short_backtrace_names_impl(&short)
+    );
+
+    let backtrace = capture_short_limited(usize::MAX);
+    assert!(short_symbols(&backtrace).any(|(_, _, symbol)| {
+        demangled_name(symbol).map_or(false, |name| name.ends_with("test_resolve_short_limited"))
+    }));
+}
+
+fn catch_unwind_trace() -> FakeBT {
+    vec![
+        frame(0x10, vec![sym("app::inner", "src/inner.rs", 10)]),
+        frame(
+            0x20,
+            vec![
+                sym("std::panicking::try::do_call", "src/panicking.rs", 1),
+                sym("std::panicking::try", "src/panicking.rs", 2),
+            ],
+        ),
+        frame(
+            0x30,
+            vec![sym("std::panic::catch_unwind", "src/panic.rs", 3)],
+        ),
+        frame(0x40, vec![sym("app::main", "src/main.rs", 5)]),
+    ]
+}
+
+#[test]
+fn test_catch_unwind_frame() {
+    let trace = catch_unwind_trace();
+    let (frame, idx) = gunk::catch_unwind_frame_impl(&trace).unwrap();
+    assert_eq!((frame.ip, idx), (0x20, 0));
+    assert!(gunk::catch_unwind_frame_impl(&fake_trace()).is_none());
+
+    let frames: Vec<_> = gunk::short_frames_until_catch_unwind_impl(&trace)
+        .map(|(frame, range)| (frame.ip, range))
+        .collect();
+    assert_eq!(frames, vec![(0x10, 0..1)]);
+}
+
+#[test]
+fn test_format_mark_catch_unwind() {
+    let formatter = ShortBacktraceFormatter::new()
+        .mark_catch_unwind(true)
+        .inline_policy(InlinePolicy::InnermostOnly);
+    assert_eq!(
+        formatter.format_impl(&catch_unwind_trace()),
+        "
+   0:       0x10 - app::inner
+                at src/inner.rs:10
+   1:       0x20 - std::panicking::try::do_call
+                at src/panicking.rs:1
+                (caught here)
+   2:       0x30 - std::panic::catch_unwind
+                at src/panic.rs:3
+   3:       0x40 - app::main
+                at src/main.rs:5"
+    );
+}
+
+// With optimizations the catch_unwind machinery is all inlined away (and without debuginfo
+// the inlined symbols can't be recovered), so there's no frame to find
+#[cfg(debug_assertions)]
+#[test]
+#[inline(never)]
+fn test_catch_unwind_frame_real() {
+    let backtrace = std::panic::catch_unwind(Backtrace::new).unwrap();
+    assert!(catch_unwind_frame(&backtrace).is_some());
+    let names: Vec<String> = short_frames_until_catch_unwind(&backtrace)
+        .flat_map(|short| short.symbols().iter().filter_map(demangled_name))
+        .collect();
+    assert!(!names
+        .iter()
+        .any(|name| name.ends_with("test_catch_unwind_frame_real")));
+}
+
+#[test]
+fn test_short_frame_slice_real() {
+    let backtrace = Backtrace::new();
+    let (frames, range, first, last) = short_slice(&backtrace);
+    let slice = short_frame_slice(&backtrace);
+    assert_eq!(slice.as_ptr(), frames.as_ptr());
+    assert_eq!(slice.len(), frames.len());
+    assert_eq!(slice.as_ptr(), backtrace.frames()[range].as_ptr());
+    assert_eq!(short_subframe_bounds(&backtrace), (first, last));
+}
+
+#[test]
+fn test_format_show_crate_tag() {
+    let trace: FakeBT = vec![
+        frame(
+            0x10,
+            vec![
+                sym("app::parse::<T>::{{closure}}", "src/parse.rs", 1),
+                sym(
+                    "<alloc::vec::Vec<T> as serde::Serialize>::serialize",
+                    "src/ser.rs",
+                    2,
+                ),
+            ],
+        ),
+        frame(0x20, vec![unknown_sym(), sym("main", "src/main.rs", 3)]),
+    ];
+    let formatter = ShortBacktraceFormatter::new().show_crate_tag(true);
+    assert_eq!(
+        formatter.format_impl(&trace),
+        "
+   0:       0x10 - [app] app::parse::<T>::{{closure}}
+                at src/parse.rs:1
+                 - [alloc] <alloc::vec::Vec<T> as serde::Serialize>::serialize
+                at src/ser.rs:2
+   1:       0x20 - <unknown>
+                 - [C] main
+                at src/main.rs:3"
+    );
+
+    let gdb = formatter.style(FormatStyle::GdbLike).format_impl(&trace);
+    assert!(gdb.starts_with("\n#0  [app] app::parse::<T>::{{closure}} at src/parse.rs:1"));
+}
+
+#[test]
+fn test_append_short_backtrace() {
+    let mut out = "Error: oh no\n  caused by: bad".to_owned();
+    format::append_short_backtrace_impl(&mut out, 4, &fake_trace());
+    let expected = FAKE_TRACE_FORMATTED.replace('\n', "\n    ");
+    assert_eq!(out, format!("Error: oh no\n  caused by: bad{}", expected));
+
+    let mut out = String::new();
+    format::append_short_backtrace_impl(&mut out, 0, &fake_trace());
+    assert_eq!(out, FAKE_TRACE_FORMATTED);
+
+    let crlf = ShortBacktraceFormatter::new()
+        .indent(2)
+        .line_ending(LineEnding::CrLf)
+        .format_impl(&fake_trace());
+    assert_eq!(crlf, FAKE_TRACE_FORMATTED.replace('\n', "\r\n  "));
+}
+
+#[test]
+fn test_format_max_bytes() {
+    let trace = fake_trace();
+    let unlimited = ShortBacktraceFormatter::new().max_bytes(FAKE_TRACE_FORMATTED.len());
+    assert_eq!(unlimited.format_impl(&trace), FAKE_TRACE_FORMATTED);
+
+    let limited = ShortBacktraceFormatter::new().max_bytes(30);
+    assert_eq!(
+        limited.format_impl(&trace),
+        format!("{}... (truncated)", &FAKE_TRACE_FORMATTED[..30])
+    );
+
+    // Don't split up a multi-byte char
+    let trace: FakeBT = vec![frame(0x10, vec![sym("app::ünïcödé", "src/lib.rs", 1)])];
+    let formatted = ShortBacktraceFormatter::new().format_impl(&trace);
+    let cut = formatted.find('ü').unwrap() + 1;
+    let limited = ShortBacktraceFormatter::new().max_bytes(cut);
+    assert_eq!(
+        limited.format_impl(&trace),
+        format!("{}... (truncated)", &formatted[..cut - 1])
+    );
+}
+
+#[test]
+fn test_short_ips() {
+    let ips: Vec<_> = offsets::short_ips_impl(&fake_trace()).collect();
+    assert_eq!(ips, vec![0x20, 0x30, 0x40, 0x50]);
+}
+
+#[test]
+#[inline(never)]
+fn test_capture_short_ips() {
+    let names = |ips: Vec<usize>| {
+        let mut names = Vec::new();
+        for ip in ips {
+            backtrace::resolve(ip as *mut c_void, |symbol| {
+                names.extend(symbol.name().map(|name| name.to_string()));
+            });
+        }
+        names
+    };
+    let short: Vec<usize> = capture_short_ips(IpScan::Markers).collect();
+    let full: Vec<usize> = capture_short_ips(IpScan::FullStack).collect();
+    // The test harness runs every test under the begin marker
+    assert!(short.len() < full.len());
+
+    for names in [names(short), names(full)] {
+        assert!(names
+            .iter()
+            .any(|name| name.contains("test::test_capture_short_ips")));
+        assert!(!names
+            .iter()
+            .any(|name| name.contains("offsets::capture_short_ips")));
+    }
+}
+
+#[test]
+fn test_format_address_format() {
+    let trace = fake_trace();
+    let formatter = ShortBacktraceFormatter::new().address_format(AddressFormat::default());
+    assert_eq!(formatter.format_impl(&trace), FAKE_TRACE_FORMATTED);
+
+    let header = |address_format| {
+        let out = ShortBacktraceFormatter::new()
+            .address_format(address_format)
+            .format_impl(&trace);
+        out.lines().nth(1).unwrap().to_owned()
+    };
+    assert_eq!(
+        header(AddressFormat::Hex { width: 6 }),
+        "   0:   0x20 - app::inner"
+    );
+    assert_eq!(
+        header(AddressFormat::ZeroPaddedHex { digits: 8 }),
+        "   0: 0x00000020 - app::inner"
+    );
+    assert_eq!(header(AddressFormat::Decimal), "   0: 32 - app::inner");
+
+    let trace: FakeBT = vec![frame(usize::MAX, vec![unknown_sym()])];
+    let out = ShortBacktraceFormatter::new()
+        .address_format(AddressFormat::Hex { width: 4 })
+        .format_impl(&trace);
+    assert_eq!(out, format!("\n   0: {:#x} - <unknown>", usize::MAX));
+}
+
+#[test]
+fn test_format_address_affixes() {
+    let header = |formatter: ShortBacktraceFormatter| {
+        let out = formatter
+            .address_affixes("<", ">")
+            .format_impl(&fake_trace());
+        out.lines().nth(1).unwrap().to_owned()
+    };
+    assert_eq!(
+        header(ShortBacktraceFormatter::new()),
+        "   0:       <0x20> - app::inner"
+    );
+    assert_eq!(
+        header(
+            ShortBacktraceFormatter::new()
+                .address_format(AddressFormat::ZeroPaddedHex { digits: 16 })
+        ),
+        "   0: <0x0000000000000020> - app::inner"
+    );
+
+    // `module+offset` isn't an address, so it isn't wrapped
+    let trace = vec![FakeFrame {
+        ip: 0x1234,
+        symbol_address: 0x1200,
+        module_base: Some(0x1000),
+        symbols: vec![sym("app::inner", "src/inner.rs", 10)],
+    }];
+    let mut names = HashMap::new();
+    names.insert(0x1000, "libapp.so".to_owned());
+    let out = ShortBacktraceFormatter::new()
+        .address_affixes("<", ">")
+        .module_names(names)
+        .format_impl(&trace);
+    assert!(out.starts_with("\n   0: libapp.so+0x234 - app::inner"));
+}
+
+#[test]
+fn test_format_address_format_real() {
+    // Make sure we print real addresses the same way `{:?}` on the pointer does
+    let backtrace = Backtrace::new();
+    let out = ShortBacktraceFormatter::new().format(&backtrace);
+    let frame = short_frames_strict(&backtrace).next().unwrap().frame;
+    let expected = format!(
+        "\n   0: {:1$?} - ",
+        frame.ip(),
+        std::mem::size_of::<usize>() + 2
+    );
+    assert!(out.starts_with(&expected));
+}
+
+#[test]
+fn test_classify_foreign() {
+    let frame: &[&str] = &["memcpy", "core::ptr::copy"];
+    assert_eq!(
+        classify::classify_frame_impl(&frame, 0..1, "app"),
+        FrameKind::Foreign
+    );
+    assert_eq!(
+        classify::classify_frame_impl(&frame, 0..2, "app"),
+        FrameKind::Std
+    );
+}
+
+#[test]
+#[inline(never)]
+fn test_classify_foreign_real() {
+    let backtrace = Backtrace::new();
+    let (frame, idx) = find_frame_by_name(&backtrace, "test_classify_foreign_real").unwrap();
+    let symbol = &frame.symbols()[idx];
+    assert!(symbol.is_rust());
+    assert_eq!(classify_symbol(symbol, "backtrace_ext"), FrameKind::User);
+}
+
+#[test]
+fn test_format_first_symbol_only() {
+    let trace = fake_trace();
+    let terse = ShortBacktraceFormatter::new().first_symbol_only(true);
+    assert_eq!(
+        terse.format_impl(&trace),
+        ShortBacktraceFormatter::new()
+            .inline_policy(InlinePolicy::InnermostOnly)
+            .format_impl(&trace)
+    );
+    assert!(!terse.format_impl(&trace).contains("app::outer"));
+
+    let full = terse.first_symbol_only(false);
+    assert_eq!(full.format_impl(&trace), FAKE_TRACE_FORMATTED);
+}
+
+#[test]
+fn test_short_backtrace_similarity() {
+    let similarity = stats::short_backtrace_similarity_impl;
+    let a: BT = &[&["app::parse"], &["app::handle"], &["app::main"]];
+    let b: BT = &[
+        &["app::parse"],
+        &["app::retry"],
+        &["app::handle"],
+        &["app::main"],
+    ];
+    let c: BT = &[&["other::thing"]];
+    let empty: BT = &[];
+
+    assert_eq!(similarity(&a, &a), 1.0);
+    assert_eq!(similarity(&a, &b), 6.0 / 7.0);
+    assert_eq!(similarity(&b, &a), 6.0 / 7.0);
+    assert_eq!(similarity(&a, &c), 0.0);
+    assert_eq!(similarity(&a, &empty), 0.0);
+    assert_eq!(similarity(&empty, &empty), 1.0);
+
+    // Order matters
+    let reversed: BT = &[&["app::main"], &["app::handle"], &["app::parse"]];
+    assert_eq!(similarity(&a, &reversed), 2.0 / 6.0);
+}
+
+#[test]
+fn test_gunk_frame_counts() {
+    assert_eq!(diagnostics::gunk_frame_counts_impl(&fake_trace()), (1, 1));
+
+    let bt: BT = &[
+        &["junk"],
+        &["junk", "__rust_end_short_backtrace", "real"],
+        &["frames"],
+        &["__rust_begin_short_backtrace"],
+        &["junk"],
+        &["junk"],
+    ];
+    assert_eq!(diagnostics::gunk_frame_counts_impl(&bt), (1, 3));
+
+    let bt: BT = &[&["no"], &["markers"]];
+    assert_eq!(diagnostics::gunk_frame_counts_impl(&bt), (0, 0));
+}
+
+#[test]
+fn test_format_address_base() {
+    let out = ShortBacktraceFormatter::new()
+        .address_base(0x30)
+        .format_impl(&fake_trace());
+    let headers: Vec<_> = out.lines().filter(|line| line.contains(" - ")).collect();
+    assert_eq!(
+        headers,
+        vec![
+            // Below the base, so left alone
+            "   0:       0x20 - app::inner",
+            "                 - app::outer",
+            "   1:        0x0 - <unknown>",
+            "   2:       0x10 - <unresolved>",
+            "   3:       0x20 - app::main",
+        ]
+    );
+}
+
+#[test]
+fn test_fold_generics() {
+    let cases = [
+        ("app::parse", "app::parse"),
+        ("app::parse::<u32>", "app::parse::<_>"),
+        (
+            "app::parse::<alloc::vec::Vec<u32>, fn() -> u8>::{{closure}}",
+            "app::parse::<_>::{{closure}}",
+        ),
+        (
+            "<alloc::vec::Vec<T> as core::fmt::Debug>::fmt",
+            "<alloc::vec::Vec<_> as core::fmt::Debug>::fmt",
+        ),
+        ("<impl app::Foo>::bar::<u8>", "<impl app::Foo>::bar::<_>"),
+        ("app::broken::<u8", "app::broken::<_>"),
+    ];
+    for (name, folded) in cases.iter() {
+        assert_eq!(names::fold_generics(name), *folded);
+    }
+}
+
+#[test]
+fn test_format_fold_generics() {
+    let trace: FakeBT = vec![frame(
+        0x10,
+        vec![
+            sym("app::parse::<u32>", "src/parse.rs", 1),
+            sym("app::parse::<alloc::string::String>", "src/parse.rs", 1),
+        ],
+    )];
+    let out = ShortBacktraceFormatter::new()
+        .fold_generics(true)
+        .format_impl(&trace);
+    assert_eq!(
+        out,
+        "
+   0:       0x10 - app::parse::<_>
+                at src/parse.rs:1
+                 - app::parse::<_>
+                at src/parse.rs:1"
+    );
+}
+
+#[test]
+fn test_short_debug() {
+    assert_eq!(
+        summary::short_debug_impl(&fake_trace()),
+        "0: app::inner at src/inner.rs:10
+0: app::outer at src/lib.rs:20
+1: <unknown>
+2: <unresolved>
+3: app::main at src/main.rs:5"
+    );
+    let empty: BT = &[];
+    assert_eq!(summary::short_debug_impl(&empty), "");
+}
+
+#[test]
+fn test_trace_touches_file() {
+    use query::{trace_touches_file_impl as touches, PathMatch};
+    use std::path::Path;
+
+    let bt = fake_trace();
+    assert!(touches(&bt, Path::new("src/lib.rs"), PathMatch::Full));
+    assert!(touches(&bt, Path::new("lib.rs"), PathMatch::Suffix));
+    assert!(!touches(&bt, Path::new("lib.rs"), PathMatch::Full));
+    assert!(!touches(&bt, Path::new("b.rs"), PathMatch::Suffix));
+    // Outside of the short backtrace
+    assert!(!touches(&bt, Path::new("std.rs"), PathMatch::Suffix));
+}
+
+#[test]
+fn test_format_frame_separator() {
+    let formatter = ShortBacktraceFormatter::new().frame_separator(Some("---".into()));
+    assert_eq!(
+        formatter.format_impl(&fake_trace()),
+        "
+   0:       0x20 - app::inner
+                at src/inner.rs:10
+                 - app::outer
+                at src/lib.rs:20
+---
+   1:       0x30 - <unknown>
+---
+   2:       0x40 - <unresolved>
+---
+   3:       0x50 - app::main
+                at src/main.rs:5"
+    );
+
+    let formatter = ShortBacktraceFormatter::new().frame_separator(None);
+    assert_eq!(formatter.format_impl(&fake_trace()), FAKE_TRACE_FORMATTED);
+
+    let bt: BT = &[&["app::main"]];
+    let formatter = ShortBacktraceFormatter::new().frame_separator(Some(String::new()));
+    assert!(!formatter.format_impl(&bt).contains("\n\n"));
+}
+
+#[test]
+fn test_panic_in_user_code() {
+    use query::panic_in_user_code_impl as in_user_code;
+
+    let bt: BT = &[
+        &["rust_end_short_backtrace"],
+        &["core::panicking::panic_fmt"],
+        &[
+            "core::option::expect_failed",
+            "core::ops::function::FnOnce::call_once",
+        ],
+        &["app::parse"],
+        &["serde::de::deserialize"],
+    ];
+    assert!(in_user_code(&bt, "app"));
+    assert!(!in_user_code(&bt, "other"));
+
+    let bt: BT = &[
+        &["core::panicking::panic_fmt"],
+        &["serde::de::fail"],
+        &["app::main"],
+    ];
+    assert!(!in_user_code(&bt, "app"));
+
+    let bt: BT = &[&["core::panicking::panic_fmt"]];
+    assert!(!in_user_code(&bt, "app"));
+}
+
+#[test]
+fn test_short_backtrace_folded() {
+    assert_eq!(
+        summary::short_backtrace_folded_impl(&fake_trace()),
+        "app::main;[unknown];[unknown];app::outer;app::inner 1"
+    );
+
+    let bt: BT = &[&["<[u8; 4] as app::Parse>::parse"], &["app::main"]];
+    assert_eq!(
+        summary::short_backtrace_folded_impl(&bt),
+        "app::main;<[u8, 4] as app::Parse>::parse 1"
+    );
+}
+
+#[test]
+fn test_find_cycles() {
+    use recursion::{find_cycles, RecursionCycle};
+
+    let cycle = |start, len, repeats| RecursionCycle {
+        start,
+        len,
+        repeats,
+    };
+    let keys = |names: &str| -> Vec<Option<char>> {
+        names
+            .chars()
+            .map(|c| Some(c).filter(|&c| c != '?'))
+            .collect()
+    };
+    assert_eq!(find_cycles(&keys("ababab"), 2), vec![cycle(0, 2, 3)]);
+    assert_eq!(find_cycles(&keys("ababab"), 1), vec![]);
+    assert_eq!(find_cycles(&keys("xaaaay"), 3), vec![cycle(1, 1, 4)]);
+    assert_eq!(
+        find_cycles(&keys("abcabcxyxy"), 3),
+        vec![cycle(0, 3, 2), cycle(6, 2, 2)]
+    );
+    // The longer cycle covers more than the repeated `a`
+    assert_eq!(find_cycles(&keys("aabaab"), 3), vec![cycle(0, 3, 2)]);
+    assert_eq!(find_cycles(&keys("a?a?a?"), 2), vec![]);
+    assert_eq!(find_cycles(&keys(""), 2), vec![]);
+    assert_eq!(find_cycles(&keys("aaa"), 0), vec![]);
+    assert_eq!(cycle(2, 2, 3).frames(), 2..8);
+}
+
+#[test]
+fn test_recursion_cycles() {
+    let bt: BT = &[
+        &["app::eval"],
+        &["app::apply", "app::eval"],
+        &["app::eval"],
+        &["app::apply", "app::eval"],
+        &["app::main"],
+    ];
+    assert_eq!(
+        recursion::recursion_cycles_impl(&bt, 4),
+        vec![RecursionCycle {
+            start: 0,
+            len: 2,
+            repeats: 2,
+        }]
+    );
+}
+
+#[test]
+fn test_format_collapse_recursion() {
+    let bt: BT = &[
+        &["app::inner"],
+        &["app::eval"],
+        &["app::apply"],
+        &["app::eval"],
+        &["app::apply"],
+        &["app::eval"],
+        &["app::apply"],
+        &["app::main"],
+    ];
+    let formatter = ShortBacktraceFormatter::new().collapse_recursion(2);
+    let out = formatter.format_impl(&bt);
+    assert!(
+        out.ends_with(
+            "
+   0:        0x0 - app::inner
+      (apply → eval) × 3
+   7:        0x0 - app::main"
+        ),
+        "{}",
+        out
+    );
+
+    let formatter = ShortBacktraceFormatter::new()
+        .style(FormatStyle::GdbLike)
+        .collapse_recursion(2);
+    assert!(formatter.format_impl(&bt).ends_with("\n#7  app::main ()"));
+
+    let formatter = ShortBacktraceFormatter::new().collapse_recursion(2);
+    assert_eq!(formatter.format_impl(&fake_trace()), FAKE_TRACE_FORMATTED);
+}
+
+#[test]
+fn test_short_frame_groups() {
+    let bt: BT = &[
+        &["app::inner"],
+        &["app::recurse"],
+        &["app::recurse"],
+        &["app::recurse"],
+        &["app::eval"],
+        &["app::apply"],
+        &["app::eval"],
+        &["app::apply"],
+        &[],
+        &[],
+        &["app::main"],
+    ];
+    let groups: Vec<_> = recursion::short_frame_groups_impl(&bt)
+        .map(|(frame, subframes, count)| (frame[subframes].to_vec(), count))
+        .collect();
+    assert_eq!(
+        groups,
+        vec![
+            (vec!["app::inner"], 1),
+            (vec!["app::recurse"], 3),
+            (vec!["app::eval"], 1),
+            (vec!["app::apply"], 1),
+            (vec!["app::eval"], 1),
+            (vec!["app::apply"], 1),
+            (vec![], 1),
+            (vec![], 1),
+            (vec!["app::main"], 1),
+        ]
+    );
+
+    // The same name at a different line isn't the same frame
+    let mut trace = fake_trace();
+    trace.insert(2, frame(0x28, vec![sym("app::inner", "src/inner.rs", 10)]));
+    trace.insert(3, frame(0x2c, vec![sym("app::inner", "src/inner.rs", 12)]));
+    trace[1].symbols.truncate(1);
+    let counts: Vec<_> = recursion::short_frame_groups_impl(&trace)
+        .map(|(_, _, count)| count)
+        .collect();
+    assert_eq!(counts, vec![2, 1, 1, 1, 1]);
+}
+
+#[test]
+fn test_format_collapse_repeated_frames() {
+    let bt: BT = &[
+        &["app::inner"],
+        &["app::recurse"],
+        &["app::recurse"],
+        &["app::recurse"],
+        &["app::eval"],
+        &["app::apply"],
+        &["app::eval"],
+        &["app::main"],
+    ];
+    let formatter = ShortBacktraceFormatter::new().collapse_repeated_frames(true);
+    assert_eq!(
+        formatter.format_impl(&bt),
+        "
+   0:        0x0 - app::inner
+   1:        0x0 - app::recurse
+      ... (repeated 2 times)
+   4:        0x0 - app::eval
+   5:        0x0 - app::apply
+   6:        0x0 - app::eval
+   7:        0x0 - app::main"
+    );
+
+    let formatter = ShortBacktraceFormatter::new()
+        .collapse_repeated_frames(true)
+        .frame_annotations(vec![(2, "here".to_owned())].into_iter().collect());
+    let out = formatter.format_impl(&bt);
+    assert!(out.contains("app::recurse\n   2:"), "{}", out);
+    assert!(
+        out.contains("\n      ... (repeated 1 times)\n   4:"),
+        "{}",
+        out
+    );
+
+    let formatter = ShortBacktraceFormatter::new().collapse_repeated_frames(true);
+    assert_eq!(formatter.format_impl(&fake_trace()), FAKE_TRACE_FORMATTED);
+}
+
+#[test]
+fn test_format_show_mangled() {
+    let bt: BT = &[&["_ZN3app4main17h5e3bd3c2a4f4e9f1E"]];
+    let formatter = ShortBacktraceFormatter::new().show_mangled(true);
+    assert_eq!(
+        formatter.format_impl(&bt),
+        "\n   0:        0x0 - _ZN3app4main17h5e3bd3c2a4f4e9f1E [_ZN3app4main17h5e3bd3c2a4f4e9f1E]"
+    );
+
+    let formatter = ShortBacktraceFormatter::new().show_mangled(false);
+    assert_eq!(formatter.format_impl(&fake_trace()), FAKE_TRACE_FORMATTED);
+}
+
+#[test]
+fn test_frames_by_directory() {
+    use std::path::PathBuf;
+
+    let bt = vec![
+        frame(
+            0x10,
+            vec![
+                sym("app::inner", "src/parse/inner.rs", 10),
+                sym("app::outer", "src/parse/outer.rs", 20),
+            ],
+        ),
+        frame(0x20, vec![sym("app::run", "src/parse/run.rs", 1)]),
+        frame(0x30, vec![unknown_sym()]),
+        frame(0x40, vec![]),
+        frame(0x50, vec![sym("app::main", "src/main.rs", 5)]),
+        frame(0x60, vec![sym("main", "main.rs", 6)]),
+    ];
+    let counts = stats::frames_by_directory_impl(&bt);
+    assert_eq!(counts.len(), 4);
+    assert_eq!(counts[&Some(PathBuf::from("src/parse"))], 2);
+    assert_eq!(counts[&Some(PathBuf::from("src"))], 1);
+    assert_eq!(counts[&Some(PathBuf::new())], 1);
+    assert_eq!(counts[&None], 2);
+}
+
+#[test]
+fn test_format_markdown() {
+    use markdown::format_short_backtrace_markdown_impl as markdown;
+
+    assert_eq!(
+        markdown(&fake_trace(), false),
+        format!("```text{}\n```\n", FAKE_TRACE_FORMATTED)
+    );
+    assert_eq!(
+        markdown(&fake_trace(), true),
+        format!(
+            "<details><summary>Backtrace</summary>\n\n```text{}\n```\n\n</details>\n",
+            FAKE_TRACE_FORMATTED
+        )
+    );
+
+    let bt: BT = &[&["app::weird````name"]];
+    assert!(markdown(&bt, false).starts_with("`````text\n"));
+    assert!(markdown(&bt, false).ends_with("\n`````\n"));
+}
+
+#[test]
+fn test_format_time_budget() {
+    use std::time::Duration;
+
+    let formatter = ShortBacktraceFormatter::new().time_budget(Duration::from_secs(0));
+    assert_eq!(
+        formatter.format_impl(&fake_trace()),
+        "\n... (time budget exceeded)"
+    );
+
+    let formatter = ShortBacktraceFormatter::new().time_budget(Duration::from_secs(3600));
+    assert_eq!(formatter.format_impl(&fake_trace()), FAKE_TRACE_FORMATTED);
+}
+
+#[test]
+fn test_format_annotated_full() {
+    let bt: BT = &[
+        &["std::panicking::begin_panic"],
+        &["std::rust_end_short_backtrace", "app::inner"],
+        &["app::main"],
+        &["std::rust_begin_short_backtrace"],
+        &[],
+    ];
+    assert_eq!(
+        diagnostics::format_annotated_full_impl(&bt),
+        "     0: 0x0 - std::panicking::begin_panic
+     1: 0x0 - std::rust_end_short_backtrace  <-- end marker
+>           - app::inner
+>    2: 0x0 - app::main
+     3: 0x0 - std::rust_begin_short_backtrace  <-- begin marker
+     4: 0x0 - <unresolved>"
+    );
+}
+
+#[test]
+fn test_common_suffix_len() {
+    use stats::common_suffix_len_impl as common_suffix_len;
+
+    let a: BT = &[
+        &["app::parse"],
+        &["app::handle", "app::route"],
+        &["app::main"],
+    ];
+    let b: BT = &[
+        &["app::render"],
+        &["app::handle", "app::route"],
+        &["app::main"],
+    ];
+    let c: BT = &[&["app::handle"], &["app::main"]];
+    assert_eq!(common_suffix_len(&a, &b), 2);
+    assert_eq!(common_suffix_len(&a, &a), 3);
+    // `app::route` was inlined into the frame in `a`, but not in `c`
+    assert_eq!(common_suffix_len(&a, &c), 1);
+    assert_eq!(common_suffix_len(&a, &&[][..]), 0);
+
+    let a = vec![
+        frame(0x10, vec![sym("app::parse", "a.rs", 1)]),
+        frame(0x20, vec![]),
+    ];
+    assert_eq!(common_suffix_len(&a, &a), 0);
+}
+
+#[test]
+fn test_new_frames_since() {
+    let names = |old: BT, new: BT| -> Vec<&str> {
+        stats::new_frames_since_impl(&old, &new)
+            .map(|(frame, _)| frame[0])
+            .collect()
+    };
+    let old: BT = &[&["app::handle"], &["app::main"]];
+    let new: BT = &[
+        &["app::decode"],
+        &["app::parse"],
+        &["app::handle"],
+        &["app::main"],
+    ];
+    assert_eq!(names(old, new), ["app::decode", "app::parse"]);
+    assert_eq!(names(new, old), Vec::<&str>::new());
+    assert_eq!(names(new, new), Vec::<&str>::new());
+    // Returned to `main` and called something else
+    let other: BT = &[&["app::render"], &["app::main"]];
+    assert_eq!(names(old, other), ["app::render"]);
+    assert_eq!(names(&[], other), ["app::render", "app::main"]);
+}
+
+#[test]
+fn test_format_index_base() {
+    let bt: BT = &[&["app::inner", "app::outer"], &["app::main"]];
+    let formatter = ShortBacktraceFormatter::new().index_base(1);
+    assert_eq!(
+        formatter.format_impl(&bt),
+        "
+   1:        0x0 - app::inner
+                 - app::outer
+   2:        0x0 - app::main"
+    );
+
+    let formatter = formatter.style(FormatStyle::GdbLike);
+    assert_eq!(
+        formatter.format_impl(&bt),
+        "\n#1  app::inner ()\n#2  app::outer ()\n#3  app::main ()"
+    );
+
+    let formatter = ShortBacktraceFormatter::new().index_base(0);
+    assert_eq!(formatter.format_impl(&fake_trace()), FAKE_TRACE_FORMATTED);
+}
+
+#[test]
+fn test_short_backtrace_names() {
+    assert_eq!(
+        summary::short_backtrace_names_impl(&fake_trace()),
+        vec!["app::inner", "app::outer", "app::main"]
+    );
+
+    let bt: BT = &[&["core::panicking::panic_fmt"], &["app::main"]];
+    assert_eq!(summary::short_backtrace_names_impl(&bt), vec!["app::main"]);
+}
+
+#[test]
+fn test_is_macro_frame() {
+    let frame = frame(
+        0x10,
+        vec![
+            sym("app::Foo::deserialize", "<proc-macro source code>", 1),
+            unknown_sym(),
+            sym("app::main", "src/main.rs", 5),
+        ],
+    );
+    assert!(classify::is_macro_frame_impl(&frame, 0..2));
+    assert!(!classify::is_macro_frame_impl(&frame, 0..3));
+    assert!(!classify::is_macro_frame_impl(&frame, 1..2));
+    assert!(!classify::is_macro_frame_impl(&frame, 0..0));
+}
+
+#[test]
+fn test_format_macro_frames() {
+    let bt = vec![
+        frame(0x10, vec![sym("app::inner", "src/inner.rs", 10)]),
+        frame(
+            0x20,
+            vec![sym("app::Foo::deserialize", "<proc-macro source code>", 1)],
+        ),
+        frame(0x30, vec![sym("app::main", "src/main.rs", 5)]),
+    ];
+    let formatter = ShortBacktraceFormatter::new().macro_frames(MacroFramePolicy::Annotate);
+    assert_eq!(
+        formatter.format_impl(&bt),
+        "
+   0:       0x10 - app::inner
+                at src/inner.rs:10
+   1:       0x20 - app::Foo::deserialize
+                at <proc-macro source code>:1
+                (macro)
+   2:       0x30 - app::main
+                at src/main.rs:5"
+    );
+
+    let formatter = ShortBacktraceFormatter::new().macro_frames(MacroFramePolicy::Hide);
+    assert_eq!(
+        formatter.format_impl(&bt),
+        "
+   0:       0x10 - app::inner
+                at src/inner.rs:10
+   2:       0x30 - app::main
+                at src/main.rs:5"
+    );
+
+    let formatter = ShortBacktraceFormatter::new().macro_frames(MacroFramePolicy::Show);
+    assert!(formatter.format_impl(&bt).contains("app::Foo::deserialize"));
+}
+
+#[test]
+fn test_format_module_names() {
+    let bt = vec![
+        FakeFrame {
+            ip: 0x1234,
+            symbol_address: 0x1200,
+            module_base: Some(0x1000),
+            symbols: vec![sym("app::inner", "src/inner.rs", 10)],
+        },
+        FakeFrame {
+            ip: 0x5678,
+            symbol_address: 0x5600,
+            module_base: Some(0x5000),
+            symbols: vec![sym("app::main", "src/main.rs", 5)],
+        },
+    ];
+    let mut names = HashMap::new();
+    names.insert(0x1000, "libapp.so".to_owned());
+    let formatter = ShortBacktraceFormatter::new().module_names(names);
+    assert_eq!(
+        formatter.format_impl(&bt),
+        "
+   0: libapp.so+0x234 - app::inner
+                at src/inner.rs:10
+   1:     0x5678 - app::main
+                at src/main.rs:5"
+    );
+}
+
+#[test]
+fn test_reaches_main() {
+    use query::reaches_main_impl as reaches_main;
+
+    let bt: BT = &[
+        &["std::rust_end_short_backtrace"],
+        &["app::main"],
+        &["std::rust_begin_short_backtrace"],
+        &["std::rt::lang_start::{{closure}}"],
+        &["main"],
+    ];
+    assert!(reaches_main(&bt));
+
+    let bt: BT = &[
+        &["std::rust_end_short_backtrace"],
+        &["app::worker"],
+        &["std::rust_begin_short_backtrace"],
+        &["std::thread::Builder::spawn_unchecked_::{{closure}}"],
+        &["start_thread"],
+    ];
+    assert!(!reaches_main(&bt));
+
+    // Without a begin marker the short backtrace runs to the end
+    let bt: BT = &[&["app::main"], &["main"]];
+    assert!(reaches_main(&bt));
+    let bt: BT = &[&["app::main"], &["std::rt::lang_start_internal"]];
+    assert!(!reaches_main(&bt));
+
+    // Anything after the start of a thread isn't the main thread's `main`
+    let bt: BT = &[
+        &["app::worker"],
+        &["std::rust_begin_short_backtrace"],
+        &["std::thread::lifecycle::spawn_unchecked::{{closure}}"],
+        &["main"],
+    ];
+    assert!(!reaches_main(&bt));
+}
+
+#[test]
+fn test_thread_entry_frame() {
+    let entry = |bt: BT| query::thread_entry_frame_impl(&bt).map(|(frame, idx)| frame[idx]);
+    let bt: BT = &[
+        &["app::worker"],
+        &["std::rust_begin_short_backtrace"],
+        &["std::thread::Builder::spawn_unchecked_::{{closure}}"],
+        &["std::sys::unix::thread::Thread::new::thread_start"],
+        &["start_thread"],
+    ];
+    assert_eq!(
+        entry(bt),
+        Some("std::thread::Builder::spawn_unchecked_::{{closure}}")
+    );
+    let bt: BT = &[
+        &["app::worker"],
+        &["std::rust_begin_short_backtrace"],
+        &["<std::sys::thread::unix::Thread>::new::thread_start"],
+    ];
+    assert_eq!(
+        entry(bt),
+        Some("<std::sys::thread::unix::Thread>::new::thread_start")
+    );
+    let bt: BT = &[
+        &["app::main"],
+        &["std::rust_begin_short_backtrace"],
+        &["std::rt::lang_start::{{closure}}"],
+        &["main"],
+    ];
+    assert_eq!(entry(bt), None);
+
+    let bt: BT = &[
+        &["app::worker"],
+        &["std::rust_begin_short_backtrace"],
+        &["std::thread::lifecycle::spawn_unchecked::{{closure}}"],
+    ];
+    let formatter = ShortBacktraceFormatter::new().mark_thread_entry(true);
+    assert_eq!(
+        formatter.format_impl(&bt),
+        "\n   0:        0x0 - app::worker\n(thread entry)"
+    );
+    let bt: BT = &[&["app::main"], &["main"]];
+    assert!(!formatter.format_impl(&bt).contains("(thread entry)"));
+
+    let spawned = std::thread::spawn(|| thread_entry_frame(&Backtrace::new()).is_some());
+    assert!(spawned.join().unwrap());
+}
+
+#[test]
+fn test_streaming_parser() {
+    let formatter = ShortBacktraceFormatter::new().mark_catch_unwind(true);
+    let first = formatter.format_impl(&catch_unwind_trace());
+    let second = FAKE_TRACE_FORMATTED;
+    let log = format!(
+        "starting\npanicked: oh no{}{}\nand again{}\nexiting",
+        first, second, second
+    );
+
+    let mut parser = StreamingParser::new();
+    let mut traces = vec![];
+    for line in log.lines() {
+        traces.extend(parser.push_line(line));
+    }
+    assert!(parser.finish().is_none());
+
+    let expected = |text: &str| ParsedShortBacktrace {
+        frames: parse_formatted(text),
+    };
+    assert_eq!(
+        traces,
+        vec![expected(&first), expected(second), expected(second)]
+    );
+
+    // A trace at the very end of the stream
+    parser.push_line("   0: 0x20 - app::main");
+    assert_eq!(parser.finish().unwrap().frames.len(), 1);
+    assert!(parser.finish().is_none());
+}
+
+#[test]
+fn test_format_subframe_order() {
+    let bt: BT = &[&["app::inner", "app::middle", "app::outer"], &["app::main"]];
+    let formatter = ShortBacktraceFormatter::new().subframe_order(SubframeOrder::OutermostFirst);
+    assert_eq!(
+        formatter.format_impl(&bt),
+        "
+   0:        0x0 - app::outer
+                 - app::middle
+                 - app::inner
+   1:        0x0 - app::main"
+    );
+
+    let formatter = formatter
+        .inline_policy(InlinePolicy::InnermostOnly)
+        .style(FormatStyle::GdbLike);
+    assert_eq!(
+        formatter.format_impl(&bt),
+        "\n#0  app::inner ()\n#1  app::main ()"
+    );
+
+    let formatter = ShortBacktraceFormatter::new().subframe_order(SubframeOrder::InnermostFirst);
+    assert_eq!(formatter.format_impl(&fake_trace()), FAKE_TRACE_FORMATTED);
+}
+
+#[test]
+fn test_format_panic_report() {
+    let report = report::format_panic_report_impl(
+        Some("main"),
+        "oh no",
+        Some(("src/main.rs", 17, 5)),
+        &fake_trace(),
+    );
+    assert_eq!(
+        report,
+        format!(
+            "thread 'main' panicked at src/main.rs:17:5:\noh no\nstack backtrace:{}",
+            FAKE_TRACE_FORMATTED
+        )
+    );
+
+    let report = report::format_panic_report_impl(None, "oh no", None, &fake_trace());
+    assert!(report.starts_with("thread '<unnamed>' panicked:\noh no\n"));
+}
+
+#[test]
+fn test_panic_message() {
+    let payload = std::panic::catch_unwind(|| panic!("static")).unwrap_err();
+    assert_eq!(report::panic_message(&*payload), "static");
+    let payload = std::panic::catch_unwind(|| panic!("formatted {}", 1)).unwrap_err();
+    assert_eq!(report::panic_message(&*payload), "formatted 1");
+    let payload = std::panic::catch_unwind(|| std::panic::panic_any(1)).unwrap_err();
+    assert_eq!(report::panic_message(&*payload), "Box<dyn Any>");
+}
+
+#[test]
+fn test_short_frames_exclude_ip_range() {
+    let trace = fake_trace();
+    let ips = |range| -> Vec<usize> {
+        gunk::short_frames_exclude_ip_range_impl(&trace, range)
+            .map(|(frame, _)| frame.ip)
+            .collect()
+    };
+    assert_eq!(ips(0x30..0x50), vec![0x20, 0x50]);
+    assert_eq!(ips(0x30..0x31), vec![0x20, 0x40, 0x50]);
+    assert_eq!(ips(0..0), vec![0x20, 0x30, 0x40, 0x50]);
+    assert!(ips(0..usize::MAX).is_empty());
+}
+
+#[test]
+fn test_short_backtrace_bytes() {
+    let trace = vec![
+        frame(
+            0x20,
+            vec![
+                sym("app::inner", "src/lib.rs", 10),
+                sym("app::outer", "src/lib.rs", 20),
+            ],
+        ),
+        frame(0x30, vec![unknown_sym()]),
+        frame(0x40, vec![]),
+        frame(usize::MAX, vec![sym("app::inner", "src/lib.rs", u32::MAX)]),
+        frame(0x50, vec![sym("app::main", "src/main.rs", 5)]),
+    ];
+    let bytes = binary::short_backtrace_to_bytes_impl(&trace);
+    let expected: Vec<_> = resolved::resolved_short_symbols_impl(&trace).collect();
+    assert_eq!(binary::short_backtrace_from_bytes(&bytes), Some(expected));
+
+    // Each name and file is only stored once
+    let count = |needle: &[u8]| bytes.windows(needle.len()).filter(|w| *w == needle).count();
+    assert_eq!(count(b"app::inner"), 1);
+    assert_eq!(count(b"src/lib.rs"), 1);
+
+    for len in 0..bytes.len() {
+        assert_eq!(binary::short_backtrace_from_bytes(&bytes[..len]), None);
+    }
+    let mut extra = bytes.clone();
+    extra.push(0);
+    assert_eq!(binary::short_backtrace_from_bytes(&extra), None);
+    let mut newer = bytes;
+    newer[0] += 1;
+    assert_eq!(binary::short_backtrace_from_bytes(&newer), None);
+
+    let empty: BT = &[];
+    let bytes = binary::short_backtrace_to_bytes_impl(&empty);
+    assert_eq!(binary::short_backtrace_from_bytes(&bytes), Some(vec![]));
+}
+
+#[test]
+fn test_short_frames_strict_with_markers() {
+    let names = |bt: BT| -> Vec<&str> {
+        adapters::short_frames_strict_with_markers_impl(&bt, "shim::enter_user", "shim::run_user")
+            .flat_map(|(frame, range)| frame[range].to_vec())
+            .collect()
+    };
+    let bt: BT = &[
+        &["rust_end_short_backtrace"],
+        &["shim::enter_user"],
+        &["shim::enter_user", "app::inner"],
+        &["app::outer"],
+        &["shim::run_user"],
+        &["shim::run_user"],
+        &["rust_begin_short_backtrace"],
+    ];
+    // The closest pair wins, and the std markers are just frames
+    assert_eq!(names(bt), vec!["app::inner", "app::outer"]);
+
+    let bt: BT = &[&["shim::run_user"], &["app::main"], &["shim::enter_user"]];
+    assert_eq!(
+        names(bt),
+        vec!["shim::run_user", "app::main", "shim::enter_user"]
+    );
+
+    let bt: BT = &[&["shim::enter_user"], &["app::main"], &["tokio::run"]];
+    assert_eq!(names(bt), vec!["app::main", "tokio::run"]);
+
+    // The std names give the same result as short_frames_strict
+    let trace = fake_trace();
+    let with_markers: Vec<_> = adapters::short_frames_strict_with_markers_impl(
+        &trace,
+        "rust_end_short_backtrace",
+        "rust_begin_short_backtrace",
+    )
+    .map(|(frame, range)| (frame.ip, range))
+    .collect();
+    let strict: Vec<_> = short_frames_strict_impl(&trace)
+        .map(|(frame, range)| (frame.ip, range))
+        .collect();
+    assert_eq!(with_markers, strict);
+}
+
+#[test]
+fn test_short_frames_between() {
+    let between = |bt: BT, outer, inner| -> Vec<&'static str> {
+        adapters::short_frames_between_impl(&bt, outer, inner)
+            .flat_map(|(frame, range)| frame.symbols()[range].iter().copied())
+            .collect()
+    };
+    let bt: BT = &[
+        &["app::parse"],
+        &["app::validate", "app::decode"],
+        &["app::route"],
+        &["app::handle"],
+        &["app::main"],
+    ];
+    assert_eq!(
+        between(bt, "handle", "parse"),
+        ["app::validate", "app::decode", "app::route"]
+    );
+    // The inlined symbols next to a boundary are kept
+    assert_eq!(between(bt, "route", "validate"), ["app::decode"]);
+    assert_eq!(
+        between(bt, "handle", "missing"),
+        ["app::parse", "app::validate", "app::decode", "app::route"]
+    );
+    assert_eq!(between(bt, "missing", "handle"), ["app::main"]);
+    assert_eq!(between(bt, "route", "decode"), Vec::<&str>::new());
+    // `outer` has to be older than `inner`
+    assert_eq!(between(bt, "parse", "route"), ["app::handle", "app::main"]);
+
+    // With recursion, the pair that's closest together wins
+    let bt: BT = &[
+        &["app::inner"],
+        &["app::outer"],
+        &["app::inner"],
+        &["app::mid"],
+        &["app::outer"],
+    ];
+    assert_eq!(between(bt, "outer", "inner"), Vec::<&str>::new());
+}
+
+#[test]
+fn test_estimate_formatted_len() {
+    let estimate = format::estimate_formatted_len_impl(&fake_trace());
+    let actual = FAKE_TRACE_FORMATTED.len();
+    assert!(
+        actual <= estimate && estimate <= actual * 3 / 2,
+        "{} vs {}",
+        estimate,
+        actual
+    );
+
+    let empty: BT = &[];
+    assert_eq!(format::estimate_formatted_len_impl(&empty), 0);
+}
+
+#[test]
+fn test_format_single_line() {
+    let formatter = ShortBacktraceFormatter::new().style(FormatStyle::SingleLine);
+    assert_eq!(
+        formatter.format_impl(&fake_trace()),
+        "
+0: app::inner (src/inner.rs:10)
+1: <unknown>
+2: <unresolved>
+3: app::main (src/main.rs:5)"
+    );
+
+    let formatter = formatter
+        .inline_policy(InlinePolicy::OutermostOnly)
+        .show_columns(true)
+        .index_base(1);
+    let bt = vec![frame(
+        0x10,
+        vec![
+            FakeSymbol {
+                col: Some(7),
+                ..sym("app::inner", "src/inner.rs", 10)
+            },
+            sym("app::outer", "src/lib.rs", 20),
+        ],
+    )];
+    assert_eq!(
+        formatter.format_impl(&bt),
+        "\n1: app::inner (src/inner.rs:10:7)"
+    );
+}
+
+#[test]
+fn test_is_truncated() {
+    use diagnostics::is_truncated_impl as is_truncated;
+
+    let bt: BT = &[&["app::main"], &["std::rust_begin_short_backtrace"]];
+    assert!(!is_truncated(&bt));
+    let bt: BT = &[
+        &["app::main"],
+        &["main"],
+        &["__libc_start_main"],
+        &["_start"],
+    ];
+    assert!(!is_truncated(&bt));
+    let bt: BT = &[&["app::worker"], &["start_thread"], &["clone"]];
+    assert!(!is_truncated(&bt));
+    let bt: BT = &[&["app::parse"], &["app::parse"], &["app::parse"]];
+    assert!(is_truncated(&bt));
+    // Only the oldest frames count
+    let bt: BT = &[
+        &["main"],
+        &["app::parse"],
+        &["app::parse"],
+        &["app::parse"],
+        &["app::parse"],
+    ];
+    assert!(is_truncated(&bt));
+    let bt: BT = &[];
+    assert!(!is_truncated(&bt));
+}
+
+#[test]
+fn test_format_demangler() {
+    let bt: BT = &[&["_ZN3foo3barEv"], &["_ZN3foo5otherEv"], &["app::main"]];
+    let formatter = ShortBacktraceFormatter::new().demangler(|raw| match raw {
+        "_ZN3foo3barEv" => Some("foo::bar()".to_owned()),
+        "app::main" => panic!("Rust names shouldn't be demangled again"),
+        _ => None,
+    });
+    assert_eq!(
+        formatter.format_impl(&bt),
+        "
+   0:        0x0 - foo::bar()
+   1:        0x0 - _ZN3foo5otherEv
+   2:        0x0 - app::main"
+    );
+    assert!(format!("{:?}", formatter).contains("Demangler(..)"));
+}
+
+#[test]
+fn test_short_regions() {
+    let regions = |bt: BT| -> Vec<Vec<&'static str>> {
+        diagnostics::short_regions_impl(&bt)
+            .into_iter()
+            .map(|region| {
+                region
+                    .flat_map(|(frame, range)| frame.symbols()[range].iter().copied())
+                    .collect()
+            })
+            .collect()
+    };
+    let bt: BT = &[
+        &["core::panicking::panic_fmt"],
+        &["std::rust_end_short_backtrace"],
+        &["app::task"],
+        &["std::rust_begin_short_backtrace", "executor::poll"],
+        &["executor::rust_end_short_backtrace"],
+        &["executor::run"],
+        &["executor::rust_begin_short_backtrace"],
+        &["main"],
+    ];
+    assert_eq!(regions(bt), vec![vec!["app::task"], vec!["executor::run"]]);
+
+    // Frames outside of any pair aren't in a region
+    let bt: BT = &[
+        &["std::rust_end_short_backtrace"],
+        &["app::task"],
+        &["std::rust_begin_short_backtrace"],
+        &["app::between"],
+        &["std::rust_begin_short_backtrace"],
+    ];
+    assert_eq!(regions(bt), vec![vec!["app::task"]]);
+
+    let bt: BT = &[&["app::main"]];
+    assert!(regions(bt).is_empty());
+    let bt: BT = &[&["rust_end_short_backtrace_and_rust_begin_short_backtrace"]];
+    assert!(regions(bt).is_empty());
+}
+
+#[test]
+fn test_dedup_printer() {
+    let bt: BT = &[&["app::parse"], &["app::main"]];
+    let other: BT = &[&["app::other"], &["app::main"]];
+
+    let printer = DedupPrinter::new();
+    assert_eq!(
+        printer.format_impl(&bt).as_deref(),
+        Some(
+            "
+   0:        0x0 - app::parse
+   1:        0x0 - app::main"
+        )
+    );
+    assert_eq!(printer.format_impl(&bt), None);
+    assert!(printer.format_impl(&other).is_some());
+    printer.clear();
+    assert!(printer.format_impl(&bt).is_some());
+
+    let printer = DedupPrinter::new()
+        .formatter(ShortBacktraceFormatter::new().style(FormatStyle::SingleLine))
+        .report_repeats(true);
+    assert_eq!(
+        printer.format_impl(&bt).as_deref(),
+        Some("\n0: app::parse\n1: app::main")
+    );
+    assert_eq!(
+        printer.format_impl(&bt).as_deref(),
+        Some("\n(backtrace repeated 1 times)")
+    );
+    assert_eq!(
+        printer.format_impl(&bt).as_deref(),
+        Some("\n(backtrace repeated 2 times)")
+    );
+
+    fn assert_sync<T: Send + Sync>() {}
+    assert_sync::<DedupPrinter>();
+}
+
+#[test]
+#[cfg(feature = "source-snippets")]
+fn test_source_snippet() {
+    let license = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/LICENSE-MIT"));
+    assert_eq!(
+        source_snippet(license, 1, 1).as_deref(),
+        Some("> 1 | Copyright (c) 2023 Axo Developer Co.\n  2 |")
+    );
+    assert_eq!(
+        source_snippet(license, 11, 1).as_deref(),
+        Some(
+            "  10 | is furnished to do so, subject to the following
+> 11 | conditions:
+  12 |"
+        )
+    );
+    assert_eq!(source_snippet(license, 0, 1), None);
+    assert_eq!(source_snippet(license, 10_000, 1), None);
+    assert_eq!(
+        source_snippet(Path::new("/nonexistent/file.rs"), 1, 1),
+        None
+    );
+
+    let bt: FakeBT = vec![
+        frame(
+            0x10,
+            vec![sym(
+                "app::parse",
+                concat!(env!("CARGO_MANIFEST_DIR"), "/LICENSE-MIT"),
+                1,
+            )],
+        ),
+        frame(0x20, vec![sym("app::main", "/nonexistent/main.rs", 5)]),
+    ];
+    let out = ShortBacktraceFormatter::new()
+        .source_context(1)
+        .format_impl(&bt);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(
+        lines[3..],
+        [
+            "                  > 1 | Copyright (c) 2023 Axo Developer Co.",
+            "                    2 |",
+            "   1:       0x20 - app::main",
+            "                at /nonexistent/main.rs:5",
+        ]
+    );
+}
+
+#[test]
+fn test_short_frames_with_counts() {
+    let bt: BT = &[
+        &[
+            "core::panicking::panic_fmt",
+            "std::rust_end_short_backtrace",
+            "app::parse",
+        ],
+        &[],
+        &["app::handle", "app::main"],
+        &["app::run", "std::rust_begin_short_backtrace"],
+    ];
+    let counts: Vec<usize> = adapters::short_frames_with_counts_impl(&bt)
+        .map(|(_, count)| count)
+        .collect();
+    assert_eq!(counts, vec![1, 0, 2, 1]);
+}
+
+#[test]
+fn test_short_frames_in_line_range() {
+    let bt: FakeBT = vec![
+        frame(
+            0x10,
+            vec![sym("std::rust_end_short_backtrace", "std.rs", 1)],
+        ),
+        frame(
+            0x20,
+            vec![
+                sym("app::inner", "/home/me/app/src/lib.rs", 10),
+                sym("app::middle", "/home/me/app/src/other.rs", 12),
+                sym("app::outer", "/home/me/app/src/lib.rs", 20),
+            ],
+        ),
+        frame(0x30, vec![unknown_sym()]),
+        frame(0x40, vec![sym("app::main", "/home/me/app/src/lib.rs", 30)]),
+        frame(
+            0x50,
+            vec![sym("std::rust_begin_short_backtrace", "src/lib.rs", 15)],
+        ),
+    ];
+    let matches = |file: &'static str, lines: Range<u32>| -> Vec<(usize, usize)> {
+        query::short_frames_in_line_range_impl(&bt, Path::new(file), lines)
+            .map(|(frame, subframe_idx)| (frame.ip, subframe_idx))
+            .collect()
+    };
+    assert_eq!(
+        matches("src/lib.rs", 10..31),
+        vec![(0x20, 0), (0x20, 2), (0x40, 0)]
+    );
+    assert_eq!(matches("lib.rs", 11..30), vec![(0x20, 2)]);
+    assert_eq!(matches("other.rs", 0..100), vec![(0x20, 1)]);
+    assert_eq!(matches("b.rs", 0..100), vec![]);
+    // Outside of the short backtrace
+    assert_eq!(matches("std.rs", 0..100), vec![]);
+}
+
+#[test]
+fn test_short_backtrace_id() {
+    // This has to stay the same between releases, so it's pinned
+    assert_eq!(
+        summary::short_backtrace_id_impl(&fake_trace(), UnknownFramePolicy::Skip),
+        "e46b7ea5"
+    );
+
+    let bt: BT = &[&["app::inner", "app::outer"], &["app::main"]];
+    let moved: BT = &[&["app::inner"], &["app::outer", "app::main"]];
+    let gunky: BT = &[
+        &["core::panicking::panic_fmt"],
+        &["app::inner", "app::outer"],
+        &["app::main"],
+    ];
+    let other: BT = &[&["app::inner"], &["app::main"]];
+    let id = |bt: BT| summary::short_backtrace_id_impl(&bt, UnknownFramePolicy::Skip);
+    assert_eq!(id(bt), "e46b7ea5");
+    assert_eq!(id(moved), id(bt));
+    assert_eq!(id(gunky), id(bt));
+    assert_ne!(id(other), id(bt));
+
+    let split: BT = &[&["ab"], &["c"]];
+    let joined: BT = &[&["a"], &["bc"]];
+    assert_ne!(id(split), id(joined));
+}
+
+#[test]
+fn test_unknown_frame_policy() {
+    use summary::fingerprint_names_impl as names;
+    use UnknownFramePolicy::{Placeholder, Skip};
+
+    let bt = fake_trace();
+    assert_eq!(
+        names(&bt, Skip),
+        vec!["app::inner", "app::outer", "app::main"]
+    );
+    assert_eq!(
+        names(&bt, Placeholder),
+        vec![
+            "app::inner",
+            "app::outer",
+            "<unknown>",
+            "<unknown>",
+            "app::main"
+        ]
+    );
+
+    // Stripping a frame only changes the key with placeholders
+    let stripped: FakeBT = vec![
+        frame(0x20, vec![sym("app::inner", "src/inner.rs", 10)]),
+        frame(0x30, vec![unknown_sym()]),
+        frame(0x50, vec![sym("app::main", "src/main.rs", 5)]),
+    ];
+    let resolved: FakeBT = vec![
+        frame(0x20, vec![sym("app::inner", "src/inner.rs", 10)]),
+        frame(0x50, vec![sym("app::main", "src/main.rs", 5)]),
+    ];
+    let other_stripped: FakeBT = vec![
+        frame(0x20, vec![sym("app::inner", "src/inner.rs", 10)]),
+        frame(0x40, vec![]),
+        frame(0x50, vec![sym("app::main", "src/main.rs", 5)]),
+    ];
+    let id = summary::short_backtrace_id_impl;
+    assert_eq!(id(&stripped, Skip), id(&resolved, Skip));
+    assert_ne!(id(&stripped, Placeholder), id(&resolved, Placeholder));
+    // But it's stable for the same stripped backtrace
+    assert_eq!(id(&stripped, Placeholder), id(&other_stripped, Placeholder));
+
+    let printer = DedupPrinter::new();
+    assert!(printer.format_impl(&stripped).is_some());
+    assert!(printer.format_impl(&resolved).is_none());
+    let printer = DedupPrinter::new().unknown_frames(Placeholder);
+    assert!(printer.format_impl(&stripped).is_some());
+    assert!(printer.format_impl(&resolved).is_some());
+    assert!(printer.format_impl(&other_stripped).is_none());
+}
+
+#[test]
+fn test_alloc_failure() {
+    let oom: BT = &[
+        &["std::alloc::rust_oom"],
+        &["alloc::alloc::handle_alloc_error"],
+        &["alloc::raw_vec::RawVec<T,A>::grow"],
+        &["app::main"],
+    ];
+    assert!(gunk::is_alloc_failure_impl(&oom));
+    let panic: BT = &[&["core::panicking::panic_fmt"], &["app::main"]];
+    assert!(!gunk::is_alloc_failure_impl(&panic));
+
+    let formatter = ShortBacktraceFormatter::new()
+        .mark_alloc_failure(true)
+        .header(Some("stack backtrace:".into()));
+    let out = formatter.format_impl(&oom);
+    assert!(out.starts_with("\nstack backtrace:\n(allocation failure)\n   0:"));
+    assert_eq!(
+        formatter.format_impl(&panic),
+        "
+stack backtrace:
+   0:        0x0 - core::panicking::panic_fmt
+   1:        0x0 - app::main"
+    );
+}
+
+#[test]
+fn test_abort_kind() {
+    let abort: BT = &[
+        &["std::sys::pal::unix::abort_internal"],
+        &["std::process::abort"],
+        &["app::main"],
+    ];
+    assert_eq!(gunk::abort_kind_impl(&abort), Some(AbortKind::Abort));
+
+    // A destructor panicking while the first panic unwinds
+    let double: BT = &[
+        &["std::panicking::begin_panic_handler"],
+        &["std::panicking::rust_panic_with_hook"],
+        &["<app::Guard as core::ops::drop::Drop>::drop"],
+        &["core::ptr::drop_in_place<app::Guard>"],
+        &["std::panicking::rust_panic_with_hook"],
+        &["app::main"],
+    ];
+    assert_eq!(
+        gunk::abort_kind_impl(&double),
+        Some(AbortKind::PanicWhilePanicking)
+    );
+    let nounwind: BT = &[
+        &["core::panicking::panic_nounwind_nobacktrace"],
+        &["core::panicking::panic_in_cleanup"],
+        &["app::main"],
+        &["core::intrinsics::abort"],
+    ];
+    assert_eq!(
+        gunk::abort_kind_impl(&nounwind),
+        Some(AbortKind::PanicWhilePanicking)
+    );
+
+    let panic: BT = &[
+        &["std::panicking::rust_panic_with_hook"],
+        &["core::panicking::panic_fmt"],
+        &["app::main"],
+    ];
+    assert_eq!(gunk::abort_kind_impl(&panic), None);
+
+    let formatter = ShortBacktraceFormatter::new().mark_abort(true);
+    assert!(formatter
+        .format_impl(&abort)
+        .starts_with("\n(abort)\n   0:"));
+    assert!(formatter
+        .format_impl(&double)
+        .starts_with("\n(abort: panic while panicking)\n   0:"));
+    assert!(!formatter.format_impl(&panic).contains("(abort"));
+}
+
+#[test]
+fn test_format_workspace_root() {
+    let bt: FakeBT = vec![
+        frame(
+            0x10,
+            vec![sym("app::parse", "/home/me/app/src/parse.rs", 10)],
+        ),
+        frame(
+            0x20,
+            vec![sym("dep::run", "/home/me/.cargo/dep/src/lib.rs", 20)],
+        ),
+        frame(
+            0x30,
+            vec![sym("app::main", "/home/me/application/main.rs", 5)],
+        ),
+    ];
+    let formatter = ShortBacktraceFormatter::new().workspace_root("/home/me/app");
+    assert_eq!(
+        formatter.format_impl(&bt),
+        "
+   0:       0x10 - app::parse
+                at src/parse.rs:10
+   1:       0x20 - dep::run
+                at /home/me/.cargo/dep/src/lib.rs:20
+   2:       0x30 - app::main
+                at /home/me/application/main.rs:5"
+    );
+    assert_eq!(
+        formatter
+            .clone()
+            .style(FormatStyle::GdbLike)
+            .format_impl(&bt)
+            .lines()
+            .nth(1),
+        Some("#0  app::parse at src/parse.rs:10")
+    );
+    assert_eq!(
+        formatter
+            .style(FormatStyle::SingleLine)
+            .format_impl(&bt)
+            .lines()
+            .nth(1),
+        Some("0: app::parse (src/parse.rs:10)")
+    );
+}
+
+#[test]
+fn test_short_frames_with_position() {
+    use adapters::FramePosition::*;
+
+    let positions = |bt: BT| -> Vec<adapters::FramePosition> {
+        adapters::short_frames_with_position_impl(&bt)
+            .map(|(_, _, position)| position)
+            .collect()
+    };
+    let bt: BT = &[
+        &["std::rust_end_short_backtrace"],
+        &["app::parse"],
+        &["app::handle"],
+        &["app::main"],
+        &["std::rust_begin_short_backtrace"],
+    ];
+    assert_eq!(positions(bt), vec![First, Interior, Last]);
+    let bt: BT = &[&["app::parse"], &["app::main"]];
+    assert_eq!(positions(bt), vec![First, Last]);
+    let bt: BT = &[&["app::main"]];
+    assert_eq!(positions(bt), vec![Only]);
+    let bt: BT = &[];
+    assert_eq!(positions(bt), vec![]);
+}
+
+#[test]
+fn test_short_backtrace_tree() {
+    let group = |crate_name: Option<&str>, frames: Range<usize>| classify::CrateGroup {
+        crate_name: crate_name.map(str::to_owned),
+        frames,
+    };
+    let bt: BT = &[
+        &["core::panicking::panic_fmt"],
+        &["core::option::Option<T>::unwrap", "app::parse"],
+        &["app::handle"],
+        &["tokio::runtime::poll"],
+        &["<tokio::Foo as core::future::Future>::poll"],
+        &["malloc"],
+        &[],
+        &["app::main"],
+    ];
+    assert_eq!(
+        classify::short_backtrace_tree_impl(&bt),
+        vec![
+            group(Some("core"), 0..1),
+            group(Some("app"), 1..3),
+            group(Some("tokio"), 3..5),
+            group(None, 5..7),
+            group(Some("app"), 7..8),
+        ]
+    );
+    let bt: BT = &[];
+    assert_eq!(classify::short_backtrace_tree_impl(&bt), vec![]);
+}
+
+#[test]
+fn test_short_frames_trim_tail() {
+    let trimmed = |bt: BT, k: usize| -> Vec<Vec<&'static str>> {
+        adapters::short_frames_trim_tail_impl(&bt, k)
+            .map(|(frame, range)| frame.symbols()[range].to_vec())
+            .collect()
+    };
+    let bt: BT = &[
+        &["std::rust_end_short_backtrace", "app::parse"],
+        &["app::main"],
+        &["runtime::poll", "runtime::block_on"],
+        &["std::rust_begin_short_backtrace"],
+    ];
+    assert_eq!(
+        trimmed(bt, 0),
+        vec![
+            vec!["app::parse"],
+            vec!["app::main"],
+            vec!["runtime::poll", "runtime::block_on"]
+        ]
+    );
+    assert_eq!(trimmed(bt, 1), vec![vec!["app::parse"], vec!["app::main"]]);
+    assert!(trimmed(bt, 3).is_empty());
+    assert!(trimmed(bt, 100).is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+fn test_signal_boundary() {
+    let bt: BT = &[
+        &["core::panicking::panic_fmt"],
+        &["app::on_sigsegv"],
+        &["__restore_rt"],
+        &["app::deref", "app::main"],
+    ];
+    let (frame, idx) = gunk::signal_trampoline_frame_impl(&bt).unwrap();
+    assert_eq!(frame[idx], "__restore_rt");
+    let not_signal: BT = &[&["app::__restore_rt_wrapper"], &["app::main"]];
+    assert!(gunk::signal_trampoline_frame_impl(&not_signal).is_none());
+
+    let formatter = ShortBacktraceFormatter::new().mark_signal_boundary(true);
+    assert_eq!(
+        formatter.format_impl(&bt),
+        "
+   0:        0x0 - core::panicking::panic_fmt
+   1:        0x0 - app::on_sigsegv
+   2:        0x0 - __restore_rt
+                (signal handler boundary)
+   3:        0x0 - app::deref
+                 - app::main"
+    );
+}
+
+#[test]
+fn test_crate_distribution() {
+    let bt: BT = &[
+        &["tokio::runtime::poll"],
+        &["core::option::Option<T>::unwrap", "app::parse"],
+        &["tokio::runtime::block_on"],
+        &["malloc"],
+        &["tokio::spawn"],
+    ];
+    assert_eq!(
+        stats::crate_distribution_impl(&bt),
+        vec![
+            ("tokio".to_owned(), 60.0),
+            ("<unknown>".to_owned(), 20.0),
+            ("app".to_owned(), 20.0),
+        ]
+    );
+    let bt: BT = &[];
+    assert!(stats::crate_distribution_impl(&bt).is_empty());
+}
+
+#[test]
+fn test_short_backtrace_jsonl() {
+    let bt: FakeBT = vec![
+        frame(
+            0x20,
+            vec![
+                sym("app::parse", "src/parse.rs", 10),
+                sym(
+                    "<app::Foo as core::fmt::Debug>::fmt",
+                    "C:\\app\\\"main\".rs",
+                    5,
+                ),
+            ],
+        ),
+        frame(0x30, vec![unknown_sym()]),
+        frame(0x40, vec![]),
+    ];
+    let lines: Vec<String> = json::short_backtrace_jsonl_impl(&bt).collect();
+    assert_eq!(
+        lines,
+        vec![
+            r#"{"index":0,"ip":"0x20","name":"app::parse","file":"src/parse.rs","line":10}"#,
+            r#"{"index":0,"ip":"0x20","name":"<app::Foo as core::fmt::Debug>::fmt","file":"C:\\app\\\"main\".rs","line":5}"#,
+            r#"{"index":1,"ip":"0x30","name":null,"file":null,"line":null}"#,
+            r#"{"index":2,"ip":"0x40","name":null,"file":null,"line":null}"#,
+        ]
+    );
+
+    let bt: BT = &[&["weird\n\u{1}name"]];
+    assert_eq!(
+        json::short_backtrace_jsonl_impl(&bt).next().unwrap(),
+        r#"{"index":0,"ip":"0x0","name":"weird\n\u0001name","file":null,"line":null}"#
+    );
+}
+
+#[test]
+fn test_nth_real_frame() {
+    let bt: FakeBT = vec![
+        frame(
+            0x10,
+            vec![sym("core::panicking::panic_fmt", "panicking.rs", 1)],
+        ),
+        frame(
+            0x20,
+            vec![
+                unknown_sym(),
+                sym("app::inner", "src/inner.rs", 10),
+                sym("app::outer", "src/lib.rs", 20),
+            ],
+        ),
+        frame(0x30, vec![unknown_sym()]),
+        frame(0x40, vec![]),
+        frame(0x50, vec![sym("app::main", "src/main.rs", 5)]),
+    ];
+    let nth = |n| query::nth_real_frame_impl(&bt, n).map(|(frame, idx)| (frame.ip, idx));
+    assert_eq!(nth(0), Some((0x20, 1)));
+    assert_eq!(nth(1), Some((0x50, 0)));
+    assert_eq!(nth(2), None);
+}
+
+#[test]
+fn test_format_frame_prefix() {
+    let bt: BT = &[&["app::parse"], &[], &["app::main"]];
+    let formatter = ShortBacktraceFormatter::new().frame_prefix("    at ");
+    assert_eq!(
+        formatter.format_impl(&bt),
+        "
+    at    0:        0x0 - app::parse
+    at    1:        0x0 - <unresolved>
+    at    2:        0x0 - app::main"
+    );
+    assert_eq!(
+        formatter
+            .clone()
+            .style(FormatStyle::SingleLine)
+            .format_impl(&bt),
+        "\n    at 0: app::parse\n    at 1: <unresolved>\n    at 2: app::main"
+    );
+    assert_eq!(
+        formatter.style(FormatStyle::GdbLike).format_impl(&bt),
+        "\n    at #0  app::parse ()\n    at #1  ?? ()\n    at #2  app::main ()"
+    );
+}
+
+#[test]
+#[cfg(feature = "test-util")]
+fn test_assert_golden_backtrace() {
+    let path =
+        std::env::temp_dir().join(format!("backtrace-ext-golden-{}.txt", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    // Written the first time
+    assert::assert_golden_backtrace_impl(&fake_trace(), &path, false);
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "app::inner\napp::outer\napp::main\n"
+    );
+    // Matches regardless of addresses and locations
+    let mut moved = fake_trace();
+    moved[1].ip = 0x1234;
+    moved[1].symbols[0].line = Some(99);
+    assert::assert_golden_backtrace_impl(&moved, &path, false);
+
+    let changed: BT = &[&["app::inner"], &["app::middle"], &["app::main"]];
+    let result = std::panic::catch_unwind(|| {
+        assert::assert_golden_backtrace_impl(&changed, &path, false);
+    });
+    let payload = result.unwrap_err();
+    let message = payload.downcast_ref::<String>().unwrap();
+    assert!(
+        message.ends_with("\n  app::inner\n- app::outer\n+ app::middle\n  app::main"),
+        "{}",
+        message
+    );
+
+    // Blessing overwrites it
+    assert::assert_golden_backtrace_impl(&changed, &path, true);
+    assert::assert_golden_backtrace_impl(&changed, &path, false);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_simplify_closures() {
+    let cases = [
+        ("app::main", "app::main"),
+        ("app::main::{{closure}}", "app::main::{closure}"),
+        (
+            "app::main::{{closure}}::{{closure}}::{{closure}}",
+            "app::main::{closure}",
+        ),
+        (
+            "app::main::{closure#0}::{closure#12}",
+            "app::main::{closure}",
+        ),
+        (
+            "<app::Foo as core::Bar>::run::{{closure}}",
+            "<app::Foo as core::Bar>::run::{closure}",
+        ),
+        ("app::closure_helper", "app::closure_helper"),
+    ];
+    for (name, simplified) in cases.iter() {
+        assert_eq!(names::simplify_closures(name), *simplified);
+    }
+
+    let bt: BT = &[&["app::run::<u32>::{{closure}}::{{closure}}"]];
+    let formatter = ShortBacktraceFormatter::new().simplify_closures(true);
+    assert_eq!(
+        formatter.format_impl(&bt),
+        "\n   0:        0x0 - app::run::<u32>::{closure}"
+    );
+    assert_eq!(
+        formatter.fold_generics(true).format_impl(&bt),
+        "\n   0:        0x0 - app::run::<_>::{closure}"
+    );
+}
+
+#[test]
+fn test_top_crate() {
+    let top = |bt: BT| query::top_crate_impl(&bt, "app");
+    let bt: BT = &[
+        &["core::panicking::panic_fmt"],
+        &["core::option::Option<T>::unwrap"],
+        &["serde::de::parse"],
+        &["app::load", "app::main"],
+    ];
+    assert_eq!(top(bt).as_deref(), Some("app"));
+    let bt: BT = &[
+        &["core::option::Option<T>::unwrap"],
+        &["malloc"],
+        &["std::sync::mutex::Mutex<T>::lock"],
+        &["serde::de::parse"],
+        &["tokio::run"],
+    ];
+    assert_eq!(top(bt).as_deref(), Some("serde"));
+    let bt: BT = &[
+        &["core::option::Option<T>::unwrap"],
+        &["std::rt::lang_start"],
+    ];
+    assert_eq!(top(bt), None);
+}
+
+#[test]
+fn test_format_highlight() {
+    let bt: BT = &[&["serde::de::parse"], &["app::poll_thing"], &["app::main"]];
+    let formatter = ShortBacktraceFormatter::new()
+        .highlight_substring("serde::")
+        .highlight_substring("poll");
+    assert_eq!(
+        formatter.format_impl(&bt),
+        "
+>  0:        0x0 - serde::de::parse
+>  1:        0x0 - app::poll_thing
+   2:        0x0 - app::main"
+    );
+    assert_eq!(
+        formatter
+            .clone()
+            .style(FormatStyle::SingleLine)
+            .format_impl(&bt),
+        "\n> 0: serde::de::parse\n> 1: app::poll_thing\n2: app::main"
+    );
+    assert_eq!(
+        formatter
+            .clone()
+            .style(FormatStyle::GdbLike)
+            .format_impl(&bt),
+        "\n> #0  serde::de::parse ()\n> #1  app::poll_thing ()\n#2  app::main ()"
+    );
+    let out = formatter.user_prefix("app").colorize(true).format_impl(&bt);
+    assert!(out.contains("0x0 - \x1b[7;2mserde::de::parse\x1b[0m"));
+    assert!(out.contains("0x0 - \x1b[32mapp::main\x1b[0m"));
+    assert!(!out.contains('>'));
+}
+
+#[test]
+#[cfg(feature = "regex")]
+fn test_format_highlight_regex() {
+    let bt: BT = &[&["serde::de::parse"], &["app::serde_glue"], &["app::main"]];
+    let formatter =
+        ShortBacktraceFormatter::new().highlight(regex::Regex::new("^serde::").unwrap());
+    assert_eq!(
+        formatter.format_impl(&bt),
+        "
+>  0:        0x0 - serde::de::parse
+   1:        0x0 - app::serde_glue
+   2:        0x0 - app::main"
+    );
+}
+
+#[test]
+fn test_format_caused_by() {
+    let formatter = ShortBacktraceFormatter::new().style(FormatStyle::CausedBy);
+    assert_eq!(
+        formatter.format_impl(&fake_trace()),
+        "
+app::main (src/main.rs:5)
+  caused by <unresolved>
+  caused by <unknown>
+  caused by app::inner (src/inner.rs:10)"
+    );
+    let bt: BT = &[&["app::parse"], &[], &["app::main"]];
+    assert_eq!(
+        formatter.format_impl(&bt),
+        "\napp::main\n  caused by <unresolved>\n  caused by app::parse"
+    );
+    let empty: BT = &[];
+    assert_eq!(formatter.format_impl(&empty), "");
+}
+
+#[test]
+fn test_self_check() {
+    let report = self_check();
+    assert!(report.is_ok(), "{:?}", report);
+    assert_eq!(report.to_string(), "short backtrace support: OK");
+
+    let bt: BT = &[
+        &["std::rust_end_short_backtrace"],
+        &["backtrace_ext::diagnostics::self_check_inner"],
+        &["backtrace_ext::diagnostics::self_check_outer"],
+        &["std::rust_begin_short_backtrace"],
+    ];
+    assert!(diagnostics::self_check_report_impl(&bt).is_ok());
+    let bt: BT = &[
+        &["backtrace_ext::diagnostics::self_check_inner"],
+        &["backtrace_ext::diagnostics::self_check_outer"],
+        &["std::rust_begin_short_backtrace"],
+    ];
+    let report = diagnostics::self_check_report_impl(&bt);
+    assert_eq!(
+        report,
+        SelfCheckReport {
+            end_marker: false,
+            begin_marker: true,
+            in_order: false,
+            frames_found: true,
+        }
+    );
+    assert_eq!(
+        report.to_string(),
+        "short backtrace support: degraded (no rust_end_short_backtrace marker)"
+    );
+}
+
+#[test]
+fn test_marker_boundary() {
+    let clamp = |bt: BT, boundary| -> Vec<(Vec<&str>, Range<usize>)> {
+        adapters::short_frames_with_boundary_impl(&bt, boundary)
+            .map(|(frame, range)| (frame.to_vec(), range))
+            .collect()
+    };
+    // Clamping at the subframe never yields a frame with none of its symbols, so it
+    // always comes out the same as moving to the adjacent frame
+    let both = |bt: BT| {
+        let adjacent = clamp(bt, MarkerBoundary::AdjacentFrame);
+        let subframe = clamp(bt, MarkerBoundary::Subframe);
+        assert!(subframe.iter().all(|(_, range)| !range.is_empty()));
+        assert_eq!(adjacent, subframe);
+        adjacent
+    };
+    const END: &str = "rust_end_short_backtrace";
+    const BEGIN: &str = "rust_begin_short_backtrace";
+
+    // Markers alone in their frames
+    let bt: BT = &[&["junk"], &[END], &["real", "also"], &[BEGIN], &["junk"]];
+    assert_eq!(both(bt), [(vec!["real", "also"], 0..2)]);
+    // The default is what short_frames_strict does
+    let strict: Vec<_> = short_frames_strict_impl(&bt)
+        .map(|(frame, range)| (frame.to_vec(), range))
+        .collect();
+    assert_eq!(strict, clamp(bt, MarkerBoundary::default()));
+
+    // Markers as the last and first symbols of frames with other symbols
+    let bt: BT = &[&["junk", END], &["real"], &[BEGIN, "junk"]];
+    assert_eq!(both(bt), [(vec!["real"], 0..1)]);
+
+    // Markers in the middle of frames
+    let bt: BT = &[&["junk", END, "real"], &["more"], &["real", BEGIN, "junk"]];
+    assert_eq!(
+        both(bt),
+        [
+            (vec!["junk", END, "real"], 2..3),
+            (vec!["more"], 0..1),
+            (vec!["real", BEGIN, "junk"], 0..1),
+        ]
+    );
+
+    // Only one side at the edge
+    let bt: BT = &[&[END], &["real", BEGIN]];
+    assert_eq!(both(bt), [(vec!["real", BEGIN], 0..1)]);
+    let bt: BT = &[&[END, "real"], &[BEGIN]];
+    assert_eq!(both(bt), [(vec![END, "real"], 1..2)]);
+
+    // Only one marker
+    let bt: BT = &[&["junk", END], &["real"]];
+    assert_eq!(both(bt), [(vec!["real"], 0..1)]);
+    let bt: BT = &[&["real"], &[BEGIN]];
+    assert_eq!(both(bt), [(vec!["real"], 0..1)]);
+
+    // Nothing between the markers
+    assert_eq!(both(&[&[END], &[BEGIN]]), []);
+    assert_eq!(both(&[&["junk", END], &[BEGIN, "junk"]]), []);
+    assert_eq!(both(&[&[END, BEGIN]]), []);
+    assert_eq!(both(&[&[BEGIN], &["real"]]), []);
+    assert_eq!(both(&[&["real"], &[END]]), []);
+    // Out of order markers are ignored
+    let bt: BT = &[&[BEGIN], &["real"], &[END]];
+    assert_eq!(both(bt).len(), 3);
+}
+
+#[test]
+fn test_unique_frames() {
+    let bt: BT = &[
+        &["app::visit"],
+        &["app::visit", "app::walk"],
+        &["app::visit"],
+        &["core::panicking::panic_fmt"],
+        &[],
+        &["app::walk"],
+        &["app::main"],
+    ];
+    assert_eq!(
+        summary::unique_frames_impl(&bt),
+        ["app::visit", "app::walk", "app::main"]
+    );
+    let empty: BT = &[];
+    assert!(summary::unique_frames_impl(&empty).is_empty());
+}
+
+#[test]
+fn test_distinguishing_frames() {
+    let bt: BT = &[&["app::parse"], &["app::load"], &["app::main"]];
+    let others: &[BT] = &[
+        &[&["app::parse"], &["app::main"]],
+        &[&["app::load"], &["app::main"]],
+        // The same names can't be told apart, so this one doesn't matter
+        &[&["app::main"], &["app::load"], &["app::parse"]],
+        &[&["app::other"]],
+    ];
+    assert_eq!(
+        summary::distinguishing_frames_impl(&bt, others),
+        ["app::parse", "app::load"]
+    );
+
+    // One name can be enough, even if it isn't the first
+    let bt: BT = &[&["app::visit"], &["app::main"]];
+    let others: &[BT] = &[&[&["app::visit"]], &[&["app::visit"], &["app::run"]]];
+    assert_eq!(
+        summary::distinguishing_frames_impl(&bt, others),
+        ["app::main"]
+    );
+    assert!(summary::distinguishing_frames_impl(&bt, &[]).is_empty());
+}
+
+#[test]
+fn test_contextual_backtrace() {
+    let context = vec![
+        ("request".to_owned(), "4f2a".to_owned()),
+        ("user".to_owned(), "say \"hi\"".to_owned()),
+    ];
+    let bt: BT = &[&["app::parse"], &["app::main"]];
+    let formatter = ShortBacktraceFormatter::new().style(FormatStyle::SingleLine);
+    assert_eq!(
+        context::format_contextual_impl(&context, &formatter, &bt),
+        "\nrequest: 4f2a\nuser: say \"hi\"\n0: app::parse\n1: app::main"
+    );
+    let lines: Vec<String> = context::contextual_jsonl_impl(&context, &bt).collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(
+        lines[0],
+        r#"{"context":{"request":"4f2a","user":"say \"hi\""}}"#
+    );
+    assert!(lines[1].contains(r#""name":"app::parse""#));
+
+    assert_eq!(
+        context::contextual_jsonl_impl(&[], &bt).next().unwrap(),
+        r#"{"context":{}}"#
+    );
+}
+
+#[test]
+fn test_parse_proc_maps() {
+    let maps = "\
+55d4159a3000-55d4159a4000 r--p 00000000 fd:01 1234                       /usr/bin/my app
+55d4159a4000-55d4159a6000 r-xp 00001000 fd:01 1234                       /usr/bin/my app
+55d4159a8000-55d4159a9000 rw-p 00000000 00:00 0                          [heap]
+7f089ce40000-7f089ce6e000 r-xp 00028000 fd:01 5678                       /usr/lib/libc.so.6
+7f089ce70000-7f089ce71000 rw-p 00000000 00:00 0 
+7ffd1b5f0000-7ffd1b5f2000 r-xp 00000000 00:00 0                          [vdso]
+";
+    let modules = offsets::parse_proc_maps(maps);
+    assert_eq!(
+        modules,
+        [
+            offsets::MappedModule {
+                range: 0x55d4159a3000..0x55d4159a4000,
+                name: "/usr/bin/my app".into(),
+                base: 0x55d4159a3000,
+            },
+            offsets::MappedModule {
+                range: 0x55d4159a4000..0x55d4159a6000,
+                name: "/usr/bin/my app".into(),
+                base: 0x55d4159a3000,
+            },
+            offsets::MappedModule {
+                range: 0x7f089ce40000..0x7f089ce6e000,
+                name: "/usr/lib/libc.so.6".into(),
+                base: 0x7f089ce18000,
+            },
+        ]
+    );
+
+    let bt = vec![
+        frame(0x55d4159a4b2f, vec![sym("app::main", "src/main.rs", 5)]),
+        frame(0x7f089ce4d24a, vec![sym("__libc_start_main", "libc.c", 1)]),
+        frame(0x1234, vec![]),
+    ];
+    let infos: Vec<_> = offsets::short_frames_with_modules_impl(&bt, &modules)
+        .map(|(_, _, info)| info)
+        .collect();
+    assert_eq!(
+        infos,
+        [
+            Some(ModuleInfo {
+                name: "/usr/bin/my app".into(),
+                base: 0x55d4159a3000,
+            }),
+            Some(ModuleInfo {
+                name: "/usr/lib/libc.so.6".into(),
+                base: 0x7f089ce18000,
+            }),
+            None,
+        ]
+    );
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_short_frames_with_modules() {
+    let backtrace = Backtrace::new();
+    let exe = std::env::current_exe().unwrap();
+    let first = short_frames_with_modules(&backtrace).next().unwrap();
+    let info = first.2.unwrap();
+    assert_eq!(info.name.file_name(), exe.file_name());
+    assert!(info.base <= first.0.ip() as usize);
+}
+
+#[test]
+fn test_same_call_site() {
+    use recursion::same_call_site_impl as same;
+
+    // `helper` inlined into `run`'s frame, and the same call in a frame of its own
+    let inlined = frame(
+        0x10,
+        vec![
+            sym("app::helper", "src/lib.rs", 7),
+            sym("app::run", "src/lib.rs", 20),
+        ],
+    );
+    let standalone = frame(0x80, vec![sym("app::helper", "src/lib.rs", 7)]);
+    assert!(same(&inlined.symbols[0], &standalone.symbols[0]));
+    assert!(!same(&inlined.symbols[0], &inlined.symbols[1]));
+
+    // Same function, called from a different line
+    assert!(!same(
+        &sym("app::helper", "src/lib.rs", 7),
+        &sym("app::helper", "src/lib.rs", 8)
+    ));
+    assert!(!same(
+        &sym("app::helper", "src/lib.rs", 7),
+        &sym("app::helper", "src/main.rs", 7)
+    ));
+    // Columns don't matter
+    let mut with_col = sym("app::helper", "src/lib.rs", 7);
+    with_col.col = Some(5);
+    assert!(same(&with_col, &sym("app::helper", "src/lib.rs", 7)));
+
+    // Unnamed symbols never match, named ones without a location only match each other
+    assert!(!same(&unknown_sym(), &unknown_sym()));
+    let no_location = FakeSymbol {
+        name: Some("app::helper"),
+        ..unknown_sym()
+    };
+    assert!(same(&no_location, &no_location));
+    assert!(!same(&no_location, &sym("app::helper", "src/lib.rs", 7)));
+}
+
+#[test]
+fn test_middle_truncate_names() {
+    let cases = [
+        (
+            "app::very::deeply::nested::module::parse",
+            27,
+            "app::very::de…module::parse",
+        ),
+        ("app::parse", 10, "app::parse"),
+        ("app::parse", 9, "app:…arse"),
+        ("app::ünïcödé::parse", 8, "app…arse"),
+        ("app::parse", 1, "…"),
+        ("app::parse", 0, ""),
+        ("", 0, ""),
+    ];
+    for (name, max, truncated) in cases.iter() {
+        assert_eq!(names::middle_truncate_name(name, *max), *truncated);
+    }
+
+    let bt: BT = &[
+        &["app::very::deeply::nested::module::parse"],
+        &["app::main"],
+    ];
+    let formatter = ShortBacktraceFormatter::new().middle_truncate_names(27);
+    assert_eq!(
+        formatter.format_impl(&bt),
+        "
+   0:        0x0 - app::very::de…module::parse
+   1:        0x0 - app::main"
+    );
+}
+
+#[test]
+fn test_short_range_with_context() {
+    let context = short_range_with_context_impl(&fake_trace());
+    assert_eq!(
+        context,
+        ShortRangeContext {
+            frames: 1..5,
+            hidden_before: 1,
+            hidden_after: 1,
+        }
+    );
+
+    let bt: BT = &[
+        &["panic"],
+        &["rust_end_short_backtrace"],
+        &["rust_begin_short_backtrace"],
+        &["start"],
+    ];
+    let context = short_range_with_context_impl(&bt);
+    assert!(context.frames.is_empty());
+    assert_eq!(context.hidden_before + context.hidden_after, 4);
+
+    let bt: BT = &[&["app::main"]];
+    let context = short_range_with_context_impl(&bt);
+    assert_eq!(
+        (context.frames, context.hidden_before, context.hidden_after),
+        (0..1, 0, 0)
+    );
+}
+
+#[test]
+fn test_format_with_offsets() {
+    let bt: BT = &[&["app::parse"], &[], &["app::inline", "app::main"]];
+    let formatter = ShortBacktraceFormatter::new();
+    let (out, offsets) = formatter.format_with_offsets_impl(&bt);
+    assert_eq!(out, formatter.format_impl(&bt));
+    assert_eq!(offsets.len(), 3);
+    assert!(out[offsets[0]..].starts_with("\n   0:"));
+    assert!(out[offsets[1]..].starts_with("\n   1:"));
+    assert!(out[offsets[2]..].starts_with("\n   2:"));
+
+    // Offsets are into the final output, after line endings and indentation
+    let formatter = formatter
+        .header(Some("stack backtrace:".to_owned()))
+        .frame_separator(Some("--".to_owned()))
+        .line_ending(LineEnding::CrLf)
+        .indent(2)
+        .style(FormatStyle::SingleLine);
+    let (out, offsets) = formatter.format_with_offsets_impl(&bt);
+    assert_eq!(out, formatter.format_impl(&bt));
+    assert_eq!(offsets.len(), 3);
+    assert!(out[offsets[0]..].starts_with("\r\n  0: app::parse\r\n"));
+    assert!(out[offsets[1]..].starts_with("\r\n  1: <unresolved>\r\n"));
+    assert!(out[offsets[2]..].starts_with("\r\n  2: app::inline"));
+
+    // Collapsed frames don't get an offset
+    let formatter = ShortBacktraceFormatter::new().collapse_unresolved_runs(true);
+    let bt: BT = &[&["app::parse"], &[], &[], &["app::main"]];
+    let (out, offsets) = formatter.format_with_offsets_impl(&bt);
+    assert_eq!(offsets.len(), 2);
+    assert!(out[offsets[1]..].starts_with("\n   3:"));
+}
+
+#[test]
+fn test_short_backtrace_format_trait() {
+    let backtrace = Backtrace::new();
+    let formats: Vec<Box<dyn ShortBacktraceFormat>> = vec![
+        Box::new(DefaultShortBacktraceFormat),
+        Box::new(ShortBacktraceFormatter::new()),
+        Box::new(|backtrace: &Backtrace| ShortBacktraceFormatter::new().format(backtrace)),
+    ];
+    let expected = ShortBacktraceFormatter::new().format(&backtrace);
+    for format in &formats {
+        assert_eq!(format.format(&backtrace), expected);
+    }
+
+    let configured: Box<dyn ShortBacktraceFormat> = Box::new(
+        ShortBacktraceFormatter::new().address_format(AddressFormat::ZeroPaddedHex { digits: 20 }),
+    );
+    assert_ne!(configured.format(&backtrace), expected);
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn test_serializable_short_backtrace() {
+    let serializable = serialize::SerializableShortBacktrace::new_impl(&fake_trace());
+    let json = serde_json::to_string(&serializable).unwrap();
+    assert_eq!(
+        json,
+        r#"{"frames":[{"ip":"0x20","symbols":[{"name":"app::inner","file":"src/inner.rs","line":10},{"name":"app::outer","file":"src/lib.rs","line":20}]},{"ip":"0x30","symbols":[{"name":null,"file":null,"line":null}]},{"ip":"0x40","symbols":[]},{"ip":"0x50","symbols":[{"name":"app::main","file":"src/main.rs","line":5}]}]}"#
+    );
+    let back: SerializableShortBacktrace = serde_json::from_str(&json).unwrap();
+    assert_eq!(back, serializable);
+
+    let backtrace = Backtrace::new();
+    let serializable = SerializableShortBacktrace::from(&backtrace);
+    assert_eq!(
+        serializable.frames.len(),
+        short_frames_strict(&backtrace).len()
+    );
+    let json = serde_json::to_string(&serializable).unwrap();
+    let back: SerializableShortBacktrace = serde_json::from_str(&json).unwrap();
+    assert_eq!(back, serializable);
+}
+
+#[test]
+fn test_short_frames_only_from() {
+    let bt: BT = &[
+        &["rust_end_short_backtrace", "rust_begin_unwind"],
+        &["core::panicking::panic_fmt"],
+        &[
+            "std::io::read",
+            "app::inner",
+            "core::iter::next",
+            "mylib::parse",
+        ],
+        &["app::main", "std::rt::lang_start"],
+        &[],
+        &["application::main"],
+        &["rust_begin_short_backtrace"],
+    ];
+    let frames: Vec<_> = gunk::short_frames_only_from_impl(&bt, &["app::", "mylib::"])
+        .map(|(frame, range)| frame[range].to_vec())
+        .collect();
+    assert_eq!(
+        frames,
+        vec![
+            vec!["app::inner", "core::iter::next", "mylib::parse"],
+            vec!["app::main"],
+        ]
+    );
+
+    // Real symbols are mangled, so this checks the prefix is matched after demangling
+    let backtrace = capture_in_helper();
+    let frames: Vec<_> = short_frames_only_from(&backtrace, &["backtrace_ext::"]).collect();
+    assert!(!frames.is_empty());
+    assert!(frames.iter().all(|short| {
+        let name = demangled_name(&short.symbols()[0]).unwrap();
+        name.starts_with("backtrace_ext::")
+    }));
+}
+
+#[test]
+fn test_parse_std_backtrace() {
+    let full = "   0:     0x55d4159a3a10 - std::backtrace::Backtrace::create::h0123456789abcdef
+   1:     0x55d4159a3a20 - std::sys::backtrace::__rust_end_short_backtrace::h0123456789abcdef
+   2:     0x55d4159a3b2f - app::parse::h0123456789abcdef
+                               at ./src/parse.rs:10:5
+   3:     0x55d4159a3b2f - app::run::h0123456789abcdef
+                               at ./src/main.rs:20:9
+   4:     0x55d4159a3c40 - <unknown>
+   5:     0x55d4159a3d50 - app::recurse::h0123456789abcdef
+                               at ./src/main.rs:30:5
+   6:     0x55d4159a3d50 - app::recurse::h0123456789abcdef
+                               at ./src/main.rs:30:5
+   7:     0x55d4159a3e60 - std::sys::backtrace::__rust_begin_short_backtrace::h0123456789abcdef
+   8:     0x55d4159a3f70 - std::rt::lang_start_internal::h0123456789abcdef";
+    let frames = parse_std_backtrace(full);
+    let summary: Vec<_> = frames
+        .iter()
+        .map(|frame| {
+            let names: Vec<_> = frame
+                .symbols
+                .iter()
+                .map(|symbol| symbol.name.as_deref())
+                .collect();
+            (frame.index, frame.ip, names)
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (
+                2,
+                Some(0x55d4159a3b2f),
+                vec![Some("app::parse"), Some("app::run")]
+            ),
+            (4, Some(0x55d4159a3c40), vec![None]),
+            (5, Some(0x55d4159a3d50), vec![Some("app::recurse")]),
+            (6, Some(0x55d4159a3d50), vec![Some("app::recurse")]),
+        ]
+    );
+    assert_eq!(frames[0].symbols[1].file, Some("./src/main.rs".into()));
+    assert_eq!(frames[0].symbols[1].line, Some(20));
+    assert_eq!(frames[0].symbols[1].col, Some(9));
+
+    // Without addresses every symbol is a frame of its own
+    let short = "   0: app::parse
+             at ./src/parse.rs:10:5
+   1: app::run
+             at ./src/main.rs:20:9
+   2: std::sys::backtrace::__rust_begin_short_backtrace";
+    let names: Vec<_> = parse_std_backtrace(short)
+        .into_iter()
+        .map(|frame| (frame.index, frame.ip, frame.symbols.len()))
+        .collect();
+    assert_eq!(names, vec![(0, None, 1), (1, None, 1)]);
+
+    assert_eq!(parse_std_backtrace("disabled backtrace"), vec![]);
+}
+
+#[cfg(feature = "std-backtrace")]
+#[clippy::msrv = "1.65"]
+#[inline(never)]
+fn capture_std_in_helper() -> std::backtrace::Backtrace {
+    let backtrace = std::backtrace::Backtrace::force_capture();
+    // Do something after capturing, so this isn't a tail call
+    assert_eq!(
+        backtrace.status(),
+        std::backtrace::BacktraceStatus::Captured
+    );
+    backtrace
+}
+
+#[cfg(feature = "std-backtrace")]
+#[test]
+fn test_from_std_backtrace() {
+    let frames = from_std_backtrace(&capture_std_in_helper());
+    let names: Vec<_> = frames
+        .iter()
+        .flat_map(|frame| &frame.symbols)
+        .filter_map(|symbol| symbol.name.as_deref())
+        .collect();
+    assert!(
+        names
+            .iter()
+            .any(|name| name.ends_with("::capture_std_in_helper")),
+        "{:?}",
+        names
+    );
+    assert!(!names
+        .iter()
+        .any(|name| name.contains("rust_begin_short_backtrace")));
+}
+
+#[test]
+fn test_format_max_frames() {
+    let bt: BT = &[
+        &["app::a"],
+        &["app::b"],
+        &["app::c"],
+        &["app::d"],
+        &["app::e"],
+        &["app::f"],
+    ];
+    let formatter = ShortBacktraceFormatter::new().max_frames(2, 1);
+    assert_eq!(
+        formatter.format_impl(&bt),
+        "
+   0:        0x0 - app::a
+   1:        0x0 - app::b
+      ... 3 frames hidden ...
+   5:        0x0 - app::f"
+    );
+
+    let formatter = ShortBacktraceFormatter::new()
+        .style(FormatStyle::GdbLike)
+        .max_frames(0, 1);
+    assert_eq!(
+        formatter.format_impl(&bt),
+        "\n      ... 5 frames hidden ...\n#5  app::f ()"
+    );
+
+    // Hidden macro frames don't count
+    let mut trace = fake_trace();
+    trace.insert(
+        2,
+        frame(0x28, vec![sym("app::expanded", "<proc-macro>", 1)]),
+    );
+    let formatter = ShortBacktraceFormatter::new()
+        .macro_frames(MacroFramePolicy::Hide)
+        .max_frames(3, 1);
+    assert!(!formatter.format_impl(&trace).contains("hidden"));
+    let formatter = formatter.max_frames(2, 1);
+    assert!(formatter
+        .format_impl(&trace)
+        .contains("\n      ... 1 frames hidden ...\n"));
+
+    let formatter = ShortBacktraceFormatter::new().max_frames(usize::MAX, usize::MAX);
+    assert_eq!(formatter.format_impl(&fake_trace()), FAKE_TRACE_FORMATTED);
+    let formatter = ShortBacktraceFormatter::new().max_frames(4, 0);
+    assert_eq!(formatter.format_impl(&fake_trace()), FAKE_TRACE_FORMATTED);
+}